@@ -0,0 +1,6 @@
+use std::convert::TryFrom;
+
+// Integer power, `None` on overflow. The exponent must not be negative.
+pub(crate) fn checked_pow(base: i64, exp: i64) -> Option<i64> {
+    base.checked_pow(u32::try_from(exp).ok()?)
+}
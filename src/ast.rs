@@ -0,0 +1,596 @@
+//! An owned representation of a parsed expression, to inspect its structure without rolling it.
+//!
+//! ```
+//! use caith::ast::{parse, Command, Expr, Operator};
+//!
+//! let ast = parse("2d6 K1 + 3 : attack").unwrap();
+//! assert_eq!(Some("attack".to_owned()), ast.reason);
+//! if let Command::Expr(Expr::BinOp { op, .. }) = ast.command {
+//!     assert_eq!(Operator::Add, op);
+//! }
+//! ```
+
+use pest::{iterators::Pair, Parser};
+
+use crate::{
+    error::{Result, RollError},
+    parser::{
+        check_options, extract_comparison, extract_crit, fractional_dice_error, get_climber,
+        parse_attempts, parse_named_dice, parse_number, parse_per_die, to_comparator, RollParser,
+        Rule, DEFAULT_MAX_DEPTH,
+    },
+    Comparator,
+};
+
+/// A parsed query: the expression and its reason
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ast {
+    /// What to roll
+    pub command: Command,
+    /// The comment after `:`, if any
+    pub reason: Option<String>,
+}
+
+/// The root of a query: a single expression or a repeated one
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Command {
+    /// A single expression
+    Expr(Expr),
+    /// An expression repeated with the `^` operator
+    Repeated {
+        /// The expression to repeat
+        expr: Expr,
+        /// How many times the expression is rolled
+        times: u64,
+        /// What to do with the results
+        kind: Repetition,
+    },
+}
+
+/// The kind of repetition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Repetition {
+    /// `^`: list all the results
+    Plain,
+    /// `^+`: sum all the results
+    Sum,
+    /// `^#`: sort the results by total
+    Sort,
+}
+
+/// A node of an expression
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    /// An integer constant
+    Integer(i64),
+    /// A float constant
+    Float(f64),
+    /// A dice term, like `2d6 K1`
+    Dice(Dice),
+    /// An expression between parenthesis
+    Block(Box<Expr>),
+    /// Dice which number is rolled first, like `(1d4)d6`. The `count` of `dice` is 0.
+    RolledCountDice {
+        /// Expression giving the number of dice
+        count: Box<Expr>,
+        /// The dice rolled
+        dice: Dice,
+    },
+    /// Dice with named faces registered on the roller, like `2d{story}`
+    NamedDice {
+        /// Number of dice
+        count: u64,
+        /// Name of the die
+        name: String,
+    },
+    /// `best(count, expr)`: the highest total of `count` rolls of an expression
+    Best {
+        /// Number of rolls
+        count: u64,
+        /// Expression rolled
+        expr: Box<Expr>,
+    },
+    /// `worst(count, expr)`: the lowest total of `count` rolls of an expression
+    Worst {
+        /// Number of rolls
+        count: u64,
+        /// Expression rolled
+        expr: Box<Expr>,
+    },
+    /// An operation between two expressions
+    BinOp {
+        /// The operator
+        op: Operator,
+        /// Left operand
+        lhs: Box<Expr>,
+        /// Right operand
+        rhs: Box<Expr>,
+    },
+}
+
+/// An arithmetic operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operator {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `^`
+    Pow,
+}
+
+/// A dice term
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dice {
+    /// Number of dice to roll, 1 if omitted in the expression
+    pub count: u64,
+    /// The kind of dice
+    pub sides: Sides,
+    /// Options, in the order of the expression
+    pub options: Vec<DiceOption>,
+}
+
+/// The kind of dice rolled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sides {
+    /// A die with this number of sides
+    Number(u64),
+    /// A Fudge die
+    Fudge,
+}
+
+/// An option of a dice term
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiceOption {
+    /// `(+# each)`: added to each die rolled first, which can't go under 1. Always the first option.
+    EachModifier(i64),
+    /// `e#` or `!o#`: explode once
+    Explode {
+        /// Minimum value to explode
+        value: u64,
+        /// Exploded dice go to the bonus tally
+        bonus: bool,
+    },
+    /// `ie#` or `!#`: explode indefinitely
+    IndefiniteExplode {
+        /// Minimum value to explode, the number of sides if `None`
+        value: Option<u64>,
+        /// Exploded dice go to the bonus tally
+        bonus: bool,
+    },
+    /// `r#`: reroll once the dice lower or equal to the value
+    Reroll(u64),
+    /// `ir#`: reroll indefinitely the dice lower or equal to the value
+    IndefiniteReroll(u64),
+    /// `ar#`: reroll once the dice lower or equal to the value, adding the new die to the pool
+    AddReroll(u64),
+    /// `rp<#`, `rp<=#`...: reroll all the dice once if the total satisfies the comparison
+    RerollPool(Comparator, u64),
+    /// `ro#h`: reroll once the dice lower or equal to the value, keeping the higher of the two
+    RerollKeepHi(u64),
+    /// `ro#l`: reroll once the dice lower or equal to the value, keeping the lower of the two
+    RerollKeepLo(u64),
+    /// `K#`: keep the highest dice
+    KeepHi(u64),
+    /// `k#`: keep the lowest dice
+    KeepLo(u64),
+    /// `k[...]`: keep the dice at these positions
+    KeepIndices(Vec<u64>),
+    /// `k<#`, `k>=#`...: keep the dice satisfying the comparison
+    KeepCompare(Comparator, u64),
+    /// `d<#`, `d<=#`...: drop the dice satisfying the comparison
+    DropCompare(Comparator, u64),
+    /// `med`: keep only the median die, the lower middle one for an even number of dice
+    Median,
+    /// `D#`: drop the highest dice
+    DropHi(u64),
+    /// `d#`: drop the lowest dice
+    DropLo(u64),
+    /// `cs#`, `cs>#` or `cs<#`: the dice marked as critical success, the bounds being included
+    CritSuccess(Comparator, u64),
+    /// `cf#`, `cf>#` or `cf<#`: the dice marked as critical failure, the bounds being included
+    CritFailure(Comparator, u64),
+    /// `t#`: minimum value to count a success
+    Target(u64),
+    /// `t[...]`: values counting as a success
+    TargetEnum(Vec<u64>),
+    /// `tt#`: minimum value to count two successes
+    DoubleTarget(u64),
+    /// `f#`: maximum value to count a failure
+    Failure(u64),
+    /// `<#`, `<=#`, `>#`, `>=#` or `==#`: count a success for each die satisfying the comparison
+    Compare(Comparator, u64),
+    /// `<#b`, `>=#b`...: like `Compare`, but each 1 rolled cancels a success, including a 1
+    /// satisfying the comparison
+    CompareBotch(Comparator, u64),
+}
+
+/// Parse an expression without rolling it.
+///
+/// The errors that can be found without rolling are reported, like contradictory options. Like
+/// when rolling, an expression with more than 64 nested parentheses is rejected with
+/// [`RollError::TooDeep`].
+pub fn parse(input: &str) -> Result<Ast> {
+    let mut pairs = RollParser::parse(Rule::command, input)?;
+    let command = pairs.next().unwrap();
+    let command = match command.as_rule() {
+        Rule::expr => Command::Expr(build_expr(command, 0)?),
+        Rule::repeated_expr => {
+            let mut inner = command.into_inner();
+            let expr = build_expr(inner.next().unwrap(), 0)?;
+            let maybe_option = inner.next().unwrap();
+            let (times, kind) = match maybe_option.as_rule() {
+                Rule::number => (maybe_option, Repetition::Plain),
+                Rule::add => (inner.next().unwrap(), Repetition::Sum),
+                Rule::sort => (inner.next().unwrap(), Repetition::Sort),
+                _ => unreachable!("{:?}", maybe_option),
+            };
+            let times = parse_number(&times)?;
+            if times == 0 {
+                return Err("Can't repeat 0 times or negatively".into());
+            }
+            Command::Repeated { expr, times, kind }
+        }
+        _ => unreachable!("{:?}", command),
+    };
+    let reason = pairs
+        .next()
+        .filter(|p| p.as_rule() == Rule::reason)
+        .map(|p| p.as_str()[1..].trim().to_owned());
+    Ok(Ast { command, reason })
+}
+
+fn build_expr(expr: Pair<Rule>, depth: usize) -> Result<Expr> {
+    if depth > DEFAULT_MAX_DEPTH {
+        return Err(RollError::TooDeep(DEFAULT_MAX_DEPTH));
+    }
+    get_climber().climb(
+        expr.into_inner(),
+        |pair: Pair<Rule>| match pair.as_rule() {
+            Rule::integer => pair
+                .as_str()
+                .replace(' ', "")
+                .parse::<i64>()
+                .map(Expr::Integer)
+                .map_err(|e| format!("Invalid number `{}`: {}", pair.as_str(), e).into()),
+            Rule::float => Ok(Expr::Float(
+                pair.as_str().replace(' ', "").parse::<f64>().unwrap(),
+            )),
+            Rule::block_expr => Ok(Expr::Block(Box::new(build_expr(
+                pair.into_inner().next().unwrap(),
+                depth + 1,
+            )?))),
+            Rule::best | Rule::worst => {
+                let rule = pair.as_rule();
+                let mut inner = pair.into_inner();
+                let count = parse_attempts(&inner.next().unwrap())?;
+                let expr = Box::new(build_expr(inner.next().unwrap(), depth + 1)?);
+                Ok(if rule == Rule::best {
+                    Expr::Best { count, expr }
+                } else {
+                    Expr::Worst { count, expr }
+                })
+            }
+            Rule::dice => match pair.clone().into_inner().next().unwrap() {
+                count if count.as_rule() == Rule::dice_count => {
+                    let expr = count
+                        .into_inner()
+                        .next()
+                        .unwrap()
+                        .into_inner()
+                        .next()
+                        .unwrap();
+                    Ok(Expr::RolledCountDice {
+                        count: Box::new(build_expr(expr, depth + 1)?),
+                        dice: build_dice(pair)?,
+                    })
+                }
+                _ => build_dice(pair).map(Expr::Dice),
+            },
+            Rule::fractional_dice => Err(fractional_dice_error(&pair)),
+            Rule::named_dice => {
+                let (count, name) = parse_named_dice(pair)?;
+                Ok(Expr::NamedDice { count, name })
+            }
+            Rule::percent => Ok(Expr::Dice(Dice {
+                count: 1,
+                sides: Sides::Number(100),
+                options: vec![DiceOption::Compare(
+                    Comparator::Le,
+                    parse_number(&pair.into_inner().next().unwrap())?,
+                )],
+            })),
+            _ => unreachable!("{:?}", pair),
+        },
+        |lhs: Result<Expr>, op: Pair<Rule>, rhs: Result<Expr>| {
+            let op = match op.as_rule() {
+                Rule::add => Operator::Add,
+                Rule::sub => Operator::Sub,
+                Rule::mul => Operator::Mul,
+                Rule::div => Operator::Div,
+                Rule::pow => Operator::Pow,
+                _ => unreachable!("{:?}", op),
+            };
+            Ok(Expr::BinOp {
+                op,
+                lhs: Box::new(lhs?),
+                rhs: Box::new(rhs?),
+            })
+        },
+    )
+}
+
+fn build_dice(dice: Pair<Rule>) -> Result<Dice> {
+    let mut inner = dice.into_inner().peekable();
+    let count = match inner.peek().map(|p| p.as_rule()) {
+        Some(Rule::nb_dice) => parse_number(&inner.next().unwrap())?,
+        Some(Rule::dice_count) => {
+            inner.next();
+            0
+        }
+        _ => 1,
+    };
+    inner.next(); // skip `d` token
+    let sides = inner.next().unwrap();
+    let sides = match sides.as_rule() {
+        Rule::number => Sides::Number(parse_number(&sides)?),
+        Rule::fudge => Sides::Fudge,
+        _ => unreachable!("{:?}", sides),
+    };
+    // a rolled number of dice is checked once rolled
+    let nb = if count == 0 { u64::MAX } else { count };
+    match sides {
+        Sides::Number(sides) => check_options(nb, sides, false, inner.clone())?,
+        Sides::Fudge => check_options(nb, 6, true, inner.clone())?,
+    }
+    let options = inner
+        .map(|option| build_option(option, sides))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Dice {
+        count,
+        sides,
+        options,
+    })
+}
+
+fn build_option(option: Pair<Rule>, sides: Sides) -> Result<DiceOption> {
+    let rule = option.as_rule();
+    let bonus = option
+        .clone()
+        .into_inner()
+        .any(|p| p.as_rule() == Rule::bonus);
+    let numbers = option
+        .clone()
+        .into_inner()
+        .filter(|p| p.as_rule() == Rule::number)
+        .map(|p| parse_number(&p))
+        .collect::<Result<Vec<_>>>()?;
+    let value = numbers.first().copied();
+    Ok(match rule {
+        // `!o` explodes on the max value, a Fudge die being rolled as a d6
+        Rule::explode => DiceOption::Explode {
+            value: value.unwrap_or(match sides {
+                Sides::Number(sides) => sides,
+                Sides::Fudge => 6,
+            }),
+            bonus,
+        },
+        Rule::i_explode => DiceOption::IndefiniteExplode { value, bonus },
+        Rule::reroll => DiceOption::Reroll(value.unwrap()),
+        Rule::i_reroll => DiceOption::IndefiniteReroll(value.unwrap()),
+        Rule::add_reroll => DiceOption::AddReroll(value.unwrap()),
+        Rule::reroll_hi => DiceOption::RerollKeepHi(value.unwrap()),
+        Rule::reroll_lo => DiceOption::RerollKeepLo(value.unwrap()),
+        Rule::keep_hi => DiceOption::KeepHi(value.unwrap()),
+        Rule::keep_lo => DiceOption::KeepLo(value.unwrap()),
+        Rule::keep_idx => DiceOption::KeepIndices(numbers),
+        Rule::per_die => DiceOption::EachModifier(parse_per_die(&option)?),
+        Rule::keep_cmp => {
+            let (comparator, value) = extract_comparison(option)?;
+            DiceOption::KeepCompare(comparator, value)
+        }
+        Rule::drop_cmp => {
+            let (comparator, value) = extract_comparison(option)?;
+            DiceOption::DropCompare(comparator, value)
+        }
+        Rule::reroll_pool => {
+            let (comparator, value) = extract_comparison(option)?;
+            DiceOption::RerollPool(comparator, value)
+        }
+        Rule::crit_success => {
+            let (comparator, value) = extract_crit(option)?;
+            DiceOption::CritSuccess(comparator, value)
+        }
+        Rule::crit_failure => {
+            let (comparator, value) = extract_crit(option)?;
+            DiceOption::CritFailure(comparator, value)
+        }
+        Rule::median => DiceOption::Median,
+        Rule::drop_hi => DiceOption::DropHi(value.unwrap()),
+        Rule::drop_lo => DiceOption::DropLo(value.unwrap()),
+        Rule::target => {
+            let value_or_enum = option.into_inner().next().unwrap();
+            match value_or_enum.as_rule() {
+                Rule::target_enum => DiceOption::TargetEnum(
+                    value_or_enum
+                        .into_inner()
+                        .map(|p| parse_number(&p))
+                        .collect::<Result<Vec<_>>>()?,
+                ),
+                _ => DiceOption::Target(value.unwrap()),
+            }
+        }
+        Rule::double_target => DiceOption::DoubleTarget(value.unwrap()),
+        Rule::failure => DiceOption::Failure(value.unwrap()),
+        Rule::compare => {
+            let comparator = to_comparator(option.clone().into_inner().next().unwrap().as_rule());
+            if option.into_inner().any(|p| p.as_rule() == Rule::botch) {
+                DiceOption::CompareBotch(comparator, value.unwrap())
+            } else {
+                DiceOption::Compare(comparator, value.unwrap())
+            }
+        }
+        _ => unreachable!("{:?}", option),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let ast = parse("2d6 K1 + 3").unwrap();
+        assert_eq!(
+            Ast {
+                command: Command::Expr(Expr::BinOp {
+                    op: Operator::Add,
+                    lhs: Box::new(Expr::Dice(Dice {
+                        count: 2,
+                        sides: Sides::Number(6),
+                        options: vec![DiceOption::KeepHi(1)],
+                    })),
+                    rhs: Box::new(Expr::Integer(3)),
+                }),
+                reason: None,
+            },
+            ast
+        );
+
+        let ast = parse("1d100 <= 65").unwrap();
+        assert_eq!(
+            Command::Expr(Expr::Dice(Dice {
+                count: 1,
+                sides: Sides::Number(100),
+                options: vec![DiceOption::Compare(Comparator::Le, 65)],
+            })),
+            ast.command
+        );
+    }
+
+    #[test]
+    fn parse_case_insensitive_test() {
+        let cases = [
+            ("2D6KH1", "2d6 K1"),
+            ("2d6Kh1", "2d6 kh1"),
+            ("4D6 KL3", "4d6 k3"),
+            ("4D6 DL1", "4d6 d1"),
+            ("4d6 Dh1", "4d6 D1"),
+            ("3D6 IE6B E5", "3d6 ie6b e5"),
+            ("1D20 RO1H", "1d20 ro1h"),
+            ("3d6 R1 IR2", "3d6 r1 ir2"),
+            ("6D10 T7 TT10 F1", "6d10 t7 tt10 f1"),
+            ("3D6 T[2,4]", "3d6 t[2,4]"),
+            ("4DF", "4df"),
+            ("4D6K>=4", "4d6 k>=4"),
+            ("4D6D<=2", "4d6 d<=2"),
+        ];
+        for (input, expected) in cases.iter() {
+            assert_eq!(parse(expected).unwrap(), parse(input).unwrap(), "{}", input);
+        }
+        assert_eq!(
+            Some("Hello World".to_owned()),
+            parse("2D6KH1 : Hello World").unwrap().reason
+        );
+    }
+
+    #[test]
+    fn parse_precedence_test() {
+        let ast = parse("(d20 + 1.5) * 4dF t[1,2] : test").unwrap();
+        assert_eq!(Some("test".to_owned()), ast.reason);
+        assert_eq!(
+            Command::Expr(Expr::BinOp {
+                op: Operator::Mul,
+                lhs: Box::new(Expr::Block(Box::new(Expr::BinOp {
+                    op: Operator::Add,
+                    lhs: Box::new(Expr::Dice(Dice {
+                        count: 1,
+                        sides: Sides::Number(20),
+                        options: vec![],
+                    })),
+                    rhs: Box::new(Expr::Float(1.5)),
+                }))),
+                rhs: Box::new(Expr::Dice(Dice {
+                    count: 4,
+                    sides: Sides::Fudge,
+                    options: vec![DiceOption::TargetEnum(vec![1, 2])],
+                })),
+            }),
+            ast.command
+        );
+    }
+
+    #[test]
+    fn parse_best_test() {
+        let ast = parse("best(2, 1d20 + 5)").unwrap();
+        assert_eq!(
+            Command::Expr(Expr::Best {
+                count: 2,
+                expr: Box::new(Expr::BinOp {
+                    op: Operator::Add,
+                    lhs: Box::new(Expr::Dice(Dice {
+                        count: 1,
+                        sides: Sides::Number(20),
+                        options: vec![],
+                    })),
+                    rhs: Box::new(Expr::Integer(5)),
+                }),
+            }),
+            ast.command
+        );
+        assert!(matches!(
+            parse("worst(3, 1d6)").unwrap().command,
+            Command::Expr(Expr::Worst { count: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn parse_repeated_test() {
+        let ast = parse("(3d6 !b e5) ^+ 4").unwrap();
+        assert_eq!(
+            Command::Repeated {
+                expr: Expr::Dice(Dice {
+                    count: 3,
+                    sides: Sides::Number(6),
+                    options: vec![
+                        DiceOption::IndefiniteExplode {
+                            value: None,
+                            bonus: true
+                        },
+                        DiceOption::Explode {
+                            value: 5,
+                            bonus: false
+                        }
+                    ],
+                }),
+                times: 4,
+                kind: Repetition::Sum,
+            },
+            ast.command
+        );
+
+        // without dice, it's an exponent
+        let ast = parse("(1 + 1) ^ 3").unwrap();
+        assert!(matches!(ast.command, Command::Expr(_)));
+    }
+
+    #[test]
+    fn parse_error_test() {
+        assert!(parse("2d").is_err());
+        assert!(parse("99999999999999999999d6").is_err());
+    }
+}
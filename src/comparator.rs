@@ -0,0 +1,43 @@
+use std::fmt::Display;
+
+/// Comparison used to check a value against a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Comparator {
+    /// `<`: strictly lower than the target
+    Lt,
+    /// `<=`: lower than or equal to the target
+    Le,
+    /// `>`: strictly greater than the target
+    Gt,
+    /// `>=`: greater than or equal to the target
+    Ge,
+    /// `==`: equal to the target
+    Eq,
+}
+
+impl Comparator {
+    /// Says if `value` satisfies the comparison against `target`.
+    pub fn compare<T: PartialOrd>(self, value: T, target: T) -> bool {
+        match self {
+            Comparator::Lt => value < target,
+            Comparator::Le => value <= target,
+            Comparator::Gt => value > target,
+            Comparator::Ge => value >= target,
+            Comparator::Eq => value == target,
+        }
+    }
+}
+
+impl Display for Comparator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Comparator::Lt => "<",
+            Comparator::Le => "<=",
+            Comparator::Gt => ">",
+            Comparator::Ge => ">=",
+            Comparator::Eq => "==",
+        };
+        write!(f, "{}", s)
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    arithmetic::checked_pow,
+    ast::{Command, Dice, DiceOption, Expr, Operator, Sides},
+    error::Result,
+    FudgeConfig,
+};
+
+/// Probability of each possible total
+pub type Distribution = BTreeMap<i64, f64>;
+
+// arbitrary limits to keep the computation fast: number of pairs of values combined at once, and
+// number of dice times number of sides for a pool of several dice
+const MAX_OUTCOMES: usize = 10_000_000;
+const MAX_POOL: u64 = 15_000;
+
+// Compute the exact distribution of the total of a command. Only sums, differences, products and
+// divisions of plain dice and integers are supported: options and repetitions are not.
+pub(crate) fn distribution(command: &Command, fudge: FudgeConfig) -> Result<Distribution> {
+    match command {
+        Command::Expr(expr) => expr_distribution(expr, fudge),
+        Command::Repeated { .. } => {
+            Err("Can't compute the distribution of a repeated expression".into())
+        }
+    }
+}
+
+pub(crate) fn expr_distribution(expr: &Expr, fudge: FudgeConfig) -> Result<Distribution> {
+    match expr {
+        Expr::Integer(i) => Ok(std::iter::once((*i, 1.0)).collect()),
+        Expr::Float(_) => Err("Can't compute the distribution of a float constant".into()),
+        Expr::Dice(dice) => dice_distribution(dice, fudge),
+        Expr::RolledCountDice { .. } => {
+            Err("Can't compute the distribution of dice with a rolled number".into())
+        }
+        Expr::NamedDice { .. } => {
+            Err("Can't compute the distribution of dice with named faces".into())
+        }
+        Expr::Block(expr) => expr_distribution(expr, fudge),
+        Expr::Best { count, expr } => {
+            attempts_distribution(*count, expr, fudge, |l, r| Some(l.max(r)))
+        }
+        Expr::Worst { count, expr } => {
+            attempts_distribution(*count, expr, fudge, |l, r| Some(l.min(r)))
+        }
+        Expr::BinOp { op, lhs, rhs } => {
+            let lhs = expr_distribution(lhs, fudge)?;
+            let rhs = expr_distribution(rhs, fudge)?;
+            let op: fn(i64, i64) -> Option<i64> = match op {
+                Operator::Add => i64::checked_add,
+                Operator::Sub => i64::checked_sub,
+                Operator::Mul => i64::checked_mul,
+                Operator::Div => {
+                    if rhs.contains_key(&0) {
+                        return Err("Can't divide by zero".into());
+                    }
+                    i64::checked_div
+                }
+                Operator::Pow => {
+                    if rhs.keys().any(|&e| e < 0) {
+                        return Err("Exponent can't be negative".into());
+                    }
+                    checked_pow
+                }
+            };
+            combine(&lhs, &rhs, op)
+        }
+    }
+}
+
+// Distribution of the highest or lowest total of `count` independent rolls of an expression
+fn attempts_distribution(
+    count: u64,
+    expr: &Expr,
+    fudge: FudgeConfig,
+    keep: fn(i64, i64) -> Option<i64>,
+) -> Result<Distribution> {
+    let attempt = expr_distribution(expr, fudge)?;
+    if count.saturating_mul(attempt.len() as u64) > MAX_POOL {
+        return Err("Too many outcomes to compute the distribution".into());
+    }
+    let mut res = attempt.clone();
+    for _ in 1..count {
+        res = combine(&res, &attempt, keep)?;
+    }
+    Ok(res)
+}
+
+fn dice_distribution(dice: &Dice, fudge: FudgeConfig) -> Result<Distribution> {
+    // critic markers don't change the total
+    let changes_total = |option: &DiceOption| {
+        !matches!(
+            option,
+            DiceOption::CritSuccess(..) | DiceOption::CritFailure(..)
+        )
+    };
+    if dice.options.iter().any(changes_total) {
+        return Err("Can't compute the distribution of dice with options".into());
+    }
+    let die: Distribution = match dice.sides {
+        Sides::Number(0) => return Err("Dice can't have 0 sides".into()),
+        Sides::Number(sides) => {
+            if sides as usize > MAX_OUTCOMES {
+                return Err("Too many outcomes to compute the distribution".into());
+            }
+            (1..=sides as i64)
+                .map(|v| (v, 1.0 / sides as f64))
+                .collect()
+        }
+        Sides::Fudge => {
+            let mut die = Distribution::new();
+            for v in 1..=6 {
+                *die.entry(fudge.value(v)).or_insert(0.0) += 1.0 / 6.0;
+            }
+            die
+        }
+    };
+    if dice.count > 1 && dice.count.saturating_mul(die.len() as u64) > MAX_POOL {
+        return Err("Too many outcomes to compute the distribution".into());
+    }
+    let mut res: Distribution = std::iter::once((0, 1.0)).collect();
+    for _ in 0..dice.count {
+        res = combine(&res, &die, i64::checked_add)?;
+    }
+    Ok(res)
+}
+
+// Distribution of `op` applied on two independent values
+fn combine(
+    lhs: &Distribution,
+    rhs: &Distribution,
+    op: fn(i64, i64) -> Option<i64>,
+) -> Result<Distribution> {
+    if lhs.len().saturating_mul(rhs.len()) > MAX_OUTCOMES {
+        return Err("Too many outcomes to compute the distribution".into());
+    }
+    let mut res = Distribution::new();
+    for (l, pl) in lhs {
+        for (r, pr) in rhs {
+            let v = op(*l, *r).ok_or("Result is too big to be computed")?;
+            *res.entry(v).or_insert(0.0) += pl * pr;
+        }
+    }
+    Ok(res)
+}
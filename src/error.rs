@@ -1,4 +1,8 @@
-use std::{error::Error, fmt::Display};
+use std::{
+    error::Error,
+    fmt::Display,
+    num::{ParseIntError, TryFromIntError},
+};
 
 use crate::parser::*;
 
@@ -12,6 +16,13 @@ pub enum RollError {
     ParseError(Box<pest::error::Error<Rule>>),
     /// Any other error while walking the AST, the String contains an explaination of what happened
     ParamError(String),
+    /// The expression has more nested parentheses than the maximum depth given, see
+    /// [`crate::Roller::with_max_depth()`]
+    TooDeep(usize),
+    /// More dice than the budget given were rolled, see [`crate::Roller::roll_with_budget()`]
+    BudgetExceeded(u64),
+    /// The dice source ran out of values, see [`crate::FixedDiceRollSource`]
+    SourceExhausted,
 }
 
 impl Display for RollError {
@@ -19,6 +30,15 @@ impl Display for RollError {
         match self {
             RollError::ParseError(e) => write!(f, "{}", e),
             RollError::ParamError(e) => write!(f, "{}", e),
+            RollError::TooDeep(max) => write!(
+                f,
+                "Expression is nested too deeply, the maximum depth is {}",
+                max
+            ),
+            RollError::BudgetExceeded(max) => {
+                write!(f, "Roll needs more than the {} dice allowed", max)
+            }
+            RollError::SourceExhausted => write!(f, "Not enough values for the dice to roll"),
         }
     }
 }
@@ -42,3 +62,15 @@ impl From<String> for RollError {
         Self::ParamError(e)
     }
 }
+
+impl From<ParseIntError> for RollError {
+    fn from(e: ParseIntError) -> Self {
+        Self::ParamError(format!("Invalid number: {}", e))
+    }
+}
+
+impl From<TryFromIntError> for RollError {
+    fn from(e: TryFromIntError) -> Self {
+        Self::ParamError(format!("Number out of range: {}", e))
+    }
+}
@@ -3,8 +3,9 @@ use std::{
     fmt::Display,
 };
 
-use crate::{error::*, RollHistory, RollResult};
+use crate::{error::*, helpers::Interpretation, DiceResult, RollHistory, RollResult};
 
+#[derive(Debug)]
 enum Element {
     Fire(([Outcome; 10], [&'static str; 5])),
     Earth(([Outcome; 10], [&'static str; 5])),
@@ -13,11 +14,13 @@ enum Element {
     Wood(([Outcome; 10], [&'static str; 5])),
 }
 
+#[derive(Debug)]
 enum Side {
     Yin,
     Yang,
 }
 
+#[derive(Debug)]
 enum Outcome {
     Success,
     Lucky,
@@ -103,6 +106,17 @@ const WATER_SUIT_FR: [&str; 5] = ["㊌ eau", "㊍ bois", "㊎ métal", "㊋ feu"
 const WOOD_SUIT_EN: [&str; 5] = ["㊍ wood", "㊋ fire", "㊌ water", "㊏ earth", "㊎ metal"];
 const WOOD_SUIT_FR: [&str; 5] = ["㊍ bois", "㊋ feu", "㊌ eau", "㊏ terre", "㊎ métal"];
 
+/// Side of the Loksyu with the most dice, see [`CdeResult::dominant_side()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DominantSide {
+    /// More Yin dice
+    Yin,
+    /// More Yang dice
+    Yang,
+    /// As many Yin dice as Yang dice
+    Tie,
+}
+
 #[derive(Debug, Default)]
 /// This struct represent the repartition of the dices according to an element
 pub struct CdeResult {
@@ -133,6 +147,22 @@ impl PartialEq for CdeResult {
     }
 }
 
+impl CdeResult {
+    /// Number of dice in the Loksyu, Yin and Yang together
+    pub fn loksyu_total(&self) -> u32 {
+        self.loksyu.0 + self.loksyu.1
+    }
+
+    /// Side of the Loksyu with the most dice
+    pub fn dominant_side(&self) -> DominantSide {
+        match self.loksyu.0.cmp(&self.loksyu.1) {
+            std::cmp::Ordering::Greater => DominantSide::Yin,
+            std::cmp::Ordering::Less => DominantSide::Yang,
+            std::cmp::Ordering::Equal => DominantSide::Tie,
+        }
+    }
+}
+
 impl TryFrom<&str> for Element {
     type Error = &'static str;
 
@@ -188,31 +218,58 @@ Tin Ji ({}): {}
     }
 }
 
-/// Interpret a [`RollResult`](crate::RollResult) according to the RPG
-/// "Hong Kong : Chroniques de l'étrange"
-pub fn compute_cde(res: &RollResult, element: &str) -> Result<CdeResult> {
-    let history = res
-        .as_single()
-        .ok_or("Not a single roll result")?
-        .get_history();
-    if history.len() != 1 {
-        Err("Should have only one roll".into())
-    } else {
-        let res = history
-            .iter()
-            .flat_map(|v| {
-                if let RollHistory::Roll(dices_res) = v {
-                    Some(dices_res)
-                } else {
-                    None
-                }
-            })
-            .next()
-            .ok_or("RollHistory must be a Roll variant")?
-            .clone();
+/// Interpreter of the rolls of the RPG "Hong Kong : Chroniques de l'étrange" for an element,
+/// usable through the [`Interpretation`] trait
+#[derive(Debug)]
+pub struct CdeInterpreter {
+    element: Element,
+}
 
-        let mapping: Element = element.try_into()?;
-        let (mapping, elements) = match mapping {
+impl CdeInterpreter {
+    /// Create the interpreter of the element rolled, in English or in French (`fire` or `feu`)
+    pub fn new(element: &str) -> Result<Self> {
+        Ok(CdeInterpreter {
+            element: element.try_into()?,
+        })
+    }
+
+    /// Interpret a [`RollResult`](crate::RollResult) with the element of the interpreter.
+    ///
+    /// The result must have exactly one roll group of d10. The constants, like in `8d10 + 0`, are
+    /// ignored.
+    pub fn compute(&self, res: &RollResult) -> Result<CdeResult> {
+        let history = res
+            .as_single()
+            .ok_or("Not a single roll result")?
+            .get_history();
+        let mut rolls = history.into_iter().filter(|h| {
+            !matches!(
+                h,
+                RollHistory::Value(_)
+                    | RollHistory::Separator(_)
+                    | RollHistory::OpenParenthesis
+                    | RollHistory::CloseParenthesis
+            )
+        });
+        let roll = match (rolls.next(), rolls.next()) {
+            (Some(roll), None) => roll,
+            _ => return Err("Should have only one roll".into()),
+        };
+        let res = match roll {
+            RollHistory::Roll(dices_res) => dices_res,
+            _ => return Err("RollHistory must be a Roll variant".into()),
+        };
+        let values: Vec<u64> = res.iter().map(|d| d.res).collect();
+
+        let mut result = self.compute_values(&values)?;
+        result.history = Some(roll.clone());
+        Ok(result)
+    }
+
+    /// Interpret the values of d10 already rolled, without a [`RollResult`](crate::RollResult).
+    /// The history of the result is made of the values.
+    pub fn compute_values(&self, values: &[u64]) -> Result<CdeResult> {
+        let (mapping, elements) = match &self.element {
             Element::Fire(m) => m,
             Element::Earth(m) => m,
             Element::Metal(m) => m,
@@ -220,30 +277,56 @@ pub fn compute_cde(res: &RollResult, element: &str) -> Result<CdeResult> {
             Element::Wood(m) => m,
         };
 
-        let mut result = res.iter().fold(CdeResult::default(), |mut acc, v| {
-            match &mapping[(v.res - 1) as usize] {
-                Outcome::Success => acc.success += 1,
-                Outcome::Lucky => acc.lucky += 1,
-                Outcome::Ill => acc.ill += 1,
-                Outcome::Loksyu(side) => match side {
-                    Side::Yin => acc.loksyu.0 += 1,
-                    Side::Yang => acc.loksyu.1 += 1,
-                },
-                Outcome::TinJi => acc.tin_ji += 1,
-            }
-            acc
-        });
+        let mut result = values
+            .iter()
+            .try_fold(CdeResult::default(), |mut acc, &v| {
+                let outcome = usize::try_from(v)
+                    .ok()
+                    .and_then(|i| mapping.get(i.checked_sub(1)?))
+                    .ok_or_else(|| format!("CdE uses d10, can't interpret a {}", v))?;
+                match outcome {
+                    Outcome::Success => acc.success += 1,
+                    Outcome::Lucky => acc.lucky += 1,
+                    Outcome::Ill => acc.ill += 1,
+                    Outcome::Loksyu(side) => match side {
+                        Side::Yin => acc.loksyu.0 += 1,
+                        Side::Yang => acc.loksyu.1 += 1,
+                    },
+                    Outcome::TinJi => acc.tin_ji += 1,
+                }
+                Ok::<_, RollError>(acc)
+            })?;
 
-        result.history = history.get(0).cloned();
-        result.elements = elements;
+        result.history = Some(RollHistory::Roll(
+            values.iter().map(|&v| DiceResult::new(v, 10)).collect(),
+        ));
+        result.elements = *elements;
         Ok(result)
     }
 }
 
+impl Interpretation for CdeInterpreter {
+    fn interpret(&self, res: &RollResult) -> Result<Box<dyn Display>> {
+        Ok(Box::new(self.compute(res)?))
+    }
+}
+
+/// Interpret a [`RollResult`](crate::RollResult) according to the RPG
+/// "Hong Kong : Chroniques de l'étrange"
+pub fn compute_cde(res: &RollResult, element: &str) -> Result<CdeResult> {
+    CdeInterpreter::new(element)?.compute(res)
+}
+
+/// Interpret the values of d10 already rolled according to the RPG
+/// "Hong Kong : Chroniques de l'étrange". Each value must be in `1..=10`.
+pub fn compute_cde_from_values(values: &[u64], element: &str) -> Result<CdeResult> {
+    CdeInterpreter::new(element)?.compute_values(values)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{tests::IteratorDiceRollSource, Critic, DiceResult, Roller};
+    use crate::{test_support::IteratorDiceRollSource, Critic, Roller};
 
     #[test]
     fn test_cde() {
@@ -269,6 +352,9 @@ mod test {
                     .map(|v| DiceResult {
                         res: *v,
                         crit: Critic::No,
+                        dropped: false,
+                        exploded: false,
+                        successes: 0,
                     })
                     .collect(),
             )),
@@ -276,6 +362,8 @@ mod test {
         };
 
         assert_eq!(expected, res);
+        assert_eq!(1, res.loksyu_total());
+        assert_eq!(DominantSide::Yang, res.dominant_side());
         println!("{}", res);
 
         // earth
@@ -298,6 +386,9 @@ mod test {
                     .map(|v| DiceResult {
                         res: *v,
                         crit: Critic::No,
+                        dropped: false,
+                        exploded: false,
+                        successes: 0,
                     })
                     .collect(),
             )),
@@ -305,6 +396,8 @@ mod test {
         };
 
         assert_eq!(expected, res);
+        assert_eq!(1, res.loksyu_total());
+        assert_eq!(DominantSide::Yang, res.dominant_side());
         println!("{}", res);
 
         // metal
@@ -327,6 +420,9 @@ mod test {
                     .map(|v| DiceResult {
                         res: *v,
                         crit: Critic::No,
+                        dropped: false,
+                        exploded: false,
+                        successes: 0,
                     })
                     .collect(),
             )),
@@ -334,6 +430,8 @@ mod test {
         };
 
         assert_eq!(expected, res);
+        assert_eq!(1, res.loksyu_total());
+        assert_eq!(DominantSide::Yin, res.dominant_side());
         println!("{}", res);
 
         // water
@@ -356,6 +454,9 @@ mod test {
                     .map(|v| DiceResult {
                         res: *v,
                         crit: Critic::No,
+                        dropped: false,
+                        exploded: false,
+                        successes: 0,
                     })
                     .collect(),
             )),
@@ -363,6 +464,8 @@ mod test {
         };
 
         assert_eq!(expected, res);
+        assert_eq!(2, res.loksyu_total());
+        assert_eq!(DominantSide::Tie, res.dominant_side());
         println!("{}", res);
 
         // wood
@@ -385,6 +488,9 @@ mod test {
                     .map(|v| DiceResult {
                         res: *v,
                         crit: Critic::No,
+                        dropped: false,
+                        exploded: false,
+                        successes: 0,
                     })
                     .collect(),
             )),
@@ -392,6 +498,92 @@ mod test {
         };
 
         assert_eq!(expected, res);
+        assert_eq!(3, res.loksyu_total());
+        assert_eq!(DominantSide::Yang, res.dominant_side());
         println!("{}", res);
     }
+
+    #[test]
+    fn test_cde_wrong_dice() {
+        let r = Roller::new("2d20").unwrap();
+        let roll_res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![3, 12].into_iter(),
+            })
+            .unwrap();
+        match compute_cde(&roll_res, "fire") {
+            Err(RollError::ParamError(e)) => assert_eq!("CdE uses d10, can't interpret a 12", e),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_cde_from_values() {
+        let roll_mock = vec![1, 2, 3, 4, 5, 7, 10, 5];
+        let roll_res = Roller::new("8d10")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.clone().into_iter(),
+            })
+            .unwrap();
+        for element in ["fire", "earth", "metal", "water", "wood"].iter() {
+            assert_eq!(
+                compute_cde(&roll_res, element).unwrap(),
+                compute_cde_from_values(&roll_mock, element).unwrap()
+            );
+        }
+        let res = compute_cde_from_values(&roll_mock, "fire").unwrap();
+        assert_eq!(
+            "[1, 2, 3, 4, 5, 7, 10, 5]",
+            res.history.unwrap().to_string()
+        );
+
+        match compute_cde_from_values(&[3, 11], "fire") {
+            Err(RollError::ParamError(e)) => assert_eq!("CdE uses d10, can't interpret a 11", e),
+            _ => unreachable!(),
+        }
+        assert!(compute_cde_from_values(&[0], "fire").is_err());
+    }
+
+    #[test]
+    fn test_cde_interpreter() {
+        let r = Roller::new("4d10").unwrap();
+        let roll_res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![1, 2, 3, 8].into_iter(),
+            })
+            .unwrap();
+        let interpreter: Box<dyn Interpretation> = Box::new(CdeInterpreter::new("feu").unwrap());
+        assert_eq!(
+            compute_cde(&roll_res, "feu").unwrap().to_string(),
+            interpreter.interpret(&roll_res).unwrap().to_string()
+        );
+        assert!(CdeInterpreter::new("air").is_err());
+    }
+
+    #[test]
+    fn test_cde_with_constant() {
+        let roll_mock = vec![1, 2, 3, 4, 5, 7, 10, 5];
+        let roll_res = Roller::new("8d10 + 0")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.clone().into_iter(),
+            })
+            .unwrap();
+        assert_eq!(
+            compute_cde_from_values(&roll_mock, "fire").unwrap(),
+            compute_cde(&roll_res, "fire").unwrap()
+        );
+
+        let roll_res = Roller::new("4d10 + 4d10")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.clone().into_iter(),
+            })
+            .unwrap();
+        match compute_cde(&roll_res, "fire") {
+            Err(RollError::ParamError(e)) => assert_eq!("Should have only one roll", e),
+            _ => unreachable!(),
+        }
+    }
 }
@@ -0,0 +1,231 @@
+use std::fmt::Display;
+
+/// Symbols rolled with Genesys-like narrative dice, before cancellation.
+///
+/// Caith can't roll the narrative dice itself: fill the tally from the faces read on the dice,
+/// then show the net result with `Display` or [`GenesysSymbols::to_string_with()`].
+///
+/// ex:
+/// ```
+/// use caith::helpers::GenesysSymbols;
+///
+/// let tally = GenesysSymbols {
+///     success: 3,
+///     failure: 1,
+///     advantage: 1,
+///     threat: 2,
+///     ..Default::default()
+/// };
+/// assert_eq!("2 success, 1 threat", tally.to_string());
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GenesysSymbols {
+    /// Number of success symbols
+    pub success: u64,
+    /// Number of failure symbols
+    pub failure: u64,
+    /// Number of advantage symbols
+    pub advantage: u64,
+    /// Number of threat symbols
+    pub threat: u64,
+    /// Number of triumph symbols, each one also counts as a success
+    pub triumph: u64,
+    /// Number of despair symbols, each one also counts as a failure
+    pub despair: u64,
+}
+
+/// Symbols left after cancellation, see [`GenesysSymbols::net()`].
+///
+/// Only one of `success` and `failure` can be non zero, the same goes for `advantage` and
+/// `threat`. Triumph and despair are never cancelled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GenesysNet {
+    /// Net successes, triumphs included
+    pub success: u64,
+    /// Net failures, despairs included
+    pub failure: u64,
+    /// Net advantages
+    pub advantage: u64,
+    /// Net threats
+    pub threat: u64,
+    /// Triumphs rolled
+    pub triumph: u64,
+    /// Despairs rolled
+    pub despair: u64,
+}
+
+impl GenesysNet {
+    /// Whether the check succeeds, which needs at least one net success
+    pub fn is_success(&self) -> bool {
+        self.success > 0
+    }
+}
+
+/// Glyphs and options used to show Genesys symbols, see [`GenesysSymbols::to_string_with()`].
+///
+/// Each symbol is shown as its count followed by its glyph, so a glyph can be a word or an emoji.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GenesysDisplay {
+    /// Glyph of the success symbol
+    pub success: String,
+    /// Glyph of the failure symbol
+    pub failure: String,
+    /// Glyph of the advantage symbol
+    pub advantage: String,
+    /// Glyph of the threat symbol
+    pub threat: String,
+    /// Glyph of the triumph symbol
+    pub triumph: String,
+    /// Glyph of the despair symbol
+    pub despair: String,
+    /// Also show the uncancelled tally, between parentheses
+    pub show_raw: bool,
+}
+
+impl Default for GenesysDisplay {
+    fn default() -> Self {
+        Self {
+            success: "success".to_string(),
+            failure: "failure".to_string(),
+            advantage: "advantage".to_string(),
+            threat: "threat".to_string(),
+            triumph: "triumph".to_string(),
+            despair: "despair".to_string(),
+            show_raw: false,
+        }
+    }
+}
+
+impl GenesysDisplay {
+    // Join the non zero counts, or give `none` if there is none
+    fn format(&self, counts: [(u64, &str); 6]) -> String {
+        let parts: Vec<_> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, glyph)| format!("{} {}", count, glyph))
+            .collect();
+        if parts.is_empty() {
+            "none".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+impl GenesysSymbols {
+    /// Cancel the opposed symbols: each success cancels a failure and each advantage cancels a
+    /// threat. Triumphs count as successes and despairs as failures for the cancellation, but are
+    /// kept in the result.
+    pub fn net(&self) -> GenesysNet {
+        let success = self.success + self.triumph;
+        let failure = self.failure + self.despair;
+        GenesysNet {
+            success: success.saturating_sub(failure),
+            failure: failure.saturating_sub(success),
+            advantage: self.advantage.saturating_sub(self.threat),
+            threat: self.threat.saturating_sub(self.advantage),
+            triumph: self.triumph,
+            despair: self.despair,
+        }
+    }
+
+    /// Show the net symbols with custom glyphs, followed by the raw tally if asked for.
+    ///
+    /// ```
+    /// use caith::helpers::{GenesysDisplay, GenesysSymbols};
+    ///
+    /// let tally = GenesysSymbols {
+    ///     success: 2,
+    ///     failure: 1,
+    ///     triumph: 1,
+    ///     ..Default::default()
+    /// };
+    /// let config = GenesysDisplay {
+    ///     success: "✔".to_string(),
+    ///     failure: "✘".to_string(),
+    ///     triumph: "☀".to_string(),
+    ///     show_raw: true,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!("2 ✔, 1 ☀ (2 ✔, 1 ✘, 1 ☀)", tally.to_string_with(&config));
+    /// ```
+    pub fn to_string_with(&self, config: &GenesysDisplay) -> String {
+        let net = self.net();
+        let res = config.format([
+            (net.success, &config.success),
+            (net.failure, &config.failure),
+            (net.advantage, &config.advantage),
+            (net.threat, &config.threat),
+            (net.triumph, &config.triumph),
+            (net.despair, &config.despair),
+        ]);
+        if config.show_raw {
+            let raw = config.format([
+                (self.success, &config.success),
+                (self.failure, &config.failure),
+                (self.advantage, &config.advantage),
+                (self.threat, &config.threat),
+                (self.triumph, &config.triumph),
+                (self.despair, &config.despair),
+            ]);
+            format!("{} ({})", res, raw)
+        } else {
+            res
+        }
+    }
+}
+
+impl Display for GenesysSymbols {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_string_with(&GenesysDisplay::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn genesys_cancel_test() {
+        let tally = GenesysSymbols {
+            success: 4,
+            failure: 2,
+            advantage: 1,
+            threat: 3,
+            ..Default::default()
+        };
+        let net = tally.net();
+        assert_eq!(
+            GenesysNet {
+                success: 2,
+                threat: 2,
+                ..Default::default()
+            },
+            net
+        );
+        assert!(net.is_success());
+        assert_eq!("2 success, 2 threat", tally.to_string());
+
+        let config = GenesysDisplay {
+            show_raw: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            "2 success, 2 threat (4 success, 2 failure, 1 advantage, 3 threat)",
+            tally.to_string_with(&config)
+        );
+
+        // triumph and despair cancel like success and failure but are kept
+        let tally = GenesysSymbols {
+            success: 1,
+            advantage: 2,
+            threat: 2,
+            triumph: 1,
+            despair: 1,
+            ..Default::default()
+        };
+        assert_eq!("1 success, 1 triumph, 1 despair", tally.to_string());
+        assert_eq!("none", GenesysSymbols::default().to_string());
+        assert!(!GenesysSymbols::default().net().is_success());
+    }
+}
@@ -3,6 +3,37 @@
 //! 
 //!
 
+use std::fmt::Display;
+
+use crate::{error::Result, RollResult};
+
+/// Interpret a roll result according to the rules of a game, like the provided helpers do.
+///
+/// Implementing it lets the interpreters of other games be called the same way as the provided
+/// ones.
+///
+/// ```
+/// use std::fmt::Display;
+///
+/// use caith::{helpers::Interpretation, Result, RollResult, Roller};
+///
+/// struct Parity;
+///
+/// impl Interpretation for Parity {
+///     fn interpret(&self, res: &RollResult) -> Result<Box<dyn Display>> {
+///         let total = res.as_single().ok_or("Not a single roll result")?.get_total();
+///         Ok(Box::new(if total % 2 == 0 { "even" } else { "odd" }))
+///     }
+/// }
+///
+/// let res = Roller::new("2d6").unwrap().roll_with_values(&[3, 4]).unwrap();
+/// assert_eq!("odd", Parity.interpret(&res).unwrap().to_string());
+/// ```
+pub trait Interpretation {
+    /// Interpret the result, giving what to show to the players
+    fn interpret(&self, res: &RollResult) -> Result<Box<dyn Display>>;
+}
+
 #[cfg(feature = "ova")]
 #[cfg_attr(docsrs, doc(cfg(feature = "ova")))]
 /// Helpers for "OVA: The Anime Role-Playing Game result"
@@ -16,3 +47,20 @@ pub use ova::*;
 pub mod cde;
 #[cfg(feature = "cde")]
 pub use cde::*;
+
+#[cfg(feature = "table")]
+#[cfg_attr(docsrs, doc(cfg(feature = "table")))]
+/// Helpers to roll on tables, mapping a result to a text
+pub mod table;
+
+#[cfg(feature = "stats")]
+#[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+/// Helpers to check a custom dice source for bias
+pub mod stats;
+
+#[cfg(feature = "genesys")]
+#[cfg_attr(docsrs, doc(cfg(feature = "genesys")))]
+/// Helpers to show the symbols of Genesys-like narrative dice after cancellation
+pub mod genesys;
+#[cfg(feature = "genesys")]
+pub use genesys::*;
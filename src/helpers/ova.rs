@@ -4,9 +4,9 @@ use crate::{error::*, RollHistory, RollResult, SingleRollResult};
 ///
 /// ex:
 /// ```
-/// use caith::*;
+/// use caith::{helpers::compute_ova, *};
 ///
-/// let number: i64 = -4;
+/// let number: i32 = -4;
 /// let roller = Roller::new(&format!("{}d6", number.abs())).unwrap();
 /// let res = roller.roll().unwrap();
 /// println!("{}", compute_ova(&res, number).unwrap());
@@ -66,7 +66,7 @@ pub fn compute_ova(res: &RollResult, number: i32) -> Result<RollResult> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{rollresult, tests::IteratorDiceRollSource, Roller};
+    use crate::{rollresult, test_support::IteratorDiceRollSource, Roller};
 
     use super::*;
 
@@ -0,0 +1,93 @@
+use crate::DiceRollSource;
+
+/// Roll `samples` dice with `sides` sides from `source` and return the chi-square statistic of the
+/// results against a uniform distribution, to check a custom [`DiceRollSource`] for bias.
+///
+/// The statistic has `sides - 1` degrees of freedom: for a fair source, it's about `sides - 1` on
+/// average. As a rough threshold, a source is suspicious if the statistic is above
+/// `sides - 1 + 2 * sqrt(2 * (sides - 1))`, like 11.3 for a d6 or 31.3 for a d20. Use enough
+/// samples to have at least a few dozens of each value, and expect a fair source to fail from
+/// time to time.
+///
+/// A value out of `1..=sides` makes the statistic infinite.
+///
+/// Panics if `sides` is lower than 2 or if `samples` is 0.
+///
+/// ex:
+/// ```
+/// use caith::{helpers::stats::uniformity_test, SeededDiceRollSource};
+///
+/// let statistic = uniformity_test(&mut SeededDiceRollSource::new(42), 6, 6000);
+/// assert!(statistic < 11.3);
+/// ```
+pub fn uniformity_test<S: DiceRollSource>(source: &mut S, sides: u64, samples: usize) -> f64 {
+    assert!(sides >= 2, "need at least 2 sides to test the uniformity");
+    assert!(
+        samples > 0,
+        "need at least one sample to test the uniformity"
+    );
+    let mut counts = vec![0usize; sides as usize];
+    for _ in 0..samples {
+        let value = source.roll_single_die(sides);
+        if value == 0 || value > sides {
+            return f64::INFINITY;
+        }
+        counts[(value - 1) as usize] += 1;
+    }
+    let expected = samples as f64 / sides as f64;
+    counts
+        .iter()
+        .map(|&count| {
+            let diff = count as f64 - expected;
+            diff * diff / expected
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededDiceRollSource;
+
+    // Rolls a 6 twice as often as the other values
+    struct BiasedDiceRollSource {
+        inner: SeededDiceRollSource,
+    }
+
+    impl DiceRollSource for BiasedDiceRollSource {
+        fn roll_single_die(&mut self, sides: u64) -> u64 {
+            let value = self.inner.roll_single_die(sides + 1);
+            value.min(sides)
+        }
+    }
+
+    #[test]
+    fn uniformity_test_test() {
+        let fair = uniformity_test(&mut SeededDiceRollSource::new(42), 6, 60_000);
+        assert!(fair < 11.3, "{}", fair);
+        let fair = uniformity_test(&mut SeededDiceRollSource::new(7), 20, 20_000);
+        assert!(fair < 31.3, "{}", fair);
+
+        let mut biased = BiasedDiceRollSource {
+            inner: SeededDiceRollSource::new(42),
+        };
+        let biased = uniformity_test(&mut biased, 6, 60_000);
+        assert!(biased > 1000.0, "{}", biased);
+    }
+
+    struct OutOfRangeDiceRollSource;
+
+    impl DiceRollSource for OutOfRangeDiceRollSource {
+        fn roll_single_die(&mut self, sides: u64) -> u64 {
+            sides + 1
+        }
+    }
+
+    #[test]
+    fn out_of_range_test() {
+        assert_eq!(
+            f64::INFINITY,
+            uniformity_test(&mut OutOfRangeDiceRollSource, 6, 10)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::ops::RangeInclusive;
+
+use crate::{error::*, RollResult};
+
+/// Find the entry of a table matching the total of a roll.
+///
+/// Each entry of `table` is a range of totals and the text to return for it. The ranges can't
+/// overlap, and it's an error if no range contains the total.
+///
+/// ex:
+/// ```
+/// use caith::{helpers::table::lookup, Roller};
+///
+/// let encounters = [(1..=60, "Nothing"), (61..=90, "Wolves"), (91..=100, "Dragon")];
+/// let res = Roller::new("1d100").unwrap().roll().unwrap();
+/// println!("{}", lookup(&res, &encounters).unwrap());
+/// ```
+///
+pub fn lookup<'a>(res: &RollResult, table: &[(RangeInclusive<i64>, &'a str)]) -> Result<&'a str> {
+    let total = res
+        .as_single()
+        .ok_or("Not a single roll result")?
+        .get_total();
+
+    let mut ranges = table.iter().map(|(range, _)| range).collect::<Vec<_>>();
+    ranges.sort_unstable_by_key(|range| range.start());
+    if let Some(overlap) = ranges.windows(2).find(|w| w[1].start() <= w[0].end()) {
+        return Err(format!(
+            "Table ranges {}..={} and {}..={} overlap",
+            overlap[0].start(),
+            overlap[0].end(),
+            overlap[1].start(),
+            overlap[1].end()
+        )
+        .into());
+    }
+
+    table
+        .iter()
+        .find(|(range, _)| range.contains(&total))
+        .map(|(_, text)| *text)
+        .ok_or_else(|| format!("No entry in the table for {}", total).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_support::IteratorDiceRollSource, Roller};
+
+    const ENCOUNTERS: [(RangeInclusive<i64>, &str); 3] = [
+        (1..=40, "Nothing"),
+        (41..=80, "Wolves"),
+        // 81 to 95 is a gap
+        (96..=100, "Dragon"),
+    ];
+
+    fn roll(value: u64) -> RollResult {
+        Roller::new("1d100")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![value].into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn lookup_test() {
+        assert_eq!("Nothing", lookup(&roll(1), &ENCOUNTERS).unwrap());
+        assert_eq!("Wolves", lookup(&roll(41), &ENCOUNTERS).unwrap());
+        assert_eq!("Dragon", lookup(&roll(100), &ENCOUNTERS).unwrap());
+        match lookup(&roll(85), &ENCOUNTERS) {
+            Err(RollError::ParamError(e)) => assert_eq!("No entry in the table for 85", e),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn lookup_overlap_test() {
+        let table = [
+            (1..=50, "Nothing"),
+            (90..=100, "Dragon"),
+            (50..=89, "Wolves"),
+        ];
+        match lookup(&roll(10), &table) {
+            Err(RollError::ParamError(e)) => {
+                assert_eq!("Table ranges 1..=50 and 50..=89 overlap", e)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -24,22 +24,61 @@
 //! # Syntax
 //!
 //! ```text
-//! xdy [OPTIONS] [TARGET] [FAILURE] [! REASON]
+//! xdy [OPTIONS] [TARGET] [FAILURE] [: REASON]
 //!
 //! roll `x` dice(s) with `y` sides
 //!
+//! `x` can be omitted, `d20` is the same as `1d20`. `x` can't be 0.
+//!
+//! `x` can be an expression between parentheses rolled first, `(1d4)d6` rolls as many d6 as the
+//! d4 gives.
+//!
 //! `y` can also be "F" or "f" for fudge dice. In this case, no option applies and ignored if provided.
 //!
+//! `xd{name}` rolls `x` dice with named faces registered with `Roller::with_die()`, like
+//! `2d{story}`. They don't count in the total and take no option.
+//!
 //! Options:
 //! + - / * : modifiers
-//! e# : Explode value. If number is omitted, we use dice sides
+//! (+# each) or (-# each) : Add the value to each die rolled, right after the dice, including the
+//! dice rolled by explosions and rerolls. It's added once they are done, so they read the value
+//! rolled: `4d6(+1 each)!` explodes on a 6 only. A die can't go under 1 but can go above the number
+//! of sides, and keeps its critic marker from the value rolled. Can't be used on Fudge dice
+//! e# or !o# : Explode value, only once per die. With `!o`, if number is omitted, we use dice
+//! sides
 //! ie# or !# : Indefinite explode value, If number is omitted, we use dice sides
-//! K#  : Keeping # highest (upperacse "K")
-//! k#  : Keeping # lowest (lowercase "k")
-//! D#  : Dropping the highest (uppercase "D")
-//! d#  : Dropping the lowest (lowercase "d")
+//! e#b, ie#b or !#b : Explode, but the exploded dice go to a separate bonus tally
+//! K# or kh# : Keeping # highest (upperacse "K")
+//! k# or kl# : Keeping # lowest (lowercase "k")
+//! k[<list of numbers>] : Keeping the dice at these positions, in roll order, starting at 1
+//! D# or dh# : Dropping the highest (uppercase "D")
+//! d# or dl# : Dropping the lowest (lowercase "d")
+//!
+//! Keeping 0 dice (`K0`, `kl0`...) is rejected as a likely mistake, while dropping 0 dice (`D0`,
+//! `dl0`...) keeps them all.
+//! k<#, k<=#, k>#, k>=#, k==# : Keeping the dice which value satisfies the comparison, whatever
+//! their number
+//! d<#, d<=#, d>#, d>=#, d==# : Dropping the dice which value satisfies the comparison, whatever
+//! their number
+//! med : Keeping only the median die. With an even number of dice, the lower of the two middle
+//! dice is kept
 //! r#  : Reroll if <= value
 //! ir# : Indefinite reroll if <= value
+//! ar# : Reroll once if <= value, adding the new die to the pool instead of replacing it
+//! rp<#, rp<=#, rp>#, rp>=#, rp==# : Reroll all the dice once if their total satisfies the
+//! comparison, keeping the second roll
+//! ro#h : Reroll once if <= value, keeping the higher of the two dice
+//! ro#l : Reroll once if <= value, keeping the lower of the two dice
+//! cs#, cs>#, cs<# : Marking the dice with this value, or at least / at most this value, as
+//! critical success instead of the max value. Doesn't change the total
+//! cf#, cf>#, cf<# : Marking the dice with this value, or at least / at most this value, as
+//! critical failure instead of 1. Doesn't change the total
+//!
+//! Options are case-insensitive (`2D6 KH1 R1`), except `K`, `k`, `D` and `d` where the case gives
+//! the meaning.
+//!
+//! Options only apply to the dice they follow: `2d6 kh1 + 2d8 kh1` keeps the highest d6 and the
+//! highest d8, and `2d6 + 2d8 kh1` keeps all the d6.
 //!
 //! Target:
 //! t#  : minimum value to count as success
@@ -49,6 +88,24 @@
 //! Failure:
 //! f# : value under which it's counted as failure
 //!
+//! Comparison, instead of target and failure:
+//! <#, <=#, >#, >=#, ==# : count as success each die which value satisfies the comparison.
+//! Without botch, no failure is subtracted: `6d6==6` is the number of 6s
+//! <#b, <=#b, >#b, >=#b, ==#b : same, but each 1 rolled cancels a success (botch). The total
+//! can't go under 0 unless allowed with `Roller::with_overbotch()`. A 1 satisfying the
+//! comparison, like with `<=3b`, is both a success and a botch: it counts for nothing
+//!
+//! Percentage:
+//! %# : succeed # percent of the time, rolled as `1d100<=#`
+//!
+//! Best and worst:
+//! `best(3, 1d20 + 5)` rolls `1d20 + 5` three times and keeps the highest total, `worst(3, 1d20 + 5)`
+//! keeps the lowest one. All the rolls are shown, the dice of the others being dropped.
+//!
+//! Exponent:
+//! `2 ^ 1d4` raises 2 to the power of a d4. `^` comes before the other operators and is right
+//! associative: `2 ^ 3 ^ 2` is `2 ^ 9`. The exponent can't be negative.
+//!
 //! Repetition:
 //! a roll can be repeated with `^` operator: `(2d6 + 6) ^ 8` will roll eight times the expression.
 //!
@@ -58,10 +115,19 @@
 //! Sorted repetition:
 //! with the `^#` operator, the roll will be repeated and sorted by total.
 //!
+//! A whole expression made of a block followed by `^` and a number, like `(1d6) ^ 2`, is a
+//! repetition. Inside a larger expression, like `(1d6) ^ 2 + 1`, or on a block without dice, like
+//! `(1 + 1) ^ 3`, `^` is the exponent.
+//!
 //! Reason:
 //! : : Any text after `:` will be a comment
 //! ```
 //!
+//! # Parsing
+//!
+//! An expression can be parsed without being rolled with [`ast::parse()`], to inspect its
+//! structure.
+//!
 //! # Helpers
 //!
 //! Some helpers are provided to interpret the roll result according to specific RPG rules.
@@ -72,14 +138,42 @@
 //! At the moment, the supported feature flags are:
 //! - `ova`: helper for "OVA: The Anime Role-Playing Game result"
 //! - `cde`: helper for "Hong Kong, Les Chroniques de l'étrange"
+//! - `table`: helper to find the entry of a table matching a roll
+//! - `stats`: helper to check a custom dice source for bias
+//! - `genesys`: helper to show the net symbols of Genesys-like narrative dice
 //!
 //! None is activated by default
 //!
+//! # Testing
+//!
+//! [`FixedDiceRollSource`] rolls with predetermined dice values, failing the roll when they run
+//! out. The `test-support` feature gives access to the `test_support` module, with more helpers to
+//! roll with predetermined dice values.
+//!
+//! # Secure rolls
+//!
+//! The `crypto` feature gives access to [`CryptoDiceRollSource`], rolling with the secure random
+//! number generator of the operating system.
+//!
+//! # Serialization
+//!
+//! The `serde` feature makes [`Roller`] and the [`ast`] types serializable with `serde`, to store
+//! parsed expressions and their settings. The die transform of a `Roller` can't be serialized:
+//! serializing a `Roller` with one fails.
+//!
 //! # Cards
 //!
 //! `caith` can create a standard deck of 52 cards plus optional Jokers if the feature `cards`
 //! is activated. See [`cards::Deck`].
 //!
+//! # Limitations
+//!
+//! The number of dice and their number of sides are not limited: `1000000000d6` rolls a billion
+//! dice. To roll untrusted expressions, [`Roller::roll_with_budget()`] bounds the number of dice
+//! rolled, explosions and rerolls included, and [`Roller::with_max_explosion_percent()`] caps the
+//! dice added by explosions. The nesting of parentheses is limited by
+//! [`Roller::with_max_depth()`].
+//!
 //! # Examples
 //!
 //! These examples are directly taken from DiceMaiden's Readme:
@@ -97,19 +191,42 @@
 //! assumed to be the same as the number of sides on the die. Thus, '3d6 e' is the same as '3d6 e6'.
 //! The dice will only explode once with this command. Use `ie` for indefinite explosions.
 //!
-//! `3d6 ie6` or `3d6!` : Roll three six-sided dice and explode on sixes indefinitely within reason.
-//! We will cap explosions at 100 rolls to prevent abuse.
+//! `3d6 ie6` or `3d6!` : Roll three six-sided dice and explode on sixes indefinitely. The number
+//! of explosions can be limited with `Roller::with_max_explosion_percent()`.
+//!
+//! `3d6 !b` : Same as `3d6!` but the exploded dice are not added to the total, they are counted
+//! in a separate bonus tally available with `RollResult::bonus_total()`.
 //!
 //! `3d10 d1` : Roll three ten-sided dice and drop one die. The lowest value will be dropped first.  
 //!
 //! `3d10 K2` : Roll three ten-sided dice and keep two. The highest value rolled will be kept.
 //! Using lowercase `k` will keep the lowest.
 //!
+//! `4d6 k[1,3]` : Roll four six-sided dice and keep the first and the third dice rolled. The dice
+//! are displayed in the order they were rolled.
+//!
 //! `4d6 r2` : Roll four six-sided dice and reroll any that are equal to or less than two once.
 //! Use `ir` for indefinite rerolls.
 //!
 //! `4d6 ir2` : Roll four six-sided dice and reroll any that are equal to or less than two (and do
-//! the same to those dice). This is capped at 100 rerolls per die to prevent abuse.
+//! the same to those dice). The rerolls stop when the dice source is exhausted.
+//!
+//! `4d6 ar1` : Roll four six-sided dice and reroll once each 1, the new die being added to the
+//! roll instead of replacing the 1.
+//!
+//! `1d20 ro1h` : Roll a twenty-sided die and reroll it once on a 1, keeping the higher of the two
+//! dice (like the Halfling's luck). The discarded die is shown in the history. Use `ro#l` to keep
+//! the lower one.
+//!
+//! `3d6 rp<6` : Roll three six-sided dice and roll them all again, once, if their total is below
+//! six. Both rolls are shown in the history, like `([~~1~~, ~~2~~, ~~1~~] | [4, 3, 5])`, the
+//! first one being dropped.
+//!
+//! `3d20 med` : Roll three twenty-sided dice and keep the middle one. With an even number of
+//! dice, the lower of the two middle dice is kept.
+//!
+//! `1d20 cs>18 cf<3` : Roll a twenty-sided die, marking it as a critical success on 18 or more
+//! and as a critical failure on 3 or less, like Roll20 does.
 //!
 //! `6d10 t7` : Roll six ten-sided dice and any that are seven or higher are counted as a success.
 //! The dice in the roll are not added together for a total. Any die that meets or exceeds the
@@ -126,6 +243,10 @@
 //!
 //! `3d6 t[2,4,6]` : only even result will count as success (handy for games like "Knight").
 //!
+//! `1d100<=65` : Roll a percentile die and count a success if it's 65 or under. For such a
+//! roll-under check, `RollResult::success_level()` tells if it's a hard (half the target) or an
+//! extreme (fifth of the target) success.
+//!
 //! `4d10 k3` : Roll four ten-sided dice and keep the lowest three dice rolled.
 //!
 //! `4d6 : Hello World!`: Roll four six-sided dice and add comment to the roll.
@@ -141,6 +262,10 @@ use pest::{
     Parser,
 };
 
+mod arithmetic;
+pub mod ast;
+mod comparator;
+mod distribution;
 pub mod helpers;
 
 mod error;
@@ -151,11 +276,19 @@ mod rollresult;
 #[cfg_attr(docsrs, doc(cfg(feature = "cards")))]
 pub mod cards;
 
+#[cfg(any(test, feature = "test-support"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-support")))]
+pub mod test_support;
+
+pub use comparator::Comparator;
+pub use distribution::Distribution;
 pub use error::*;
+pub use parser::{DiceRollSource, RollEvent, TotalModifier};
 pub use rollresult::*;
 
-use parser::{DiceRollSource, RollParser, Rule};
-use rand::Rng;
+use parser::{DieTransform, RollOptions, RollParser, Rule};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::convert::TryFrom;
 
 const REASON_CHAR: char = ':';
 
@@ -168,8 +301,14 @@ const REASON_CHAR: char = ':';
 /// see [Pest's issue](https://github.com/pest-parser/pest/issues/472)
 /// and [Forum topic](https://users.rust-lang.org/t/how-to-deal-with-external-type-which-is-send-and-sync/47530)
 ///
+/// It also holds the settings used when rolling, set with the `with_*` methods.
+///
 #[derive(Clone, Debug)]
-pub struct Roller(String);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Roller {
+    input: String,
+    options: RollOptions,
+}
 
 struct RngDiceRollSource<'a, T>
 where
@@ -183,236 +322,1161 @@ where
     T: Rng,
 {
     fn roll_single_die(&mut self, sides: u64) -> u64 {
-        self.rng.gen_range(1..1 + sides)
+        self.rng.gen_range(1..=sides)
     }
 }
 
-impl Roller {
-    /// Store the input
-    ///
-    /// As of version 2.0.0, it always returns `Ok(Self)`.
-    ///
-    /// This is to have a stable API for further optimization where the parsing is done here (so it
-    /// can fail) and saved, see `Roller` documentation above.
-    ///
-    pub fn new(input: &str) -> Result<Self> {
-        Ok(Roller(input.to_owned()))
+/// A [`DiceRollSource`] using a pseudo random number generator initialized with a seed.
+///
+/// The same seed gives the same dice, see [`Roller::roll_seeded()`].
+#[derive(Clone, Debug)]
+pub struct SeededDiceRollSource {
+    rng: StdRng,
+}
+
+impl SeededDiceRollSource {
+    /// Create a source from a seed
+    pub fn new(seed: u64) -> Self {
+        SeededDiceRollSource {
+            rng: StdRng::seed_from_u64(seed),
+        }
     }
+}
 
-    /// Evaluate and roll the dices with default Rng source (`rand::thread_rng()`)
-    pub fn roll(&self) -> Result<RollResult> {
-        self.roll_with(&mut rand::thread_rng())
+impl DiceRollSource for SeededDiceRollSource {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        self.rng.gen_range(1..=sides)
     }
+}
 
-    /// Evaluate and roll the dices with provided rng source
-    pub fn roll_with<RNG: Rng>(&self, rng: &mut RNG) -> Result<RollResult> {
-        self.roll_with_source(&mut RngDiceRollSource { rng })
+/// A [`DiceRollSource`] using the cryptographically secure random number generator of the
+/// operating system, for fairness-sensitive contexts like gambling or competitions.
+///
+/// The values are sampled without bias in the range of the die. The rolls are not deterministic
+/// and can't be reproduced, unlike with [`SeededDiceRollSource`].
+///
+/// ```
+/// use caith::{CryptoDiceRollSource, Roller};
+///
+/// let res = Roller::new("1d20")
+///     .unwrap()
+///     .roll_with_source(&mut CryptoDiceRollSource)
+///     .unwrap();
+/// ```
+#[cfg(feature = "crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "crypto")))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CryptoDiceRollSource;
+
+#[cfg(feature = "crypto")]
+impl DiceRollSource for CryptoDiceRollSource {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        rand::rngs::OsRng.gen_range(1..=sides)
     }
+}
 
-    /// Evaluate and roll the dice with provided dice roll source
-    pub fn roll_with_source<RNG: DiceRollSource>(&self, rng: &mut RNG) -> Result<RollResult> {
-        let mut pairs = RollParser::parse(Rule::command, &self.0)?;
-        let expr_type = pairs.next().unwrap();
-        let mut roll_res = match expr_type.as_rule() {
-            Rule::expr => {
-                RollResult::new_single(parser::compute(expr_type.into_inner(), rng, false)?)
-            }
-            Rule::repeated_expr => Roller::process_repeated_expr(expr_type, rng)?,
-            _ => unreachable!(),
-        };
+/// A [`DiceRollSource`] giving the values it owns, in order, like for deterministic tests.
+///
+/// Unlike [`Roller::roll_with_values()`], the values don't have to be all used, and the source
+/// can be used for several rolls. A roll needing more dice than there are values left fails with
+/// [`RollError::SourceExhausted`], and a value out of the range of its die fails it too.
+///
+/// ```
+/// use caith::{FixedDiceRollSource, RollError, Roller};
+///
+/// let r = Roller::new("2d6").unwrap();
+/// let mut source = FixedDiceRollSource::new(vec![3, 5, 6]);
+/// let res = r.roll_with_source(&mut source).unwrap();
+/// assert_eq!(8, res.as_single().unwrap().get_total());
+/// assert!(matches!(
+///     r.roll_with_source(&mut source),
+///     Err(RollError::SourceExhausted)
+/// ));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FixedDiceRollSource {
+    values: Vec<u64>,
+    cursor: usize,
+    error: Option<String>,
+    exhausted: bool,
+}
 
-        if let Some(reason) = pairs.next() {
-            if reason.as_rule() == Rule::reason {
-                roll_res.add_reason(reason.as_str()[1..].trim().to_owned());
-            }
+impl FixedDiceRollSource {
+    /// Create a source giving `values`, in order
+    pub fn new(values: Vec<u64>) -> Self {
+        FixedDiceRollSource {
+            values,
+            ..Default::default()
         }
-        Ok(roll_res)
     }
 
-    fn process_repeated_expr<RNG: DiceRollSource>(
-        expr_type: Pair<Rule>,
-        rng: &mut RNG,
-    ) -> Result<RollResult> {
-        let mut pairs = expr_type.into_inner();
-        let expr = pairs.next().unwrap();
-        let maybe_option = pairs.next().unwrap();
-        let (number, sum_all, sort) = match maybe_option.as_rule() {
-            Rule::number => (maybe_option.as_str().parse::<i64>().unwrap(), false, false),
-            Rule::add => (
-                pairs.next().unwrap().as_str().parse::<i64>().unwrap(),
-                true,
-                false,
-            ),
-            Rule::sort => (
-                pairs.next().unwrap().as_str().parse::<i64>().unwrap(),
-                false,
-                true,
-            ),
-            _ => unreachable!(),
-        };
-        if number <= 0 {
-            Err("Can't repeat 0 times or negatively".into())
-        } else {
-            let results: Result<Vec<SingleRollResult>> =
-                (0..number).try_fold(Vec::new(), |mut res, _| {
-                    let c = parser::compute(expr.clone().into_inner(), rng, false)?;
-                    res.push(c);
-                    Ok(res)
-                });
-            let mut results = results?;
-            if sort {
-                results.sort_unstable_by(|a, b| a.get_total().partial_cmp(&b.get_total()).unwrap());
+    /// Get the values not given yet
+    pub fn remaining(&self) -> &[u64] {
+        &self.values[self.cursor..]
+    }
+
+    /// Start again from the first value
+    pub fn reset(&mut self) {
+        self.cursor = 0;
+        self.error = None;
+        self.exhausted = false;
+    }
+}
+
+impl DiceRollSource for FixedDiceRollSource {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        if self.is_exhausted() {
+            return 1;
+        }
+        match self.values.get(self.cursor) {
+            Some(&value) if value >= 1 && value <= sides => {
+                self.cursor += 1;
+                value
+            }
+            Some(&value) => {
+                self.error = Some(format!("Value {} is out of range for a d{}", value, sides));
+                1
+            }
+            None => {
+                self.exhausted = true;
+                1
             }
-            let total = if sum_all {
-                Some(
-                    results
-                        .iter()
-                        .fold(0, |acc, current| acc + current.get_total()),
-                )
-            } else {
-                None
-            };
-            Ok(RollResult::new_repeated(results, total))
         }
     }
 
-    /// Get an iterator on the dices in the expression
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use caith::Roller;
-    ///
-    /// let r = Roller::new("1d6 + 1d4 + 1d10 + 1d20").unwrap();
-    /// assert_eq!(vec!["1d6", "1d4", "1d10", "1d20"], r.dices().expect("Error on parse").collect::<Vec<_>>());
-    /// ```
-    pub fn dices(&self) -> Result<Dices> {
-        let pairs = RollParser::parse(Rule::command, &self.0)?
-            .next()
-            .unwrap()
-            .into_inner();
-        Ok(Dices { pairs })
+    fn is_exhausted(&self) -> bool {
+        self.exhausted || self.error.is_some()
     }
 
-    /// Give back the query string
-    pub fn as_str(&self) -> &str {
-        &self.0
+    fn take_error(&mut self) -> Option<RollError> {
+        if let Some(e) = self.error.take() {
+            Some(e.into())
+        } else if std::mem::take(&mut self.exhausted) {
+            Some(RollError::SourceExhausted)
+        } else {
+            None
+        }
     }
+}
 
-    /// Removes the reason from the Roller
-    pub fn trim_reason(&mut self) {
-        if let Some(idx) = self.0.find(REASON_CHAR) {
-            self.0 = self.0[..idx].to_owned()
+// Gives the provided values, recording the first problem met instead of panicking
+struct ValuesDiceRollSource<'a> {
+    values: std::slice::Iter<'a, u64>,
+    error: Option<String>,
+}
+
+impl DiceRollSource for ValuesDiceRollSource<'_> {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        if self.error.is_some() {
+            return 1;
+        }
+        match self.values.next() {
+            Some(&value) if value >= 1 && value <= sides => value,
+            Some(&value) => {
+                self.error = Some(format!("Value {} is out of range for a d{}", value, sides));
+                1
+            }
+            None => {
+                self.error = Some("Not enough values for the dice to roll".to_owned());
+                1
+            }
         }
     }
+
+    fn is_exhausted(&self) -> bool {
+        self.error.is_some()
+    }
 }
 
-/// Iterator that lazily returns each dice of the expression.
-///
-/// See [`Roller::dices()`] for example
-///
-pub struct Dices<'a> {
-    pairs: Pairs<'a, Rule>,
+// Gives the provided values, rolling with another source where there is none, recording the
+// first value out of range instead of panicking
+struct PartialDiceRollSource<'a, RNG: DiceRollSource> {
+    source: &'a mut RNG,
+    values: std::slice::Iter<'a, Option<u64>>,
+    error: Option<String>,
 }
 
-impl<'a> Iterator for Dices<'a> {
-    type Item = String;
+impl<RNG: DiceRollSource> DiceRollSource for PartialDiceRollSource<'_, RNG> {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        if self.error.is_some() {
+            return 1;
+        }
+        match self.values.next() {
+            Some(Some(value)) if *value >= 1 && *value <= sides => *value,
+            Some(Some(value)) => {
+                self.error = Some(format!("Value {} is out of range for a d{}", value, sides));
+                1
+            }
+            Some(None) | None => self.source.roll_single_die(sides),
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        parser::find_first_dice(&mut self.pairs)
+    fn on_event(&mut self, event: &RollEvent) {
+        self.source.on_event(event);
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.error.is_some() || self.source.is_exhausted()
+    }
+
+    fn reserve(&mut self, count: u64) -> Result<()> {
+        self.source.reserve(count)
+    }
+
+    fn take_error(&mut self) -> Option<RollError> {
+        self.source.take_error()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// Counts the dice rolled by another source
+struct CountingDiceRollSource<'a, RNG: DiceRollSource> {
+    source: &'a mut RNG,
+    draws: u64,
+}
 
-    pub(crate) struct IteratorDiceRollSource<'a, T>
-    where
-        T: Iterator<Item = u64>,
-    {
-        pub iterator: &'a mut T,
+impl<RNG: DiceRollSource> DiceRollSource for CountingDiceRollSource<'_, RNG> {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        self.draws = self.draws.saturating_add(1);
+        self.source.roll_single_die(sides)
     }
 
-    impl<T> DiceRollSource for IteratorDiceRollSource<'_, T>
-    where
-        T: Iterator<Item = u64>,
-    {
-        fn roll_single_die(&mut self, sides: u64) -> u64 {
-            match self.iterator.next() {
-                Some(value) => {
-                    if value > sides {
-                        panic!("Tried to return {} for a {} sided dice", value, sides)
-                    }
-                    println!("Dice {}", value);
-                    value
-                }
-                None => panic!("Iterator out of values"),
-            }
+    fn on_event(&mut self, event: &RollEvent) {
+        self.source.on_event(event);
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.source.is_exhausted()
+    }
+
+    fn reserve(&mut self, count: u64) -> Result<()> {
+        self.source.reserve(count)
+    }
+
+    fn take_error(&mut self) -> Option<RollError> {
+        self.source.take_error()
+    }
+}
+
+// Counts the dice rolled by another source, giving 1s once more than `max` were rolled, and
+// refusing to roll a group of dice which doesn't fit in the budget left
+struct BudgetDiceRollSource<'a, RNG: DiceRollSource> {
+    source: &'a mut RNG,
+    max: u64,
+    rolled: u64,
+}
+
+impl<RNG: DiceRollSource> DiceRollSource for BudgetDiceRollSource<'_, RNG> {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        self.rolled = self.rolled.saturating_add(1);
+        if self.is_exhausted() {
+            1
+        } else {
+            self.source.roll_single_die(sides)
         }
     }
 
-    #[test]
-    fn get_repeat_test() {
-        let r = Roller::new("(2d6 + 6) ^ 8 : test").unwrap();
-        let roll_mock = vec![3, 5, 3, 5, 3, 5, 3, 5, 3, 5, 3, 5, 3, 5, 3, 5];
-        let roll_res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut roll_mock.into_iter(),
-            })
-            .unwrap();
-        match roll_res.get_result() {
-            rollresult::RollResultType::Single(_) => unreachable!(),
-            rollresult::RollResultType::Repeated(rep) => {
-                assert_eq!(8, rep.len());
-                for res in rep.iter() {
-                    assert_eq!(14, res.get_total());
-                }
-            }
+    fn on_event(&mut self, event: &RollEvent) {
+        if !self.is_exhausted() {
+            self.source.on_event(event);
         }
-        eprintln!();
-        for res in roll_res.as_repeated().unwrap().iter() {
-            eprintln!("{}", res)
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.rolled > self.max || self.source.is_exhausted()
+    }
+
+    fn reserve(&mut self, count: u64) -> Result<()> {
+        if count > self.max.saturating_sub(self.rolled) {
+            // exhaust the budget, stopping the rest of the roll
+            self.rolled = self.rolled.saturating_add(count);
+            Err(RollError::BudgetExceeded(self.max))
+        } else {
+            self.source.reserve(count)
         }
+    }
 
-        eprintln!();
-        eprintln!("{}", roll_res);
+    fn take_error(&mut self) -> Option<RollError> {
+        self.source.take_error()
     }
+}
 
-    #[test]
-    fn get_repeat_sort_test() {
-        let r = Roller::new("(2d6 + 6) ^# 8 : test").unwrap();
-        let roll_mock = vec![3, 5, 1, 1, 6, 5, 3, 5, 4, 5, 2, 4, 3, 5, 1, 2];
-        let mut expected = roll_mock
-            .as_slice()
-            .chunks(2)
-            .map(|two| two[0] as i64 + two[1] as i64 + 6)
-            .collect::<Vec<_>>();
-        expected.sort_unstable();
-        let roll_res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut roll_mock.into_iter(),
-            })
-            .unwrap();
-        match roll_res.get_result() {
-            rollresult::RollResultType::Single(_) => unreachable!(),
-            rollresult::RollResultType::Repeated(rep) => {
-                assert_eq!(8, rep.len());
+// Applies the die transform of the `Roller` on the dice of another source, recording the first
+// value out of range instead of panicking
+struct TransformDiceRollSource<'a, RNG: DiceRollSource> {
+    source: &'a mut RNG,
+    transform: &'a DieTransform,
+    error: Option<String>,
+}
 
-                let res_vec = rep.iter().map(|r| r.get_total()).collect::<Vec<_>>();
-                assert_eq!(expected, res_vec);
+impl<RNG: DiceRollSource> DiceRollSource for TransformDiceRollSource<'_, RNG> {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        let value = (self.transform.0)(sides, self.source.roll_single_die(sides));
+        if value == 0 || value > sides {
+            if self.error.is_none() {
+                self.error = Some(format!(
+                    "Transformed value {} is out of range for a d{}",
+                    value, sides
+                ));
             }
-        };
-        eprintln!("{}", roll_res);
+            1
+        } else {
+            value
+        }
     }
 
-    #[test]
-    fn get_repeat_sum_test() {
-        let r = Roller::new("(2d6 + 6) ^+ 2 : test").unwrap();
-        let roll_mock = vec![3, 5, 4, 2];
-        let expected = roll_mock
-            .as_slice()
-            .chunks(2)
+    fn on_event(&mut self, event: &RollEvent) {
+        self.source.on_event(event);
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.error.is_some() || self.source.is_exhausted()
+    }
+
+    fn reserve(&mut self, count: u64) -> Result<()> {
+        self.source.reserve(count)
+    }
+
+    fn take_error(&mut self) -> Option<RollError> {
+        self.source.take_error()
+    }
+}
+
+// Gives the events to an observer before passing them to the source
+struct ObservedDiceRollSource<'a, RNG, F>
+where
+    RNG: DiceRollSource,
+    F: FnMut(&RollEvent),
+{
+    source: &'a mut RNG,
+    observer: F,
+}
+
+impl<RNG, F> DiceRollSource for ObservedDiceRollSource<'_, RNG, F>
+where
+    RNG: DiceRollSource,
+    F: FnMut(&RollEvent),
+{
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        self.source.roll_single_die(sides)
+    }
+
+    fn on_event(&mut self, event: &RollEvent) {
+        (self.observer)(event);
+        self.source.on_event(event);
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.source.is_exhausted()
+    }
+
+    fn reserve(&mut self, count: u64) -> Result<()> {
+        self.source.reserve(count)
+    }
+
+    fn take_error(&mut self) -> Option<RollError> {
+        self.source.take_error()
+    }
+}
+
+impl Roller {
+    /// Store the input
+    ///
+    /// As of version 2.0.0, it always returns `Ok(Self)`.
+    ///
+    /// This is to have a stable API for further optimization where the parsing is done here (so it
+    /// can fail) and saved, see `Roller` documentation above.
+    ///
+    pub fn new(input: &str) -> Result<Self> {
+        Ok(Roller {
+            input: input.to_owned(),
+            options: RollOptions::default(),
+        })
+    }
+
+    /// Check that an expression can be rolled, without rolling it.
+    ///
+    /// The expression is fully parsed and the errors that don't depend on the dice rolled are
+    /// reported: syntax, numbers out of range, dice with 0 sides, contradictory options, positions
+    /// to keep out of the pool, nesting depth... The default settings of a `Roller` are used.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// assert!(Roller::validate("4d6 K3 : strength").is_ok());
+    /// assert!(Roller::validate("4d6 K3 k1").is_err());
+    /// ```
+    pub fn validate(input: &str) -> Result<()> {
+        ast::parse(input).map(|_| ())
+    }
+
+    /// Use custom thresholds to read the Fudge dice
+    pub fn with_fudge_config(mut self, config: FudgeConfig) -> Self {
+        self.options.fudge = config;
+        self
+    }
+
+    /// Roll an extra Fudge die for each `+` rolled, the extra dice exploding again on `+`. The
+    /// extra dice are added to the group, `4dF` may then show more than 4 dice. Like the
+    /// indefinite explosions of the other dice, they stop when the dice source is exhausted or
+    /// when the limit set with [`Roller::with_max_explosion_percent()`] is reached, reporting
+    /// [`Warning::ExplosionCapReached`]. Fudge dice don't explode by default.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let r = Roller::new("2dF").unwrap().with_fudge_explosions(true);
+    /// let res = r.roll_with_values(&[6, 3, 5, 1]).unwrap();
+    /// assert_eq!("[+, +, ▢, -]", res.history_string());
+    /// assert_eq!(1, res.as_single().unwrap().get_total());
+    /// ```
+    pub fn with_fudge_explosions(mut self, explode: bool) -> Self {
+        self.options.fudge_explode = explode;
+        self
+    }
+
+    /// Transform each die rolled before it's recorded: `transform` gets the number of sides of the
+    /// die and the rolled value, and returns the value to use instead.
+    ///
+    /// Rolling returns an error if a transformed value isn't between 1 and the number of sides.
+    /// The transform can't be serialized, serializing the `Roller` fails.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// // a cursed die always rolls 1
+    /// let r = Roller::new("3d6").unwrap().with_die_transform(|_, _| 1);
+    /// assert_eq!(3, r.roll().unwrap().as_single().unwrap().get_total());
+    /// ```
+    pub fn with_die_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(u64, u64) -> u64 + Send + Sync + 'static,
+    {
+        self.options.die_transform = Some(DieTransform(std::sync::Arc::new(transform)));
+        self
+    }
+
+    /// Allow the total of a pool with botches (ex: `6d10>=7b`) to go under 0 when there are more
+    /// 1s than successes. By default, the total is floored at 0.
+    pub fn with_overbotch(mut self, allow: bool) -> Self {
+        self.options.overbotch = allow;
+        self
+    }
+
+    /// Record the span of the input each step of the history comes from, available with
+    /// [`SingleRollResult::get_history_views()`]. Not recorded by default.
+    pub fn with_spans(mut self, record: bool) -> Self {
+        self.options.spans = record;
+        self
+    }
+
+    /// Lower the total to `cap` when it's above, like for a damage cap. The history is kept as
+    /// rolled, and [`RollResult::is_capped()`] says if the cap was hit. For a repeated roll, each
+    /// roll is capped.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let r = Roller::new("3d6 + 2").unwrap().with_total_cap(10);
+    /// let res = r.roll_with_values(&[6, 5, 4]).unwrap();
+    /// assert_eq!(10, res.as_single().unwrap().get_total());
+    /// assert!(res.is_capped());
+    /// ```
+    pub fn with_total_cap(mut self, cap: i64) -> Self {
+        self.options.total_cap = Some(cap);
+        self
+    }
+
+    /// Raise the total to `floor` when it's below, like to never deal negative damage. The history
+    /// is kept as rolled, and [`Warning::TotalFloored`] is reported if the floor was hit. For a
+    /// repeated roll, each roll is floored. When a cap is given too, it's applied after the floor.
+    ///
+    /// ```
+    /// use caith::{Roller, Warning};
+    ///
+    /// let r = Roller::new("2d6 - 10").unwrap().with_total_floor(0);
+    /// let res = r.roll_with_values(&[3, 4]).unwrap();
+    /// assert_eq!(0, res.as_single().unwrap().get_total());
+    /// assert_eq!(&[Warning::TotalFloored], res.warnings());
+    /// ```
+    pub fn with_total_floor(mut self, floor: i64) -> Self {
+        self.options.total_floor = Some(floor);
+        self
+    }
+
+    /// Limit the dice added by explosions to `percent` % of the dice of each group, like `200` for
+    /// at most 8 more dice on `4d6!`. The explosions beyond the limit are not rolled: the roll
+    /// stops there without failing, and [`Warning::ExplosionCapReached`] is reported. The number of
+    /// explosions is not limited by default, the indefinite ones only stop when the dice source is
+    /// exhausted.
+    ///
+    /// ```
+    /// use caith::{Roller, Warning};
+    ///
+    /// let r = Roller::new("2d6!").unwrap().with_max_explosion_percent(100);
+    /// let res = r.roll_with_values(&[6, 6, 6, 6]).unwrap();
+    /// assert_eq!(24, res.as_single().unwrap().get_total());
+    /// assert_eq!(&[Warning::ExplosionCapReached], res.warnings());
+    /// ```
+    pub fn with_max_explosion_percent(mut self, percent: u64) -> Self {
+        self.options.max_explosion_percent = Some(percent);
+        self
+    }
+
+    /// Set the maximum number of nested parentheses, 64 by default. Rolling an expression nested
+    /// deeper returns [`RollError::TooDeep`], protecting from inputs that would exhaust the stack.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.options.max_depth = max_depth;
+        self
+    }
+
+    /// Register a die with named faces, rolled with its name between braces like `2d{story}`.
+    /// Each face has the same chance to be rolled, a face can be repeated to make it more likely.
+    /// Registering a name again replaces its faces.
+    ///
+    /// The faces rolled are shown in the history and available in [`RollHistory::Faces`], they
+    /// don't count in the total. Rolling a name which wasn't registered is an error.
+    ///
+    /// The name must be made of ASCII letters, and the die needs at least one face.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let r = Roller::new("2d{story} + 1d6")
+    ///     .unwrap()
+    ///     .with_die("story", &["Hope", "Fear", "Neutral"])
+    ///     .unwrap();
+    /// let res = r.roll_with_values(&[2, 3, 4]).unwrap();
+    /// assert_eq!("`[Fear, Neutral] + [4]` = **4**", res.to_string());
+    /// ```
+    pub fn with_die(mut self, name: &str, faces: &[&str]) -> Result<Self> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("Invalid die name `{}`, only letters are allowed", name).into());
+        }
+        if faces.is_empty() {
+            return Err(format!("Die `{}` needs at least one face", name).into());
+        }
+        self.options.named_dice.insert(
+            name.to_owned(),
+            faces.iter().map(|face| face.to_string()).collect(),
+        );
+        Ok(self)
+    }
+
+    /// Compute the exact probability of each possible total of the expression, without rolling.
+    ///
+    /// Only sums, differences, products and divisions of dice without options and integers are
+    /// supported. An error is returned for other expressions, like exploding dice which have no
+    /// upper bound, or when there are too many outcomes to compute. The die transform set with
+    /// [`Roller::with_die_transform()`] and the Fudge explosions set with
+    /// [`Roller::with_fudge_explosions()`] are not taken into account.
+    pub fn distribution(&self) -> Result<Distribution> {
+        distribution::distribution(&ast::parse(&self.input)?.command, self.options.fudge)
+    }
+
+    /// Probability of the total being greater than or equal to `dc`, see
+    /// [`Roller::distribution()`] for the supported expressions.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// // chance to hit AC 15 with a +5 bonus
+    /// let p = Roller::new("1d20 + 5").unwrap().probability_at_least(15).unwrap();
+    /// assert!((p - 0.55).abs() < 1e-9);
+    /// ```
+    pub fn probability_at_least(&self, dc: i64) -> Result<f64> {
+        Ok(self.distribution()?.range(dc..).map(|(_, p)| p).sum())
+    }
+
+    /// Probability of the total being lower than or equal to `dc`, see
+    /// [`Roller::distribution()`] for the supported expressions.
+    pub fn probability_at_most(&self, dc: i64) -> Result<f64> {
+        Ok(self.distribution()?.range(..=dc).map(|(_, p)| p).sum())
+    }
+
+    /// Evaluate and roll the dices with default Rng source (`rand::thread_rng()`)
+    pub fn roll(&self) -> Result<RollResult> {
+        self.roll_with(&mut rand::thread_rng())
+    }
+
+    /// Evaluate and roll the dices with a pseudo random number generator initialized with `seed`.
+    ///
+    /// Rolling the same expression with the same seed always gives the same result, dice and
+    /// history included. This is only guaranteed for a given version of `caith`: the generator
+    /// may change between versions.
+    pub fn roll_seeded(&self, seed: u64) -> Result<RollResult> {
+        self.roll_with_source(&mut SeededDiceRollSource::new(seed))
+    }
+
+    /// Evaluate the expression using the provided values as dice results, in the order the dice
+    /// are rolled. Useful to enter dice rolled physically.
+    ///
+    /// Each value must be in the range of the die it's used for, and there must be exactly one
+    /// value per die rolled, explosions and rerolls included.
+    pub fn roll_with_values(&self, values: &[u64]) -> Result<RollResult> {
+        let mut source = ValuesDiceRollSource {
+            values: values.iter(),
+            error: None,
+        };
+        let res = self.roll_with_source(&mut source)?;
+        if let Some(e) = source.error {
+            return Err(e.into());
+        }
+        let left = source.values.len();
+        if left > 0 {
+            return Err(format!("Too many values, {} left unused", left).into());
+        }
+        Ok(res)
+    }
+
+    /// Evaluate the expression using the provided values for some dice, and rolling the other
+    /// ones with `rng`, like when some of the dice were rolled physically.
+    ///
+    /// `values` follows the order the dice are rolled, explosions and rerolls included: a die is
+    /// given the value when it's `Some`, and is rolled when it's `None`. The dice after the last
+    /// value are rolled too. Each value must be in the range of the die it's used for, and all of
+    /// them must be used.
+    ///
+    /// ```
+    /// use caith::{Roller, SeededDiceRollSource};
+    ///
+    /// let r = Roller::new("4d6").unwrap();
+    /// let mut source = SeededDiceRollSource::new(42);
+    /// let res = r
+    ///     .roll_with_partial(&[Some(3), None, Some(5), None], &mut source)
+    ///     .unwrap();
+    /// let dice: Vec<u64> = res.as_single().unwrap().kept_dice().iter().map(|d| d.res).collect();
+    /// assert_eq!(4, dice.len());
+    /// assert!(dice.contains(&3) && dice.contains(&5));
+    /// ```
+    pub fn roll_with_partial<RNG: DiceRollSource>(
+        &self,
+        values: &[Option<u64>],
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        let mut source = PartialDiceRollSource {
+            source: rng,
+            values: values.iter(),
+            error: None,
+        };
+        let res = self.roll_with_source(&mut source);
+        if let Some(e) = source.error {
+            return Err(e.into());
+        }
+        let left = source.values.filter(|v| v.is_some()).count();
+        if left > 0 {
+            return Err(format!("Too many values, {} left unused", left).into());
+        }
+        res
+    }
+
+    /// Evaluate and roll the dices with provided rng source
+    pub fn roll_with<RNG: Rng>(&self, rng: &mut RNG) -> Result<RollResult> {
+        self.roll_with_source(&mut RngDiceRollSource { rng })
+    }
+
+    /// Evaluate and roll the dice with provided dice roll source
+    pub fn roll_with_source<RNG: DiceRollSource>(&self, rng: &mut RNG) -> Result<RollResult> {
+        let pairs = RollParser::parse(Rule::command, &self.input)?;
+        self.roll_parsed(pairs, None, rng)
+    }
+
+    /// Roll the expression `n` times with the same dice roll source, parsing it only once.
+    ///
+    /// The rolls draw from `rng` one after the other, explosions and rerolls included, so it gives
+    /// the same results as calling [`Roller::roll_with_source()`] `n` times. Useful to sample a
+    /// distribution. Stops at the first error.
+    ///
+    /// ```
+    /// use caith::{Roller, SeededDiceRollSource};
+    ///
+    /// let r = Roller::new("3d6").unwrap();
+    /// let mut source = SeededDiceRollSource::new(42);
+    /// let results = r.roll_n_with_source(1000, &mut source).unwrap();
+    /// assert_eq!(1000, results.len());
+    /// ```
+    pub fn roll_n_with_source<RNG: DiceRollSource>(
+        &self,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<RollResult>> {
+        let pairs = RollParser::parse(Rule::command, &self.input)?;
+        (0..n)
+            .map(|_| self.roll_parsed(pairs.clone(), None, rng))
+            .collect()
+    }
+
+    // Roll the parsed expression, computing its total with `modifier` if given
+    fn roll_parsed<RNG: DiceRollSource>(
+        &self,
+        pairs: Pairs<Rule>,
+        modifier: Option<TotalModifier>,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        let mut rng = CountingDiceRollSource {
+            source: rng,
+            draws: 0,
+        };
+        let res = match &self.options.die_transform {
+            Some(transform) => {
+                let mut source = TransformDiceRollSource {
+                    source: &mut rng,
+                    transform,
+                    error: None,
+                };
+                let res = self.evaluate(pairs, modifier, &mut source);
+                match source.error {
+                    Some(e) => Err(e.into()),
+                    None => res,
+                }
+            }
+            None => self.evaluate(pairs, modifier, &mut rng),
+        };
+        // a problem of the source explains the roll better than the error it may have caused
+        match rng.take_error() {
+            Some(e) => Err(e),
+            None => res.map(|mut res| {
+                res.set_draw_count(rng.draws);
+                res
+            }),
+        }
+    }
+
+    /// Evaluate and roll the dice with provided dice roll source, failing with
+    /// [`RollError::BudgetExceeded`] if more than `max_dice` dice are rolled. All the dice count,
+    /// including the explosions and rerolls, to bound the work done for untrusted expressions: a
+    /// group of dice which doesn't fit in the budget left stops the roll before being rolled.
+    ///
+    /// ```
+    /// use caith::{Roller, RollError, SeededDiceRollSource};
+    ///
+    /// let r = Roller::new("10d6").unwrap();
+    /// let mut source = SeededDiceRollSource::new(42);
+    /// assert!(r.roll_with_budget(&mut source, 10).is_ok());
+    /// assert!(matches!(
+    ///     r.roll_with_budget(&mut source, 9),
+    ///     Err(RollError::BudgetExceeded(9))
+    /// ));
+    /// ```
+    pub fn roll_with_budget<RNG: DiceRollSource>(
+        &self,
+        rng: &mut RNG,
+        max_dice: u64,
+    ) -> Result<RollResult> {
+        let mut source = BudgetDiceRollSource {
+            source: rng,
+            max: max_dice,
+            rolled: 0,
+        };
+        let res = self.roll_with_source(&mut source);
+        if source.rolled > max_dice {
+            Err(RollError::BudgetExceeded(max_dice))
+        } else {
+            res
+        }
+    }
+
+    /// Roll a plain dice expression like `4d6`, keeping the `n` highest dice, like `4d6 K3`.
+    ///
+    /// The typed `roll_*` methods apply an option without building the expression, like for an
+    /// UI with buttons. The expression must be a single dice term without option, the reason is
+    /// allowed.
+    ///
+    /// ```
+    /// use caith::{Roller, SeededDiceRollSource};
+    ///
+    /// let res = Roller::new("4d6 : strength")
+    ///     .unwrap()
+    ///     .roll_keep_highest(3, &mut SeededDiceRollSource::new(42))
+    ///     .unwrap();
+    /// println!("{}", res);
+    /// ```
+    pub fn roll_keep_highest<RNG: DiceRollSource>(
+        &self,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        self.roll_with_modifier(TotalModifier::KeepHi(n), rng)
+    }
+
+    /// Roll a plain dice expression keeping the `n` lowest dice, like `4d6 k3`. See
+    /// [`Roller::roll_keep_highest()`].
+    pub fn roll_keep_lowest<RNG: DiceRollSource>(
+        &self,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        self.roll_with_modifier(TotalModifier::KeepLo(n), rng)
+    }
+
+    /// Roll a plain dice expression dropping the `n` highest dice, like `4d6 D1`. See
+    /// [`Roller::roll_keep_highest()`].
+    pub fn roll_drop_highest<RNG: DiceRollSource>(
+        &self,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        self.roll_with_modifier(TotalModifier::DropHi(n), rng)
+    }
+
+    /// Roll a plain dice expression dropping the `n` lowest dice, like `4d6 d1`. See
+    /// [`Roller::roll_keep_highest()`].
+    pub fn roll_drop_lowest<RNG: DiceRollSource>(
+        &self,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        self.roll_with_modifier(TotalModifier::DropLo(n), rng)
+    }
+
+    /// Roll a plain dice expression counting the successes, the dice of `value` or more, like
+    /// `6d10 t7`. See [`Roller::roll_keep_highest()`].
+    pub fn roll_target<RNG: DiceRollSource>(
+        &self,
+        value: u64,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        if value == 0 {
+            return Err("Target can't be 0".into());
+        }
+        self.roll_with_modifier(TotalModifier::TargetFailureDouble(value, 0, 0), rng)
+    }
+
+    /// Roll the expression and resolve it as a check against a difficulty class in one call,
+    /// counting one degree every `degree_size` points of margin. See [`RollResult::check()`].
+    ///
+    /// When `roll_under` is set, the total must be lower than or equal to `dc`, like for a
+    /// percentile skill, otherwise it must be greater than or equal to `dc`.
+    ///
+    /// ```
+    /// use caith::{FixedDiceRollSource, Roller};
+    ///
+    /// let r = Roller::new("1d20 + 5").unwrap();
+    /// let mut source = FixedDiceRollSource::new(vec![18]);
+    /// let check = r.resolve_check(15, 5, false, &mut source).unwrap();
+    /// assert!(check.success);
+    /// assert_eq!(1, check.degrees);
+    /// ```
+    pub fn resolve_check<RNG: DiceRollSource>(
+        &self,
+        dc: i64,
+        degree_size: u64,
+        roll_under: bool,
+        rng: &mut RNG,
+    ) -> Result<CheckResult> {
+        if degree_size == 0 {
+            return Err("Degree size can't be 0".into());
+        }
+        Ok(self
+            .roll_with_source(rng)?
+            .check(dc, degree_size, roll_under))
+    }
+
+    // Roll a single dice term without option and compute its total with `modifier`
+    fn roll_with_modifier<RNG: DiceRollSource>(
+        &self,
+        modifier: TotalModifier,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        let count =
+            match ast::parse(&self.input)?.command {
+                ast::Command::Expr(ast::Expr::Dice(ast::Dice {
+                    count,
+                    sides: ast::Sides::Number(_),
+                    options,
+                })) if options.is_empty() => count,
+                _ => return Err(
+                    "Options can only be applied on a single dice term without option, like `4d6`"
+                        .into(),
+                ),
+            };
+        match modifier {
+            TotalModifier::KeepHi(0) | TotalModifier::KeepLo(0) => {
+                return Err("Can't keep 0 dice".into())
+            }
+            TotalModifier::KeepHi(n) | TotalModifier::KeepLo(n) if n as u64 > count => {
+                return Err(format!("Can't keep {} dice out of {}", n, count).into())
+            }
+            TotalModifier::DropHi(n) | TotalModifier::DropLo(n) if n as u64 > count => {
+                return Err(format!("Can't drop {} dice out of {}", n, count).into())
+            }
+            _ => (),
+        }
+        let pairs = RollParser::parse(Rule::command, &self.input)?;
+        self.roll_parsed(pairs, Some(modifier), rng)
+    }
+
+    /// Evaluate and roll the dice with provided dice roll source, calling `observer` for each die
+    /// as it's rolled, like to animate the roll. See [`RollEvent`].
+    ///
+    /// ```
+    /// use caith::{RollEvent, Roller, SeededDiceRollSource};
+    ///
+    /// let mut events = Vec::new();
+    /// let res = Roller::new("2d6!")
+    ///     .unwrap()
+    ///     .roll_with_observer(&mut SeededDiceRollSource::new(42), |event| events.push(*event))
+    ///     .unwrap();
+    /// assert!(events.len() >= 2);
+    /// ```
+    pub fn roll_with_observer<RNG, F>(&self, rng: &mut RNG, observer: F) -> Result<RollResult>
+    where
+        RNG: DiceRollSource,
+        F: FnMut(&RollEvent),
+    {
+        self.roll_with_source(&mut ObservedDiceRollSource {
+            source: rng,
+            observer,
+        })
+    }
+
+    fn evaluate<RNG: DiceRollSource>(
+        &self,
+        mut pairs: Pairs<Rule>,
+        modifier: Option<TotalModifier>,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        let expr_type = pairs.next().unwrap();
+        let mut roll_res = match expr_type.as_rule() {
+            Rule::expr => RollResult::new_single(parser::compute(
+                expr_type.into_inner(),
+                rng,
+                &self.options,
+                0,
+                &mut 0,
+            )?),
+            Rule::repeated_expr => self.process_repeated_expr(expr_type, rng)?,
+            _ => unreachable!(),
+        };
+        if let Some(modifier) = modifier {
+            roll_res.apply_modifier(modifier)?;
+        }
+        roll_res.clamp_total(self.options.total_floor, self.options.total_cap)?;
+
+        if let Some(reason) = pairs.next() {
+            if reason.as_rule() == Rule::reason {
+                roll_res.add_reason(reason.as_str()[1..].trim().to_owned());
+            }
+        }
+        Ok(roll_res)
+    }
+
+    fn process_repeated_expr<RNG: DiceRollSource>(
+        &self,
+        expr_type: Pair<Rule>,
+        rng: &mut RNG,
+    ) -> Result<RollResult> {
+        let mut pairs = expr_type.into_inner();
+        let expr = pairs.next().unwrap();
+        let maybe_option = pairs.next().unwrap();
+        let (number, sum_all, sort) = match maybe_option.as_rule() {
+            Rule::number => (parser::parse_number::<i64>(&maybe_option)?, false, false),
+            Rule::add => (parser::parse_number(&pairs.next().unwrap())?, true, false),
+            Rule::sort => (parser::parse_number(&pairs.next().unwrap())?, false, true),
+            _ => unreachable!(),
+        };
+        if number <= 0 {
+            Err("Can't repeat 0 times or negatively".into())
+        } else {
+            let results: Result<Vec<SingleRollResult>> =
+                (0..number).try_fold(Vec::new(), |mut res, _| {
+                    let c =
+                        parser::compute(expr.clone().into_inner(), rng, &self.options, 0, &mut 0)?;
+                    res.push(c);
+                    Ok(res)
+                });
+            let mut results = results?;
+            if sort {
+                results.sort_unstable_by(|a, b| a.get_total().partial_cmp(&b.get_total()).unwrap());
+            }
+            let total = if sum_all {
+                Some(
+                    results
+                        .iter()
+                        .try_fold(0i64, |acc, current| acc.checked_add(current.get_total()))
+                        .ok_or("Total is too big to be computed")?,
+                )
+            } else {
+                None
+            };
+            Ok(RollResult::new_repeated(results, total))
+        }
+    }
+
+    /// Get an iterator on the dices in the expression
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let r = Roller::new("1d6 + 1d4 + 1d10 + 1d20").unwrap();
+    /// assert_eq!(vec!["1d6", "1d4", "1d10", "1d20"], r.dices().expect("Error on parse").collect::<Vec<_>>());
+    /// ```
+    pub fn dices(&self) -> Result<Dices<'_>> {
+        let pairs = RollParser::parse(Rule::command, &self.input)?
+            .next()
+            .unwrap()
+            .into_inner();
+        Ok(Dices { pairs })
+    }
+
+    /// Get the number and the kind of dice of each dice group in the expression, in order,
+    /// without rolling it. Constants are left out.
+    ///
+    /// The number is `None` when it's rolled, like in `(1d4)d6`, unless its expression has no dice
+    /// like `(2 + 1)d6`. The dice rolling the number are listed before the group. A die with
+    /// named faces counts as many sides as faces. The groups in `best`, `worst` or a repeated
+    /// expression are listed once.
+    ///
+    /// ```
+    /// use caith::{ast::Sides, Roller};
+    ///
+    /// let r = Roller::new("2d6 + 1d8 + 3").unwrap();
+    /// assert_eq!(
+    ///     vec![(Some(2), Sides::Number(6)), (Some(1), Sides::Number(8))],
+    ///     r.sides_of_each_group().unwrap()
+    /// );
+    /// ```
+    pub fn sides_of_each_group(&self) -> Result<Vec<(Option<u64>, ast::Sides)>> {
+        let expr = match ast::parse(&self.input)?.command {
+            ast::Command::Expr(expr) | ast::Command::Repeated { expr, .. } => expr,
+        };
+        let mut groups = Vec::new();
+        self.collect_groups(&expr, &mut groups)?;
+        Ok(groups)
+    }
+
+    // Push the dice groups of `expr` to `groups`, see `Roller::sides_of_each_group()`
+    fn collect_groups(
+        &self,
+        expr: &ast::Expr,
+        groups: &mut Vec<(Option<u64>, ast::Sides)>,
+    ) -> Result<()> {
+        match expr {
+            ast::Expr::Dice(dice) => groups.push((Some(dice.count), dice.sides)),
+            ast::Expr::RolledCountDice { count, dice } => {
+                self.collect_groups(count, groups)?;
+                // a count without dice has a single possible value
+                let resolved = distribution::expr_distribution(count, self.options.fudge)
+                    .ok()
+                    .filter(|d| d.len() == 1)
+                    .and_then(|d| d.keys().next().copied())
+                    .and_then(|c| u64::try_from(c).ok());
+                groups.push((resolved, dice.sides));
+            }
+            ast::Expr::NamedDice { count, name } => {
+                let faces = self.options.named_dice.get(name).ok_or_else(|| {
+                    format!("Unknown die `{}`, it must be registered first", name)
+                })?;
+                groups.push((Some(*count), ast::Sides::Number(faces.len() as u64)));
+            }
+            ast::Expr::Block(expr)
+            | ast::Expr::Best { expr, .. }
+            | ast::Expr::Worst { expr, .. } => self.collect_groups(expr, groups)?,
+            ast::Expr::BinOp { lhs, rhs, .. } => {
+                self.collect_groups(lhs, groups)?;
+                self.collect_groups(rhs, groups)?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Give back the query string
+    pub fn as_str(&self) -> &str {
+        &self.input
+    }
+
+    /// Removes the reason from the Roller
+    pub fn trim_reason(&mut self) {
+        if let Some(idx) = self.input.find(REASON_CHAR) {
+            self.input = self.input[..idx].to_owned()
+        }
+    }
+}
+
+/// Iterator that lazily returns each dice of the expression.
+///
+/// See [`Roller::dices()`] for example
+///
+pub struct Dices<'a> {
+    pairs: Pairs<'a, Rule>,
+}
+
+impl<'a> Iterator for Dices<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        parser::find_first_dice(&mut self.pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::IteratorDiceRollSource;
+
+    #[test]
+    fn get_repeat_test() {
+        let r = Roller::new("(2d6 + 6) ^ 8 : test").unwrap();
+        let roll_mock = vec![3, 5, 3, 5, 3, 5, 3, 5, 3, 5, 3, 5, 3, 5, 3, 5];
+        let roll_res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.into_iter(),
+            })
+            .unwrap();
+        match roll_res.get_result() {
+            rollresult::RollResultType::Single(_) => unreachable!(),
+            rollresult::RollResultType::Repeated(rep) => {
+                assert_eq!(8, rep.len());
+                for res in rep.iter() {
+                    assert_eq!(14, res.get_total());
+                }
+            }
+        }
+        eprintln!();
+        for res in roll_res.as_repeated().unwrap().iter() {
+            eprintln!("{}", res)
+        }
+
+        eprintln!();
+        eprintln!("{}", roll_res);
+    }
+
+    #[test]
+    fn get_repeat_sort_test() {
+        let r = Roller::new("(2d6 + 6) ^# 8 : test").unwrap();
+        let roll_mock = vec![3, 5, 1, 1, 6, 5, 3, 5, 4, 5, 2, 4, 3, 5, 1, 2];
+        let mut expected = roll_mock
+            .as_slice()
+            .chunks(2)
+            .map(|two| two[0] as i64 + two[1] as i64 + 6)
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+        let roll_res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.into_iter(),
+            })
+            .unwrap();
+        match roll_res.get_result() {
+            rollresult::RollResultType::Single(_) => unreachable!(),
+            rollresult::RollResultType::Repeated(rep) => {
+                assert_eq!(8, rep.len());
+
+                let res_vec = rep.iter().map(|r| r.get_total()).collect::<Vec<_>>();
+                assert_eq!(expected, res_vec);
+            }
+        };
+        eprintln!("{}", roll_res);
+    }
+
+    #[test]
+    fn get_repeat_sum_test() {
+        let r = Roller::new("(2d6 + 6) ^+ 2 : test").unwrap();
+        let roll_mock = vec![3, 5, 4, 2];
+        let expected = roll_mock
+            .as_slice()
+            .chunks(2)
             .map(|two| two[0] as i64 + two[1] as i64 + 6)
             .collect::<Vec<_>>();
         let expected: i64 = expected.iter().sum();
@@ -421,306 +1485,2237 @@ mod tests {
                 iterator: &mut roll_mock.into_iter(),
             })
             .unwrap();
-        match roll_res.get_result() {
-            rollresult::RollResultType::Single(_) => unreachable!(),
-            rollresult::RollResultType::Repeated(rep) => {
-                assert_eq!(2, rep.len());
-                assert_eq!(expected, rep.get_total().unwrap());
+        match roll_res.get_result() {
+            rollresult::RollResultType::Single(_) => unreachable!(),
+            rollresult::RollResultType::Repeated(rep) => {
+                assert_eq!(2, rep.len());
+                assert_eq!(expected, rep.get_total().unwrap());
+            }
+        }
+        eprintln!();
+        eprintln!("{}", roll_res);
+    }
+
+    #[test]
+    fn get_single_test() {
+        let r = Roller::new("2d6 + 6 : test").unwrap();
+        let roll_mock = vec![3, 5];
+        let expected = roll_mock
+            .as_slice()
+            .chunks(2)
+            .map(|two| two[0] as i64 + two[1] as i64)
+            .collect::<Vec<_>>();
+        let expected = expected.iter().sum::<i64>() + 6;
+        let roll_res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.into_iter(),
+            })
+            .unwrap();
+        match roll_res.get_result() {
+            rollresult::RollResultType::Single(res) => assert_eq!(expected, res.get_total()),
+            rollresult::RollResultType::Repeated(_) => unreachable!(),
+        }
+        eprintln!();
+        eprintln!("{}", roll_res.as_single().unwrap());
+    }
+
+    #[test]
+    fn one_value_test() {
+        let r = Roller::new("20").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(20, res.get_total());
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn one_dice_test() {
+        let r = Roller::new("d20").unwrap();
+        let roll_mock = vec![8];
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.into_iter(),
+            })
+            .unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(8, res.get_total());
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn implicit_one_dice_test() {
+        let r = Roller::new("d6+d8").unwrap();
+        assert_eq!(vec!["d6", "d8"], r.dices().unwrap().collect::<Vec<_>>());
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![4, 7].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(11, res.as_single().unwrap().get_total());
+        assert_eq!("[4] + [7]", res.as_single().unwrap().to_string_history());
+
+        let r = Roller::new("2d6+d4").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![1, 2, 3].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(6, res.as_single().unwrap().get_total());
+
+        let r = Roller::new("(d6 + d4) ^# 2").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![6, 4, 1, 1].into_iter(),
+            })
+            .unwrap();
+        let totals = res
+            .as_repeated()
+            .unwrap()
+            .iter()
+            .map(|r| r.get_total())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![2, 10], totals);
+
+        let r = Roller::new("(d6) * 2").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![5].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(10, res.as_single().unwrap().get_total());
+    }
+
+    #[test]
+    fn zero_dice_test() {
+        assert!(matches!(
+            Roller::new("0d6").unwrap().roll(),
+            Err(RollError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn float_mul_test() {
+        let r = Roller::new("20 * 1.5").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(30, res.get_total());
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn float_signed_mul_test() {
+        let r = Roller::new("20 * +1.5").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(30, res.get_total());
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn float_neg_signed_mul_test() {
+        let r = Roller::new("20 * -1.5").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(-30, res.get_total());
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn float_add_test() {
+        let r = Roller::new("20 + 1.5").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(21, res.get_total());
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn float_signed_add_test() {
+        let r = Roller::new("20 + +1.5").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(21, res.get_total());
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn float_neg_signed_add_test() {
+        let r = Roller::new("20 + -1.5").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(18, res.get_total());
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn signed_add_test() {
+        let r = Roller::new("20 + +5").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(25, res.get_total());
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn signed_neg_add_test() {
+        let r = Roller::new("20 + -5").unwrap();
+        let res = r.roll().unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(15, res.get_total());
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn pow_test() {
+        let total = |input: &str, values: &[u64]| {
+            Roller::new(input)
+                .unwrap()
+                .roll_with_values(values)
+                .map(|res| res.as_single().unwrap().get_total())
+        };
+        assert_eq!(8, total("2^3", &[]).unwrap());
+        assert_eq!(16, total("2 ^ 1d4", &[4]).unwrap());
+        // before `*` and right associative
+        assert_eq!(18, total("2 * 3 ^ 2", &[]).unwrap());
+        assert_eq!(512, total("2 ^ 3 ^ 2", &[]).unwrap());
+        assert_eq!(10, total("(1d6) ^ 2 + 1", &[3]).unwrap());
+        // a block without dice can't be repeated, it's raised to the power
+        assert_eq!(8, total("(1 + 1) ^ 3", &[]).unwrap());
+        assert_eq!(8, total("((1 + 1))^3 : reason", &[]).unwrap());
+        assert_eq!(1, total("5 ^ 0", &[]).unwrap());
+
+        match total("10 ^ 20", &[]) {
+            Err(RollError::ParamError(e)) => assert_eq!("Result is too big to be computed", e),
+            _ => unreachable!(),
+        }
+        match total("2 ^ (1 - 1d6)", &[3]) {
+            Err(RollError::ParamError(e)) => assert_eq!("Exponent can't be negative", e),
+            _ => unreachable!(),
+        }
+
+        let res = Roller::new("2 ^ 1d4")
+            .unwrap()
+            .roll_with_values(&[3])
+            .unwrap();
+        assert_eq!("2 ^ [3]", res.as_single().unwrap().to_string_history());
+        // a whole block to a power is still a repetition
+        assert!(Roller::new("(1d6) ^ 2")
+            .unwrap()
+            .roll()
+            .unwrap()
+            .as_repeated()
+            .is_some());
+        assert!(Roller::new("((1 + 1) * 1d6) ^ 2")
+            .unwrap()
+            .roll()
+            .unwrap()
+            .as_repeated()
+            .is_some());
+
+        let r = Roller::new("2 ^ 1d2").unwrap();
+        assert!((r.probability_at_least(4).unwrap() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn best_worst_test() {
+        let r = Roller::new("best(3, 1d20 + 5)").unwrap();
+        let res = r.roll_with_values(&[8, 15, 12]).unwrap();
+        let single = res.as_single().unwrap();
+        assert_eq!(20, single.get_total());
+        assert_eq!(
+            "([8] + 5 | [15] + 5 | [12] + 5)",
+            single.to_string_history()
+        );
+        let marked = DisplayConfig {
+            show_dropped: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            "([~~8~~] + 5 | [15] + 5 | [~~12~~] + 5)",
+            single.to_string_history_with(&marked)
+        );
+        assert_eq!(
+            vec![15],
+            single.kept_dice().iter().map(|d| d.res).collect::<Vec<_>>()
+        );
+        // the attempts not kept don't count
+        assert_eq!(20, res.total_with(TotalModifier::None).unwrap());
+
+        let r = Roller::new("WORST(3, 1d20) * 2").unwrap();
+        let res = r.roll_with_values(&[12, 15, 4]).unwrap();
+        assert_eq!(8, res.as_single().unwrap().get_total());
+        assert_eq!(
+            "([12] | [15] | [4]) * 2",
+            res.as_single().unwrap().to_string_history()
+        );
+
+        // disadvantage on a damage roll: the lower second attempt is kept
+        let res = Roller::new("worst(2, 2d6 + 3)")
+            .unwrap()
+            .roll_with_values(&[6, 5, 2, 3])
+            .unwrap();
+        let single = res.as_single().unwrap();
+        assert_eq!(8, single.get_total());
+        assert_eq!(
+            "([~~6~~, ~~5~~] + 3 | [3, 2] + 3)",
+            single.to_string_history_with(&marked)
+        );
+        assert_eq!(
+            vec![3, 2],
+            single.kept_dice().iter().map(|d| d.res).collect::<Vec<_>>()
+        );
+        assert_eq!(8, res.total_with(TotalModifier::None).unwrap());
+        assert_eq!(3, res.total_with(TotalModifier::KeepHi(1)).unwrap());
+
+        // the first of equal totals is kept
+        let res = Roller::new("best(2, (1d6))")
+            .unwrap()
+            .roll_with_values(&[4, 4])
+            .unwrap();
+        assert_eq!(
+            "(([4]) | ([~~4~~]))",
+            res.as_single().unwrap().to_string_history_with(&marked)
+        );
+
+        match Roller::new("best(0, 1d20)").unwrap().roll() {
+            Err(RollError::ParamError(e)) => {
+                assert_eq!("`best` and `worst` need at least one roll", e)
+            }
+            _ => unreachable!(),
+        }
+        assert!(Roller::validate("best(0, 1d20)").is_err());
+
+        let r = Roller::new("best(2, 1d2)").unwrap();
+        assert!((r.probability_at_least(2).unwrap() - 0.75).abs() < 1e-9);
+        let r = Roller::new("worst(2, 1d2)").unwrap();
+        assert!((r.probability_at_least(2).unwrap() - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rolled_count_test() {
+        let r = Roller::new("(1d4)d6").unwrap();
+        let res = r.roll_with_values(&[3, 5, 2, 6]).unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(13, res.get_total());
+        assert_eq!("([3])d[6, 5, 2]", res.to_string_history());
+
+        let res = Roller::new("(1d4 + 1)d6 K2 + 1")
+            .unwrap()
+            .roll_with_values(&[1, 5, 2])
+            .unwrap();
+        assert_eq!(8, res.as_single().unwrap().get_total());
+        assert_eq!(
+            "([1] + 1)d[5, 2] + 1",
+            res.as_single().unwrap().to_string_history()
+        );
+
+        match Roller::new("(1d4 - 1)d6").unwrap().roll_with_values(&[1]) {
+            Err(RollError::ParamError(e)) => assert_eq!("Can't roll 0 dice", e),
+            _ => unreachable!(),
+        }
+        // a block not followed by `d` is still a block
+        let res = Roller::new("(1d4) + 2")
+            .unwrap()
+            .roll_with_values(&[3])
+            .unwrap();
+        assert_eq!(5, res.as_single().unwrap().get_total());
+        assert!(Roller::new("(1d4)d6").unwrap().distribution().is_err());
+        assert!(matches!(
+            crate::ast::parse("(1d4)d6").unwrap().command,
+            crate::ast::Command::Expr(crate::ast::Expr::RolledCountDice { .. })
+        ));
+    }
+
+    #[test]
+    fn counting_roller_test() {
+        let r = Roller::new("3d6").unwrap();
+        let rolls = vec![3, 6, 3];
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap();
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            assert_eq!(res.get_total(), 12);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn target_number_test() {
+        let r = Roller::new("10d10 t7").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut (1..11),
+            })
+            .unwrap();
+        println!("{}", res);
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            // We rolled one of every number, with a target number of 7 we should score a success
+            // on the 7, 8, 9, and 10. So four total.
+            assert_eq!(res.get_total(), 4);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn target_number_double_test() {
+        let r = Roller::new("10d10 t7 tt9").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut (1..11),
+            })
+            .unwrap();
+        println!("{}", res);
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            // We rolled one of every number. That's a success each for the 7 and 8, and two
+            // success each for the 9 and 10. So a toal of six.
+            assert_eq!(res.get_total(), 6);
+        } else {
+            unreachable!();
+        }
+    }
+
+    // Where a user has asked for a doubles threashold that is lower than the single threashold,
+    // the single threashold is ignored.
+    #[test]
+    fn target_number_double_lower_than_target_test() {
+        let r = Roller::new("10d10 tt7 t9").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut (1..11),
+            })
+            .unwrap();
+        println!("{}", res);
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            // We rolled one of every number. That's two successes each for the 7, 8, 9, and 10.
+            // So eight total.
+            assert_eq!(res.get_total(), 8);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn keep_then_compare_test() {
+        let marked = DisplayConfig {
+            show_dropped: Some(true),
+            ..Default::default()
+        };
+        // only the kept dice count successes, and they are not added again to the history
+        let res = Roller::new("4d6K3>=4")
+            .unwrap()
+            .roll_with_values(&[6, 5, 1, 4])
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(3, res.get_total());
+        assert_eq!("[6, 5, 4, ~~1~~]", res.to_string_history_with(&marked));
+
+        let res = Roller::new("4d6k3>=4")
+            .unwrap()
+            .roll_with_values(&[6, 5, 4, 4])
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(3, res.get_total());
+        assert_eq!("[~~6~~, 5, 4, 4]", res.to_string_history_with(&marked));
+
+        let res = Roller::new("4d6K3t4")
+            .unwrap()
+            .roll_with_values(&[6, 5, 1, 4])
+            .unwrap();
+        assert_eq!(3, res.as_single().unwrap().get_total());
+    }
+
+    // Where a user has asked for a doubles without singles.
+    #[test]
+    fn target_number_double_only() {
+        let r = Roller::new("10d10 tt8").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut (1..11),
+            })
+            .unwrap();
+        println!("{}", res);
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            // We rolled one of every number. That's two successes each for the 8, 9, and 10.
+            // So six total.
+            assert_eq!(res.get_total(), 6);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn target_enum() {
+        let r = Roller::new("6d6 t[2,4,6]").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut (1..7),
+            })
+            .unwrap();
+        println!("{}", res);
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            // We rolled one of every number. That's half of them being even
+            assert_eq!(res.get_total(), 3);
+        } else {
+            unreachable!();
+        }
+
+        let mock = vec![1, 2, 2, 4, 6, 3];
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut mock.into_iter(),
+            })
+            .unwrap();
+        println!("{}", res);
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            // We rolled one of every number. That's half of them being even
+            assert_eq!(res.get_total(), 4);
+        } else {
+            unreachable!();
+        }
+
+        let mock = vec![1, 3, 3, 4, 6, 3];
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut mock.into_iter(),
+            })
+            .unwrap();
+        println!("{}", res);
+        let res = res.get_result();
+        if let RollResultType::Single(res) = res {
+            // We rolled one of every number. That's half of them being even
+            assert_eq!(res.get_total(), 2);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn explode_once_test() {
+        // the exploded die maxes too, but doesn't explode again
+        let res = Roller::new("1d6 !o")
+            .unwrap()
+            .roll_with_values(&[6, 6])
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(12, res.get_total());
+        assert_eq!(1, res.get_explosion_count());
+        assert_eq!("[6][6]", res.to_string_history());
+
+        let res = Roller::new("3d6 !o5b")
+            .unwrap()
+            .roll_with_values(&[5, 2, 6, 6, 5])
+            .unwrap();
+        assert_eq!(13, res.as_single().unwrap().get_total());
+        assert_eq!(11, res.bonus_total());
+
+        assert_eq!(ast::parse("2d8 e8").unwrap(), ast::parse("2d8 !o").unwrap());
+        assert_eq!(
+            ast::parse("2d8 e5b").unwrap(),
+            ast::parse("2d8 !O5b").unwrap()
+        );
+    }
+
+    #[test]
+    fn explode_bonus_test() {
+        let r = Roller::new("2d6 e6b").unwrap();
+        let roll_mock = vec![6, 3, 4];
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.into_iter(),
+            })
+            .unwrap();
+        assert_eq!(9, res.as_single().unwrap().get_total());
+        assert_eq!(4, res.bonus_total());
+        assert_eq!(
+            "[6, 3] (bonus [4])",
+            res.as_single().unwrap().to_string_history()
+        );
+
+        let r = Roller::new("2d6 !b").unwrap();
+        let roll_mock = vec![6, 3, 6, 2];
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.into_iter(),
+            })
+            .unwrap();
+        assert_eq!(9, res.as_single().unwrap().get_total());
+        assert_eq!(8, res.bonus_total());
+
+        // without the suffix, exploded dice are in the total
+        let r = Roller::new("2d6 !").unwrap();
+        let roll_mock = vec![6, 3, 6, 2];
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.into_iter(),
+            })
+            .unwrap();
+        assert_eq!(17, res.as_single().unwrap().get_total());
+        assert_eq!(0, res.bonus_total());
+    }
+
+    #[test]
+    fn per_group_keep_test() {
+        let values = [2, 5, 7, 3];
+        let total = |input: &str| {
+            Roller::new(input)
+                .unwrap()
+                .roll_with_values(&values)
+                .unwrap()
+                .as_single()
+                .unwrap()
+                .get_total()
+        };
+        assert_eq!(12, total("(2d6kh1) + (2d8kh1)"));
+        assert_eq!(12, total("2d6kh1 + 2d8kh1"));
+        assert_eq!(14, total("2d6 + 2d8 kh1"));
+        assert_eq!(5, total("2d6kl1 + 2d8kl1"));
+
+        let res = Roller::new("2d6kh1 + 2d8kh1")
+            .unwrap()
+            .roll_with_values(&values)
+            .unwrap();
+        let kept: Vec<u64> = res
+            .as_single()
+            .unwrap()
+            .kept_dice()
+            .iter()
+            .map(|d| d.res)
+            .collect();
+        assert_eq!(vec![5, 7], kept);
+    }
+
+    #[test]
+    fn keep_indices_test() {
+        let r = Roller::new("4d6 k[1,3]").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![2, 6, 5, 1].into_iter(),
+            })
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(7, res.get_total());
+        assert_eq!("[2, 6, 5, 1]", res.to_string_history());
+    }
+
+    #[test]
+    fn keep_indices_out_of_range_test() {
+        let r = Roller::new("4d6 k[1,5]").unwrap();
+        let res = r.roll_with_source(&mut IteratorDiceRollSource {
+            iterator: &mut vec![2, 6, 5, 1].into_iter(),
+        });
+        match res {
+            Err(RollError::ParamError(e)) => {
+                assert_eq!("Can't keep die at position 5, positions go from 1 to 4", e)
+            }
+            _ => unreachable!(),
+        }
+
+        let r = Roller::new("4d6 k[0]").unwrap();
+        assert!(r.roll().is_err());
+        let r = Roller::new("4d6 k[2,2]").unwrap();
+        assert!(r.roll().is_err());
+    }
+
+    #[test]
+    fn roll_seeded_test() {
+        let r = Roller::new("10d100 + 1d20 ! : seeded").unwrap();
+        let res1 = r.roll_seeded(42).unwrap();
+        let res2 = r.roll_seeded(42).unwrap();
+        let res1 = res1.as_single().unwrap();
+        let res2 = res2.as_single().unwrap();
+        assert_eq!(res1.get_total(), res2.get_total());
+        assert_eq!(res1.to_string_history(), res2.to_string_history());
+
+        let res3 = r.roll_seeded(43).unwrap();
+        assert_ne!(
+            res1.to_string_history(),
+            res3.as_single().unwrap().to_string_history()
+        );
+    }
+
+    #[test]
+    fn fudge_test() {
+        let r = Roller::new("6dF").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut (1..7),
+            })
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(0, res.get_total());
+        assert_eq!("[+, +, ▢, ▢, -, -]", res.to_string_history());
+    }
+
+    #[test]
+    fn fudge_explosions_test() {
+        let r = Roller::new("1dF").unwrap().with_fudge_explosions(true);
+        // two `+` in a row, then a blank stops it
+        let res = r.roll_with_values(&[6, 5, 3]).unwrap();
+        let single = res.as_single().unwrap();
+        assert_eq!(2, single.get_total());
+        assert_eq!(2, single.get_explosion_count());
+        assert_eq!("[+, +, ▢]", single.to_string_history());
+        assert!(res.warnings().is_empty());
+
+        // a `+` explodes once
+        let res = r.roll_with_values(&[5, 1]).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
+        assert_eq!(1, res.as_single().unwrap().get_explosion_count());
+
+        let r = Roller::new("2dF")
+            .unwrap()
+            .with_fudge_explosions(true)
+            .with_max_explosion_percent(50);
+        let res = r.roll_with_values(&[6, 6, 6]).unwrap();
+        assert_eq!(3, res.as_single().unwrap().get_total());
+        assert_eq!(&[Warning::ExplosionCapReached], res.warnings());
+
+        // not by default
+        let res = Roller::new("1dF").unwrap().roll_with_values(&[6]).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_explosion_count());
+    }
+
+    #[test]
+    fn fudge_config_test() {
+        let r = Roller::new("6dF").unwrap().with_fudge_config(FudgeConfig {
+            minus_max: 1,
+            blank_max: 5,
+        });
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![1, 2, 6, 6, 5, 3].into_iter(),
+            })
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(1, res.get_total());
+        assert_eq!("[+, +, ▢, ▢, ▢, -]", res.to_string_history());
+
+        let r = Roller::new("4dF").unwrap().with_fudge_config(FudgeConfig {
+            minus_max: 4,
+            blank_max: 2,
+        });
+        assert!(r.roll().is_err());
+    }
+
+    #[test]
+    fn roll_with_values_test() {
+        let r = Roller::new("4d6 + 1d8").unwrap();
+        let res = r.roll_with_values(&[3, 6, 2, 5, 8]).unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(24, res.get_total());
+        assert_eq!("[6, 5, 3, 2] + [8]", res.to_string_history());
+
+        match r.roll_with_values(&[3, 6, 2, 5]) {
+            Err(RollError::ParamError(e)) => {
+                assert_eq!("Not enough values for the dice to roll", e)
+            }
+            _ => unreachable!(),
+        }
+        match r.roll_with_values(&[3, 7, 2, 5, 8]) {
+            Err(RollError::ParamError(e)) => assert_eq!("Value 7 is out of range for a d6", e),
+            _ => unreachable!(),
+        }
+        match r.roll_with_values(&[3, 6, 2, 5, 0]) {
+            Err(RollError::ParamError(e)) => assert_eq!("Value 0 is out of range for a d8", e),
+            _ => unreachable!(),
+        }
+        match r.roll_with_values(&[3, 6, 2, 5, 8, 1]) {
+            Err(RollError::ParamError(e)) => assert_eq!("Too many values, 1 left unused", e),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn outcome_test() {
+        let res = Roller::new("3d6 + 2")
+            .unwrap()
+            .roll_with_values(&[1, 2, 3])
+            .unwrap();
+        assert_eq!(Outcome::Sum(8), res.outcome());
+
+        let res = Roller::new("6d10 t7 tt10 f1")
+            .unwrap()
+            .roll_with_values(&[7, 10, 1, 3, 8, 1])
+            .unwrap();
+        assert_eq!(2, res.as_single().unwrap().get_total());
+        assert_eq!(
+            Outcome::SuccessCount {
+                successes: 4,
+                failures: 2
+            },
+            res.outcome()
+        );
+
+        // the total of a botched pool doesn't go under 0, the count keeps all the failures
+        let res = Roller::new("4d10>=7b")
+            .unwrap()
+            .roll_with_values(&[1, 1, 8, 2])
+            .unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
+        assert_eq!(
+            Outcome::SuccessCount {
+                successes: 1,
+                failures: 2
+            },
+            res.outcome()
+        );
+
+        let res = Roller::new("4dF")
+            .unwrap()
+            .roll_with_values(&[1, 6, 5, 3])
+            .unwrap();
+        assert_eq!(Outcome::Fudge(1), res.outcome());
+
+        let res = Roller::new("(2d6) ^+ 2")
+            .unwrap()
+            .roll_with_values(&[1, 2, 3, 4])
+            .unwrap();
+        assert_eq!(Outcome::Sum(10), res.outcome());
+    }
+
+    #[test]
+    fn count_matching_test() {
+        let r = Roller::new("6d6==6").unwrap();
+        let res = r.roll_with_values(&[6, 6, 2, 6, 1, 3]).unwrap();
+        assert_eq!(3, res.as_single().unwrap().get_total());
+        assert_eq!(
+            Outcome::SuccessCount {
+                successes: 3,
+                failures: 0
+            },
+            res.outcome()
+        );
+        let res = r.roll_with_values(&[5, 4, 2, 3, 1, 3]).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
+    }
+
+    #[test]
+    fn resolve_check_test() {
+        let r = Roller::new("1d20 + 2").unwrap();
+        // success by two degrees
+        let check = r
+            .resolve_check(10, 4, false, &mut FixedDiceRollSource::new(vec![17]))
+            .unwrap();
+        assert_eq!(
+            CheckResult {
+                total: 19,
+                success: true,
+                degrees: 2
+            },
+            check
+        );
+        // failure by one degree
+        let check = r
+            .resolve_check(10, 4, false, &mut FixedDiceRollSource::new(vec![5]))
+            .unwrap();
+        assert_eq!(
+            CheckResult {
+                total: 7,
+                success: false,
+                degrees: -1
+            },
+            check
+        );
+
+        let r = Roller::new("1d100").unwrap();
+        let check = r
+            .resolve_check(65, 10, true, &mut FixedDiceRollSource::new(vec![42]))
+            .unwrap();
+        assert!(check.success);
+        assert_eq!(2, check.degrees);
+        let check = r
+            .resolve_check(65, 10, true, &mut FixedDiceRollSource::new(vec![70]))
+            .unwrap();
+        assert!(!check.success);
+        assert_eq!(-1, check.degrees);
+
+        assert!(r
+            .resolve_check(65, 0, true, &mut FixedDiceRollSource::new(vec![70]))
+            .is_err());
+    }
+
+    #[test]
+    fn roll_under_test() {
+        let r = Roller::new("1d100<=65").unwrap();
+        let res = r.roll_with_values(&[31]).unwrap();
+        assert_eq!(1, res.as_single().unwrap().get_total());
+        assert_eq!(Some(SuccessLevel::Hard), res.success_level());
+        assert!(res.success_level().unwrap().is_success());
+
+        let res = r.roll_with_values(&[13]).unwrap();
+        assert_eq!(Some(SuccessLevel::Extreme), res.success_level());
+        let res = r.roll_with_values(&[65]).unwrap();
+        assert_eq!(Some(SuccessLevel::Regular), res.success_level());
+        let res = r.roll_with_values(&[66]).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
+        assert_eq!(Some(SuccessLevel::Failure), res.success_level());
+
+        let r = Roller::new("5d6 >= 5").unwrap();
+        let res = r.roll_with_values(&[5, 2, 6, 4, 1]).unwrap();
+        assert_eq!(2, res.as_single().unwrap().get_total());
+        assert_eq!(None, res.success_level());
+        let res = Roller::new("5d6 < 3")
+            .unwrap()
+            .roll_with_values(&[5, 2, 6, 4, 1])
+            .unwrap();
+        assert_eq!(2, res.as_single().unwrap().get_total());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        let r = Roller::new("4d6 K3 + 1dF : stat")
+            .unwrap()
+            .with_total_cap(15)
+            .with_fudge_config(FudgeConfig {
+                minus_max: 1,
+                blank_max: 5,
+            });
+        let json = serde_json::to_string(&r).unwrap();
+        let restored: Roller = serde_json::from_str(&json).unwrap();
+        for seed in 0..20 {
+            assert_eq!(
+                r.roll_seeded(seed).unwrap(),
+                restored.roll_seeded(seed).unwrap()
+            );
+        }
+
+        // the die transform would be lost
+        assert!(serde_json::to_string(&r.with_die_transform(|_, _| 1)).is_err());
+
+        let ast = ast::parse("best(2, (1d4)d6 ro1h + 2) * 3 : test").unwrap();
+        let json = serde_json::to_string(&ast).unwrap();
+        assert_eq!(ast, serde_json::from_str::<ast::Ast>(&json).unwrap());
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn crypto_source_test() {
+        let mut source = CryptoDiceRollSource;
+        for sides in [1, 2, 6, 20, 100, u64::MAX].iter() {
+            for _ in 0..1000 {
+                let value = source.roll_single_die(*sides);
+                assert!(value >= 1 && value <= *sides, "{} on a d{}", value, sides);
             }
         }
-        eprintln!();
-        eprintln!("{}", roll_res);
+        let res = Roller::new("10d6")
+            .unwrap()
+            .roll_with_source(&mut source)
+            .unwrap();
+        let total = res.as_single().unwrap().get_total();
+        assert!((10..=60).contains(&total));
     }
 
     #[test]
-    fn get_single_test() {
-        let r = Roller::new("2d6 + 6 : test").unwrap();
-        let roll_mock = vec![3, 5];
-        let expected = roll_mock
-            .as_slice()
-            .chunks(2)
-            .map(|two| two[0] as i64 + two[1] as i64)
-            .collect::<Vec<_>>();
-        let expected = expected.iter().sum::<i64>() + 6;
-        let roll_res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut roll_mock.into_iter(),
+    fn reason_is_literal_test() {
+        // only the 1d20 is rolled, a second value would be left unused
+        let r = Roller::new("1d20! : rolling 2d6 + 3 ^ 2 for fun: (maybe)").unwrap();
+        let res = r.roll_with_values(&[12]).unwrap();
+        assert_eq!(12, res.as_single().unwrap().get_total());
+        assert_eq!(
+            Some(&"rolling 2d6 + 3 ^ 2 for fun: (maybe)".to_owned()),
+            res.get_reason()
+        );
+        assert_eq!(vec!["1d20!"], r.dices().unwrap().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn roll_n_with_source_test() {
+        // the explosions and rerolls of each roll take their values from the shared source
+        let r = Roller::new("1d6! + 1d6 r1 : test").unwrap();
+        let values = vec![6, 2, 1, 4, 3, 5, 6, 6, 1, 2];
+        let results = r
+            .roll_n_with_source(
+                3,
+                &mut IteratorDiceRollSource {
+                    iterator: &mut values.clone().into_iter(),
+                },
+            )
+            .unwrap();
+        let totals: Vec<_> = results
+            .iter()
+            .map(|res| res.as_single().unwrap().get_total())
+            .collect();
+        assert_eq!(vec![12, 8, 15], totals);
+        assert!(results
+            .iter()
+            .all(|res| res.get_reason().map(String::as_str) == Some("test")));
+
+        let mut source = IteratorDiceRollSource {
+            iterator: &mut values.into_iter(),
+        };
+        let one_by_one: Vec<_> = (0..3)
+            .map(|_| r.roll_with_source(&mut source).unwrap())
+            .collect();
+        assert_eq!(one_by_one, results);
+
+        assert!(r
+            .roll_n_with_source(0, &mut SeededDiceRollSource::new(1))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn partial_values_test() {
+        let r = Roller::new("4d6 + 1d8").unwrap();
+        let mut source = FixedDiceRollSource::new(vec![2, 6, 7]);
+        let res = r
+            .roll_with_partial(&[Some(3), None, Some(5), None], &mut source)
+            .unwrap();
+        assert_eq!(
+            "[6, 5, 3, 2] + [7]",
+            res.as_single().unwrap().to_string_history()
+        );
+        assert!(source.remaining().is_empty());
+
+        let mut source = SeededDiceRollSource::new(7);
+        for _ in 0..20 {
+            let res = r
+                .roll_with_partial(&[None, Some(6), None, Some(1), Some(8)], &mut source)
+                .unwrap();
+            let dice = res.as_single().unwrap().kept_dice();
+            let values: Vec<_> = dice.iter().map(|d| d.res).collect();
+            assert!(values.contains(&6) && values.contains(&1), "{:?}", values);
+            assert_eq!(8, values[4]);
+            assert!(values[..4].iter().all(|v| (1..=6).contains(v)));
+        }
+
+        let err = |values: &[Option<u64>]| {
+            r.roll_with_partial(values, &mut SeededDiceRollSource::new(1))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!("Value 7 is out of range for a d6", err(&[None, Some(7)]));
+        assert_eq!(
+            "Too many values, 1 left unused",
+            err(&[None, None, None, None, None, None, Some(2)])
+        );
+    }
+
+    #[test]
+    fn fixed_source_test() {
+        let r = Roller::new("2d6 + 1d4").unwrap();
+        let mut source = FixedDiceRollSource::new(vec![3, 5, 2, 6, 6]);
+        let res = r.roll_with_source(&mut source).unwrap();
+        assert_eq!(10, res.as_single().unwrap().get_total());
+        assert_eq!(&[6, 6], source.remaining());
+
+        // running out of values fails the roll instead of panicking
+        match r.roll_with_source(&mut source) {
+            Err(RollError::SourceExhausted) => (),
+            _ => unreachable!(),
+        }
+        assert!(source.remaining().is_empty());
+        assert_eq!(
+            "Not enough values for the dice to roll",
+            RollError::SourceExhausted.to_string()
+        );
+
+        // an indefinite explosion stops when the values run out
+        assert!(matches!(
+            Roller::new("1d6!")
+                .unwrap()
+                .roll_with_source(&mut FixedDiceRollSource::new(vec![6, 6, 6])),
+            Err(RollError::SourceExhausted)
+        ));
+
+        // the source can be reused from the start, and through the other ways to roll
+        source.reset();
+        let results = r.roll_n_with_source(2, &mut source);
+        assert!(matches!(results, Err(RollError::SourceExhausted)));
+        source.reset();
+        assert!(r.roll_with_budget(&mut source, 3).is_ok());
+
+        let mut source = FixedDiceRollSource::new(vec![7]);
+        match r.roll_with_source(&mut source) {
+            Err(RollError::ParamError(e)) => assert_eq!("Value 7 is out of range for a d6", e),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn budget_test() {
+        // always rolls the maximum, exploding forever without a budget
+        struct MaxSource;
+        impl DiceRollSource for MaxSource {
+            fn roll_single_die(&mut self, sides: u64) -> u64 {
+                sides
+            }
+        }
+
+        let r = Roller::new("2d6! + 1d4").unwrap();
+        match r.roll_with_budget(&mut MaxSource, 50) {
+            Err(RollError::BudgetExceeded(50)) => (),
+            _ => unreachable!(),
+        }
+        let r = Roller::new("3d6 ir5").unwrap();
+        assert!(matches!(
+            r.roll_with_budget(&mut MaxSource, 2),
+            Err(RollError::BudgetExceeded(2))
+        ));
+        assert_eq!(
+            18,
+            r.roll_with_budget(&mut MaxSource, 3)
+                .unwrap()
+                .as_single()
+                .unwrap()
+                .get_total()
+        );
+
+        // a group of dice over the budget is refused before rolling any of them
+        struct CountingSource(u64);
+        impl DiceRollSource for CountingSource {
+            fn roll_single_die(&mut self, _sides: u64) -> u64 {
+                self.0 += 1;
+                1
+            }
+        }
+
+        let mut source = CountingSource(0);
+        let r = Roller::new("4d6 + 1000000000d6 + 1d6").unwrap();
+        assert!(matches!(
+            r.roll_with_budget(&mut source, 10),
+            Err(RollError::BudgetExceeded(10))
+        ));
+        assert_eq!(4, source.0);
+
+        // running out of values stops the indefinite rerolls too
+        match Roller::new("1d6 ir1").unwrap().roll_with_values(&[1]) {
+            Err(RollError::ParamError(e)) => {
+                assert_eq!("Not enough values for the dice to roll", e)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn draw_count_test() {
+        let r = Roller::new("2d6!").unwrap();
+        let res = r.roll_with_values(&[6, 3, 2]).unwrap();
+        assert_eq!(1, res.as_single().unwrap().get_explosion_count());
+        assert_eq!(3, res.draw_count());
+
+        // the source is drawn from, whatever the transform gives
+        let res = r
+            .clone()
+            .with_die_transform(|_, _| 1)
+            .roll_with_source(&mut FixedDiceRollSource::new(vec![6, 6]))
+            .unwrap();
+        assert_eq!(2, res.draw_count());
+
+        let res = Roller::new("(1d6 ro1h) ^ 3")
+            .unwrap()
+            .roll_with_values(&[1, 4, 5, 1, 2])
+            .unwrap();
+        assert_eq!(5, res.draw_count());
+        let sum = res
+            + Roller::new("1d4 + 2")
+                .unwrap()
+                .roll_with_values(&[3])
+                .unwrap();
+        assert_eq!(6, sum.draw_count());
+
+        // the count isn't compared
+        let rolled = Roller::new("1d6").unwrap().roll_with_values(&[4]).unwrap();
+        let built = RollResult::from_parts(4, vec![RollHistory::roll(&[4], 6)], None).unwrap();
+        assert_eq!(1, rolled.draw_count());
+        assert_eq!(0, built.draw_count());
+        assert_eq!(rolled, built);
+        assert_eq!(0, Roller::new("3").unwrap().roll().unwrap().draw_count());
+    }
+
+    #[test]
+    fn max_explosion_percent_test() {
+        struct MaxSource;
+        impl DiceRollSource for MaxSource {
+            fn roll_single_die(&mut self, sides: u64) -> u64 {
+                sides
+            }
+        }
+
+        let r = Roller::new("4d6!").unwrap().with_max_explosion_percent(200);
+        let res = r.roll_with_source(&mut MaxSource).unwrap();
+        let single = res.as_single().unwrap();
+        assert_eq!(8, single.get_explosion_count());
+        assert_eq!(72, single.get_total());
+        assert_eq!(&[Warning::ExplosionCapReached], res.warnings());
+
+        // each group has its own limit, from its own number of dice
+        let r = Roller::new("3d6! + 1d10!")
+            .unwrap()
+            .with_max_explosion_percent(150);
+        let res = r.roll_with_source(&mut MaxSource).unwrap();
+        assert_eq!(5, res.as_single().unwrap().get_explosion_count());
+
+        // exploding once is limited too
+        let r = Roller::new("4d6e6").unwrap().with_max_explosion_percent(50);
+        let res = r.roll_with_values(&[6, 6, 6, 2, 3, 4]).unwrap();
+        assert_eq!(2, res.as_single().unwrap().get_explosion_count());
+        assert_eq!(&[Warning::ExplosionCapReached], res.warnings());
+
+        // under the limit, nothing changes
+        let res = r.roll_with_values(&[6, 1, 2, 2, 3]).unwrap();
+        assert_eq!(14, res.as_single().unwrap().get_total());
+        assert!(res.warnings().is_empty());
+    }
+
+    #[test]
+    fn warnings_test() {
+        // gives the same value until exhausted
+        struct LimitedSource {
+            value: u64,
+            left: u32,
+        }
+        impl DiceRollSource for LimitedSource {
+            fn roll_single_die(&mut self, _sides: u64) -> u64 {
+                self.left = self.left.saturating_sub(1);
+                self.value
+            }
+            fn is_exhausted(&self) -> bool {
+                self.left == 0
+            }
+        }
+
+        let res = Roller::new("2d6 ir1")
+            .unwrap()
+            .roll_with_source(&mut LimitedSource { value: 1, left: 5 })
+            .unwrap();
+        assert_eq!(&[Warning::RerollCapReached], res.warnings());
+        let res = Roller::new("1d6!")
+            .unwrap()
+            .roll_with_source(&mut LimitedSource { value: 6, left: 5 })
+            .unwrap();
+        assert_eq!(30, res.as_single().unwrap().get_total());
+        assert_eq!(&[Warning::ExplosionCapReached], res.warnings());
+        let res = Roller::new("2d6 ir1 + 1d6!")
+            .unwrap()
+            .roll_with_source(&mut LimitedSource { value: 5, left: 5 })
+            .unwrap();
+        assert!(res.warnings().is_empty());
+
+        let r = Roller::new("(2d6) ^+ 2").unwrap().with_total_cap(10);
+        let res = r.roll_with_values(&[6, 5, 1, 2]).unwrap();
+        assert_eq!(&[Warning::TotalCapped], res.warnings());
+        let res = r.roll_with_values(&[3, 5, 1, 2]).unwrap();
+        assert!(res.warnings().is_empty());
+
+        let big = Roller::new("9223372036854775807").unwrap().roll().unwrap();
+        let res = big.clone().merge(big);
+        assert_eq!(i64::MAX, res.as_single().unwrap().get_total());
+        assert_eq!(&[Warning::OverflowSaturated], res.warnings());
+    }
+
+    #[test]
+    fn typed_modifier_test() {
+        let r = Roller::new("4d6 : strength").unwrap();
+        let values = || vec![2, 5, 4, 1].into_iter();
+        let total = |res: Result<RollResult>| res.unwrap().as_single().unwrap().get_total();
+
+        let res = r
+            .roll_keep_highest(3, &mut IteratorDiceRollSource::new(&mut values()))
+            .unwrap();
+        assert_eq!(Some(&"strength".to_owned()), res.get_reason());
+        assert_eq!(11, res.as_single().unwrap().get_total());
+        assert_eq!(
+            vec![5, 4, 2],
+            res.as_single()
+                .unwrap()
+                .kept_dice()
+                .iter()
+                .map(|d| d.res)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            3,
+            total(r.roll_keep_lowest(2, &mut IteratorDiceRollSource::new(&mut values())))
+        );
+        assert_eq!(
+            3,
+            total(r.roll_drop_highest(2, &mut IteratorDiceRollSource::new(&mut values())))
+        );
+        assert_eq!(
+            9,
+            total(r.roll_drop_lowest(2, &mut IteratorDiceRollSource::new(&mut values())))
+        );
+        assert_eq!(
+            2,
+            total(r.roll_target(4, &mut IteratorDiceRollSource::new(&mut values())))
+        );
+
+        // same as the options in the expression
+        let expected = Roller::new("4d6 K3 : strength")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource::new(&mut values()))
+            .unwrap();
+        assert_eq!(
+            expected.to_string(),
+            r.roll_keep_highest(3, &mut IteratorDiceRollSource::new(&mut values()))
+                .unwrap()
+                .to_string()
+        );
+
+        assert!(r
+            .roll_keep_highest(5, &mut IteratorDiceRollSource::new(&mut values()))
+            .is_err());
+        assert!(r
+            .roll_drop_lowest(5, &mut IteratorDiceRollSource::new(&mut values()))
+            .is_err());
+        assert!(r
+            .roll_keep_lowest(0, &mut IteratorDiceRollSource::new(&mut values()))
+            .is_err());
+        assert_eq!(
+            12,
+            total(r.roll_drop_lowest(0, &mut IteratorDiceRollSource::new(&mut values())))
+        );
+        assert!(r
+            .roll_target(0, &mut IteratorDiceRollSource::new(&mut values()))
+            .is_err());
+        for input in ["4d6 K3", "4d6 + 1", "4dF", "(4d6) ^ 2"].iter() {
+            assert!(
+                Roller::new(input)
+                    .unwrap()
+                    .roll_keep_highest(3, &mut IteratorDiceRollSource::new(&mut values()))
+                    .is_err(),
+                "{}",
+                input
+            );
+        }
+
+        // the cap applies to the total computed with the modifier
+        let res = Roller::new("4d6")
+            .unwrap()
+            .with_total_cap(10)
+            .roll_keep_highest(3, &mut IteratorDiceRollSource::new(&mut values()))
+            .unwrap();
+        assert_eq!(10, res.as_single().unwrap().get_total());
+        assert_eq!(&[Warning::TotalCapped], res.warnings());
+    }
+
+    #[test]
+    fn observer_test() {
+        let mut events = Vec::new();
+        let res = Roller::new("2d6! + 1d4 r1")
+            .unwrap()
+            .roll_with_observer(
+                &mut IteratorDiceRollSource::new(&mut vec![6, 3, 6, 2, 1, 4].into_iter()),
+                |event| events.push(*event),
+            )
+            .unwrap();
+        assert_eq!(21, res.as_single().unwrap().get_total());
+        assert_eq!(
+            vec![
+                RollEvent::DieRolled {
+                    group: 0,
+                    sides: 6,
+                    value: 6
+                },
+                RollEvent::DieRolled {
+                    group: 0,
+                    sides: 6,
+                    value: 3
+                },
+                RollEvent::Exploded {
+                    group: 0,
+                    sides: 6,
+                    value: 6
+                },
+                RollEvent::Exploded {
+                    group: 0,
+                    sides: 6,
+                    value: 2
+                },
+                RollEvent::DieRolled {
+                    group: 1,
+                    sides: 4,
+                    value: 1
+                },
+                RollEvent::Rerolled {
+                    group: 1,
+                    sides: 4,
+                    previous: 1,
+                    value: 4
+                },
+            ],
+            events
+        );
+
+        // the groups follow the roll groups of the result
+        let mut groups = Vec::new();
+        let res = Roller::new("(1d6 + 2) * 1d8 ro2h")
+            .unwrap()
+            .roll_with_observer(
+                &mut IteratorDiceRollSource::new(&mut vec![3, 2, 5].into_iter()),
+                |event| match event {
+                    RollEvent::DieRolled { group, .. } | RollEvent::Rerolled { group, .. } => {
+                        groups.push(*group)
+                    }
+                    _ => (),
+                },
+            )
+            .unwrap();
+        assert_eq!(vec![0, 2, 2], groups);
+        assert_eq!(3, res.as_single().unwrap().get_group_ranges().len());
+    }
+
+    #[test]
+    fn per_die_modifier_test() {
+        let values = [5, 3, 2, 1];
+        let res = Roller::new("4d6(-1 each)")
+            .unwrap()
+            .roll_with_values(&values)
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(8, res.get_total());
+        assert_eq!("[4, 2, 1, 1]", res.to_string_history());
+        // a flat modifier applies once
+        let res = Roller::new("4d6 - 1")
+            .unwrap()
+            .roll_with_values(&values)
+            .unwrap();
+        assert_eq!(10, res.as_single().unwrap().get_total());
+
+        let res = Roller::new("2d6 (+2 each) K1")
+            .unwrap()
+            .roll_with_values(&[6, 1])
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(8, res.get_total());
+        match &res.get_history()[0] {
+            RollHistory::Roll(dice) => {
+                assert_eq!(
+                    vec![Critic::Max, Critic::Min],
+                    dice.iter().map(|d| d.crit).collect::<Vec<_>>()
+                )
+            }
+            _ => unreachable!(),
+        }
+
+        assert_eq!(
+            ast::parse("4d6(-1 each)").unwrap(),
+            ast::parse("4d6 ( - 1 EACH )").unwrap()
+        );
+        assert!(Roller::new("4d6 K3 (-1 each)").unwrap().roll().is_err());
+        assert!(Roller::new("4dF(+1 each)").unwrap().roll().is_err());
+
+        // explosions read the value rolled, the exploded dice get the modifier too
+        let res = Roller::new("4d6(+1 each)!")
+            .unwrap()
+            .roll_with_values(&[5, 4, 3, 2])
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(18, res.get_total());
+        assert_eq!(0, res.get_explosion_count());
+        let res = Roller::new("4d6(+1 each)!")
+            .unwrap()
+            .roll_with_values(&[6, 1, 1, 1, 2])
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(16, res.get_total());
+        assert_eq!(1, res.get_explosion_count());
+    }
+
+    #[test]
+    fn keep_compare_test() {
+        let r = Roller::new("4d6k>=4").unwrap();
+        let res = r.roll_with_values(&[2, 5, 4, 1]).unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(9, res.get_total());
+        assert_eq!(
+            vec![5, 4],
+            res.kept_dice().iter().map(|d| d.res).collect::<Vec<_>>()
+        );
+        assert_eq!("[5, 4, 2, 1]", res.to_string_history());
+
+        // no die satisfying the comparison
+        let res = r.roll_with_values(&[2, 3, 1, 1]).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
+        assert!(res.as_single().unwrap().kept_dice().is_empty());
+
+        assert!(Roller::validate("4d6 k>=4 K2").is_err());
+    }
+
+    #[test]
+    fn drop_compare_test() {
+        let r = Roller::new("4d6d<=2").unwrap();
+        let res = r.roll_with_values(&[2, 5, 1, 6]).unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(11, res.get_total());
+        assert_eq!(
+            vec![6, 5],
+            res.kept_dice().iter().map(|d| d.res).collect::<Vec<_>>()
+        );
+        let marked = DisplayConfig {
+            show_dropped: Some(true),
+            ..Default::default()
+        };
+        assert_eq!("[6, 5, ~~2~~, ~~1~~]", res.to_string_history_with(&marked));
+
+        // all the dice can be dropped, and none
+        let res = r.roll_with_values(&[2, 1, 1, 2]).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
+        let res = r.roll_with_values(&[3, 4, 5, 6]).unwrap();
+        assert_eq!(18, res.as_single().unwrap().get_total());
+
+        // with exploded dice, and another comparator
+        let res = Roller::new("3d6! D>5")
+            .unwrap()
+            .roll_with_values(&[6, 3, 2, 4])
+            .unwrap();
+        assert_eq!(9, res.as_single().unwrap().get_total());
+
+        assert!(matches!(
+            ast::parse("4d6 d<=2").unwrap().command,
+            ast::Command::Expr(ast::Expr::Dice(ast::Dice { ref options, .. }))
+                if options == &[ast::DiceOption::DropCompare(Comparator::Le, 2)]
+        ));
+        assert!(Roller::validate("4d6 d<=2 K2").is_err());
+    }
+
+    #[test]
+    fn median_test() {
+        let r = Roller::new("3d20med").unwrap();
+        let res = r.roll_with_values(&[17, 4, 12]).unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(12, res.get_total());
+        assert_eq!(
+            vec![12],
+            res.kept_dice().iter().map(|d| d.res).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "[~~17~~, 12, ~~4~~]",
+            res.to_string_history_with(&DisplayConfig {
+                show_dropped: Some(true),
+                ..Default::default()
             })
+        );
+
+        // lower middle die for an even pool
+        let res = Roller::new("4d6 MED")
+            .unwrap()
+            .roll_with_values(&[6, 2, 5, 3])
             .unwrap();
-        match roll_res.get_result() {
-            rollresult::RollResultType::Single(res) => assert_eq!(expected, res.get_total()),
-            rollresult::RollResultType::Repeated(_) => unreachable!(),
+        assert_eq!(3, res.as_single().unwrap().get_total());
+        let res = Roller::new("1d6 med")
+            .unwrap()
+            .roll_with_values(&[4])
+            .unwrap();
+        assert_eq!(4, res.as_single().unwrap().get_total());
+
+        assert!(Roller::validate("3d20 med K1").is_err());
+    }
+
+    #[test]
+    fn reroll_pool_test() {
+        let r = Roller::new("1d20rp<5 + 2").unwrap();
+        let res = r.roll_with_values(&[3, 12]).unwrap();
+        let single = res.as_single().unwrap();
+        assert_eq!(14, single.get_total());
+        assert_eq!("([3] | [12]) + 2", single.to_string_history());
+        let marked = DisplayConfig {
+            show_dropped: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            "([~~3~~] | [12]) + 2",
+            single.to_string_history_with(&marked)
+        );
+        assert_eq!(14, res.total_with(TotalModifier::None).unwrap());
+
+        // the second roll stands, even if it's as low
+        let res = r.roll_with_values(&[3, 1]).unwrap();
+        assert_eq!(3, res.as_single().unwrap().get_total());
+
+        // no reroll when the total doesn't satisfy the comparison
+        let res = r.roll_with_values(&[5]).unwrap();
+        assert_eq!("[5] + 2", res.as_single().unwrap().to_string_history());
+
+        // the other options apply to each roll
+        let res = Roller::new("4d6 K3 RP<=6")
+            .unwrap()
+            .roll_with_values(&[1, 2, 1, 3, 6, 4, 1, 5])
+            .unwrap();
+        assert_eq!(15, res.as_single().unwrap().get_total());
+        assert_eq!(
+            "([~~3~~, ~~2~~, ~~1~~, ~~1~~] | [6, 5, 4, ~~1~~])",
+            res.as_single().unwrap().to_string_history_with(&marked)
+        );
+    }
+
+    #[test]
+    fn named_dice_test() {
+        let r = Roller::new("2d{story} : scene")
+            .unwrap()
+            .with_die("story", &["Hope", "Fear", "Neutral"])
+            .unwrap();
+        let res = r.roll_with_values(&[1, 3]).unwrap();
+        let single = res.as_single().unwrap();
+        assert_eq!(
+            vec![&RollHistory::Faces(vec![
+                "Hope".to_owned(),
+                "Neutral".to_owned()
+            ])],
+            single.get_history()
+        );
+        assert_eq!(0, single.get_total());
+        assert_eq!(
+            "`[Hope, Neutral]` = **0**, Reason: `scene`",
+            res.to_string()
+        );
+        // the faces are rolled like a die with as many sides
+        assert!(r.roll_with_values(&[4, 1]).is_err());
+
+        // a single die, next to other dice
+        let r = Roller::new("1d20 + d{story}")
+            .unwrap()
+            .with_die("story", &["Hope", "Fear"])
+            .unwrap();
+        let res = r.roll_with_values(&[12, 2]).unwrap();
+        assert_eq!("`[12] + [Fear]` = **12**", res.to_string());
+
+        match Roller::new("2d{story}").unwrap().roll() {
+            Err(RollError::ParamError(e)) => {
+                assert_eq!("Unknown die `story`, it must be registered first", e)
+            }
+            _ => unreachable!(),
         }
-        eprintln!();
-        eprintln!("{}", roll_res.as_single().unwrap());
+        let r = Roller::new("2D{dragon}").unwrap();
+        assert!(r.clone().with_die("my die", &["a"]).is_err());
+        assert!(r.clone().with_die("dragon", &[]).is_err());
+        let r = r.with_die("dragon", &["Fire", "Ice"]).unwrap();
+        let res = r.roll_with_values(&[2, 2]).unwrap();
+        assert_eq!("[Ice, Ice]", res.history_string());
+        assert!(matches!(
+            ast::parse("2d{dragon}").unwrap().command,
+            ast::Command::Expr(ast::Expr::NamedDice { count: 2, .. })
+        ));
+
+        // a bare word isn't a die
+        assert!(Roller::validate("1d6 + typo").is_err());
+        assert!(Roller::validate("2story").is_err());
+        assert!(Roller::validate("2d {story}").is_err());
+    }
+
+    #[test]
+    fn sides_of_each_group_test() {
+        use ast::Sides;
+
+        let r = Roller::new("2d6 + 1d8 - 3 : damage").unwrap();
+        assert_eq!(
+            vec![(Some(2), Sides::Number(6)), (Some(1), Sides::Number(8))],
+            r.sides_of_each_group().unwrap()
+        );
+
+        let r = Roller::new("(1d4)d6 + (2 + 1)d10 * best(2, 4dF) + 2d{story}")
+            .unwrap()
+            .with_die("story", &["Hope", "Fear", "Neutral"])
+            .unwrap();
+        assert_eq!(
+            vec![
+                (Some(1), Sides::Number(4)),
+                (None, Sides::Number(6)),
+                (Some(3), Sides::Number(10)),
+                (Some(4), Sides::Fudge),
+                (Some(2), Sides::Number(3)),
+            ],
+            r.sides_of_each_group().unwrap()
+        );
+
+        assert!(Roller::new("1 + 2")
+            .unwrap()
+            .sides_of_each_group()
+            .unwrap()
+            .is_empty());
+        assert!(Roller::new("2d{story}")
+            .unwrap()
+            .sides_of_each_group()
+            .is_err());
+    }
+
+    #[test]
+    fn add_reroll_test() {
+        let r = Roller::new("4d6ar1").unwrap();
+        let res = r.roll_with_values(&[1, 5, 1, 6, 3, 4]).unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(20, res.get_total());
+        assert_eq!("[6, 5, 1, 1][4, 3]", res.to_string_history());
+
+        // a reroll of 1 is not rerolled again
+        let res = r.roll_with_values(&[1, 5, 2, 6, 1]).unwrap();
+        assert_eq!(15, res.as_single().unwrap().get_total());
+        let res = r.roll_with_values(&[2, 5, 2, 6]).unwrap();
+        assert_eq!("[6, 5, 2, 2]", res.as_single().unwrap().to_string_history());
+
+        // the added dice are part of the pool
+        let res = Roller::new("3d6 AR2 K3")
+            .unwrap()
+            .roll_with_values(&[2, 3, 4, 6])
+            .unwrap();
+        assert_eq!(13, res.as_single().unwrap().get_total());
+        assert!(Roller::validate("2d6 ar1 K3").is_ok());
+    }
+
+    #[test]
+    fn reroll_keep_test() {
+        let r = Roller::new("1d20 ro1h").unwrap();
+        let res = r.roll_with_values(&[1, 15]).unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(15, res.get_total());
+        assert_eq!("[15] (discarded [1])", res.to_string_history());
+        let res = r.roll_with_values(&[1, 20]).unwrap();
+        match &res.as_single().unwrap().get_history()[0] {
+            RollHistory::Roll(dice) => assert_eq!(Critic::Max, dice[0].crit),
+            _ => unreachable!(),
+        }
+        let res = r.roll_with_values(&[12]).unwrap();
+        assert_eq!("[12]", res.as_single().unwrap().to_string_history());
+
+        let values = [2, 5, 1, 1, 4];
+        let res = Roller::new("3d6 ro2h")
+            .unwrap()
+            .roll_with_values(&values)
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(11, res.get_total());
+        assert_eq!("[5, 4, 2] (discarded [1, 1])", res.to_string_history());
+
+        let res = Roller::new("3d6 ro2l")
+            .unwrap()
+            .roll_with_values(&values)
+            .unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(7, res.get_total());
+        assert_eq!("[5, 1, 1] (discarded [4, 2])", res.to_string_history());
+    }
+
+    #[test]
+    fn total_cap_test() {
+        let r = Roller::new("2d6 + 3").unwrap().with_total_cap(10);
+        let res = r.roll_with_values(&[6, 5]).unwrap();
+        assert_eq!(10, res.as_single().unwrap().get_total());
+        assert!(res.is_capped());
+        assert_eq!("[6, 5] + 3", res.as_single().unwrap().to_string_history());
+
+        // reaching the cap doesn't hit it
+        let res = r.roll_with_values(&[4, 3]).unwrap();
+        assert_eq!(10, res.as_single().unwrap().get_total());
+        assert!(!res.is_capped());
+        let res = r.roll_with_values(&[1, 2]).unwrap();
+        assert_eq!(6, res.as_single().unwrap().get_total());
+        assert!(!res.is_capped());
+
+        let res = Roller::new("(1d6)^+3")
+            .unwrap()
+            .with_total_cap(4)
+            .roll_with_values(&[6, 2, 5])
+            .unwrap();
+        let repeated = res.as_repeated().unwrap();
+        assert_eq!(Some(10), repeated.get_total());
+        assert!(res.is_capped());
+
+        // the cap applies after the keep option given with the roller
+        let mut values = vec![6, 6, 6, 1].into_iter();
+        let res = Roller::new("4d6")
+            .unwrap()
+            .with_total_cap(15)
+            .roll_keep_highest(3, &mut IteratorDiceRollSource::new(&mut values))
+            .unwrap();
+        assert_eq!(15, res.as_single().unwrap().get_total());
+        assert!(res.is_capped());
+    }
+
+    #[test]
+    fn total_floor_test() {
+        let r = Roller::new("2d6 - 10").unwrap().with_total_floor(0);
+        let res = r.roll_with_values(&[3, 4]).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
+        assert_eq!(&[Warning::TotalFloored], res.warnings());
+        assert_eq!("[4, 3] - 10", res.as_single().unwrap().to_string_history());
+
+        // the floor doesn't bind
+        let res = r.roll_with_values(&[6, 5]).unwrap();
+        assert_eq!(1, res.as_single().unwrap().get_total());
+        assert!(res.warnings().is_empty());
+
+        // any floor can be given, and each repeated roll is floored
+        let res = Roller::new("(1d6 - 3)^+3")
+            .unwrap()
+            .with_total_floor(1)
+            .roll_with_values(&[1, 6, 2])
+            .unwrap();
+        assert_eq!(Some(5), res.as_repeated().unwrap().get_total());
+        assert_eq!(&[Warning::TotalFloored], res.warnings());
+
+        // the cap is applied last
+        let res = Roller::new("1d6")
+            .unwrap()
+            .with_total_floor(5)
+            .with_total_cap(3)
+            .roll_with_values(&[1])
+            .unwrap();
+        assert_eq!(3, res.as_single().unwrap().get_total());
+    }
+
+    #[test]
+    fn fractional_dice_test() {
+        let err = |input: &str| Roller::new(input).unwrap().roll().unwrap_err().to_string();
+        assert_eq!(
+            "Invalid dice `1.5d6`: dice counts must be whole numbers, did you mean 3d3?",
+            err("1.5d6")
+        );
+        assert_eq!(
+            "Invalid dice `2d6.5`: dice sides must be whole numbers, did you mean 2d6 or 2d7?",
+            err("2d6.5")
+        );
+        assert_eq!(
+            "Invalid dice `d6.5`: dice sides must be whole numbers, did you mean 1d6 or 1d7?",
+            err("1 + d6.5")
+        );
+        assert_eq!(
+            "Invalid dice `1.5d7`: dice counts must be whole numbers",
+            err("1.5d7 + 2")
+        );
+        assert_eq!(
+            "Invalid dice `2.5dF`: dice counts must be whole numbers",
+            err("2.5dF")
+        );
+        assert_eq!(
+            err("1.5d6"),
+            Roller::validate("1.5d6").unwrap_err().to_string()
+        );
+        // a float constant is still allowed
+        assert!(Roller::validate("1d6 * 1.5").is_ok());
     }
 
     #[test]
-    fn one_value_test() {
-        let r = Roller::new("20").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(20, res.get_total());
-        } else {
-            assert!(false);
+    fn crit_markers_test() {
+        let crits = |input: &str, values: &[u64]| {
+            let res = Roller::new(input)
+                .unwrap()
+                .roll_with_values(values)
+                .unwrap();
+            let res = res.as_single().unwrap();
+            match &res.get_history()[0] {
+                RollHistory::Roll(dice) => (
+                    res.get_total(),
+                    dice.iter().map(|d| d.crit).collect::<Vec<_>>(),
+                ),
+                _ => unreachable!(),
+            }
+        };
+        let r = "1d20cs>18cf<3";
+        assert_eq!((18, vec![Critic::Max]), crits(r, &[18]));
+        assert_eq!((20, vec![Critic::Max]), crits(r, &[20]));
+        assert_eq!((17, vec![Critic::No]), crits(r, &[17]));
+        assert_eq!((3, vec![Critic::Min]), crits(r, &[3]));
+        assert_eq!((1, vec![Critic::Min]), crits(r, &[1]));
+        assert_eq!((4, vec![Critic::No]), crits(r, &[4]));
+
+        // the other marker keeps its default, a value alone matches only itself
+        assert_eq!(
+            (40, vec![Critic::No, Critic::Max, Critic::Min]),
+            crits("3d20 cs19", &[19, 1, 20])
+        );
+        assert_eq!(
+            (9, vec![Critic::Max, Critic::Min, Critic::Min]),
+            crits("3d6 K3 CF<2", &[6, 2, 1])
+        );
+
+        match ast::parse(r).unwrap().command {
+            ast::Command::Expr(ast::Expr::Dice(dice)) => assert_eq!(
+                vec![
+                    ast::DiceOption::CritSuccess(Comparator::Ge, 18),
+                    ast::DiceOption::CritFailure(Comparator::Le, 3)
+                ],
+                dice.options
+            ),
+            _ => unreachable!(),
         }
+        assert!(Roller::validate("1d20 cs>18 cs20").is_err());
+        assert_eq!(20, Roller::new(r).unwrap().distribution().unwrap().len());
     }
 
     #[test]
-    fn one_dice_test() {
-        let r = Roller::new("d20").unwrap();
-        let roll_mock = vec![8];
-        let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut roll_mock.into_iter(),
-            })
+    fn reroll_without_rerolled_dice_test() {
+        let res = Roller::new("3d6 r1")
+            .unwrap()
+            .roll_with_values(&[2, 4, 3])
             .unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(8, res.get_total());
-        } else {
-            unreachable!();
-        }
+        assert_eq!(9, res.as_single().unwrap().get_total());
     }
 
     #[test]
-    fn float_mul_test() {
-        let r = Roller::new("20 * 1.5").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(30, res.get_total());
-        } else {
-            unreachable!()
+    fn contradictory_options_test() {
+        let cases = [
+            (
+                "4d6K2k2",
+                "Can't use `K2` and `k2` together, only one keep or drop option is allowed",
+            ),
+            (
+                "4d6 K3 d1",
+                "Can't use `K3` and `d1` together, only one keep or drop option is allowed",
+            ),
+            ("4d6 K5", "Can't keep 5 dice out of 4"),
+            ("4d6 d5", "Can't drop 5 dice out of 4"),
+            ("4d6kh0", "`kh0` would keep no dice"),
+            ("4d6 k0", "`k0` would keep no dice"),
+            ("2d6 e6 K0", "`K0` would keep no dice"),
+            ("6d10 t7 t8", "Can't use `t7` and `t8` together"),
+            ("6d10 t7 f1 f2", "Can't use `f1` and `f2` together"),
+            ("3d6 !1", "`!1` would explode indefinitely on every value"),
+            ("3d1 !", "`!` would explode indefinitely on every value"),
+            (
+                "3d6 ir6",
+                "`ir6` would reroll indefinitely every value of a d6",
+            ),
+        ];
+        for (input, expected) in cases.iter() {
+            match Roller::new(input).unwrap().roll() {
+                Err(RollError::ParamError(e)) => assert_eq!(expected, &e, "{}", input),
+                _ => unreachable!("{}", input),
+            }
+            match ast::parse(input) {
+                Err(RollError::ParamError(e)) => assert_eq!(expected, &e, "{}", input),
+                _ => unreachable!("{}", input),
+            }
         }
-    }
 
-    #[test]
-    fn float_signed_mul_test() {
-        let r = Roller::new("20 * +1.5").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(30, res.get_total());
-        } else {
-            unreachable!()
+        // dropping no dice keeps them all
+        for input in ["4d6dl0", "4d6 D0"].iter() {
+            let res = Roller::new(input)
+                .unwrap()
+                .roll_with_values(&[2, 6, 3, 4])
+                .unwrap();
+            assert_eq!(15, res.as_single().unwrap().get_total(), "{}", input);
+            assert_eq!(4, res.as_single().unwrap().kept_dice().len(), "{}", input);
         }
+
+        // exploded dice can make the pool bigger than the keep count
+        let res = Roller::new("2d6 e6 K3")
+            .unwrap()
+            .roll_with_values(&[6, 2, 3])
+            .unwrap();
+        assert_eq!(11, res.as_single().unwrap().get_total());
     }
 
     #[test]
-    fn float_neg_signed_mul_test() {
-        let r = Roller::new("20 * -1.5").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(-30, res.get_total());
-        } else {
-            unreachable!()
+    fn max_depth_test() {
+        let nested = |n| format!("{}1d6{}", "(".repeat(n), ")".repeat(n));
+        assert!(Roller::new(&nested(64)).unwrap().roll().is_ok());
+        match Roller::new(&nested(65)).unwrap().roll() {
+            Err(RollError::TooDeep(64)) => (),
+            _ => unreachable!(),
         }
-    }
+        match ast::parse(&nested(65)) {
+            Err(RollError::TooDeep(64)) => (),
+            _ => unreachable!(),
+        }
+        // far too deep to be evaluated, must fail without blowing the stack
+        assert!(Roller::new(&nested(100_000)).unwrap().roll().is_err());
 
-    #[test]
-    fn float_add_test() {
-        let r = Roller::new("20 + 1.5").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(21, res.get_total());
-        } else {
-            unreachable!()
+        let r = Roller::new("((1d6)) + (1d4)").unwrap().with_max_depth(2);
+        assert!(r.roll().is_ok());
+        let r = Roller::new("(((1d6)))").unwrap().with_max_depth(2);
+        match r.roll() {
+            Err(e @ RollError::TooDeep(2)) => assert_eq!(
+                "Expression is nested too deeply, the maximum depth is 2",
+                e.to_string()
+            ),
+            _ => unreachable!(),
         }
     }
 
     #[test]
-    fn float_signed_add_test() {
-        let r = Roller::new("20 + +1.5").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(21, res.get_total());
-        } else {
-            unreachable!()
+    fn probability_test() {
+        let close = |expected: f64, p: Result<f64>| {
+            let p = p.unwrap();
+            assert!((expected - p).abs() < 1e-9, "{} != {}", expected, p)
+        };
+        let r = Roller::new("1d20+5").unwrap();
+        close(0.55, r.probability_at_least(15));
+        close(0.5, r.probability_at_most(15));
+        close(1.0, r.probability_at_least(6));
+        close(0.0, r.probability_at_least(26));
+
+        let r = Roller::new("2d6 : damage").unwrap();
+        close(21.0 / 36.0, r.probability_at_least(7));
+        let distribution = r.distribution().unwrap();
+        assert_eq!(11, distribution.len());
+        close(6.0 / 36.0, Ok(distribution[&7]));
+
+        close(
+            15.0 / 36.0,
+            Roller::new("(1d6 - 1d6) * 2")
+                .unwrap()
+                .probability_at_least(2),
+        );
+        close(
+            1.0 / 3.0,
+            Roller::new("1dF").unwrap().probability_at_most(-1),
+        );
+        close(
+            0.5,
+            Roller::new("1dF")
+                .unwrap()
+                .with_fudge_config(FudgeConfig {
+                    minus_max: 3,
+                    blank_max: 4,
+                })
+                .probability_at_most(-1),
+        );
+
+        for input in &[
+            "1d6!",
+            "3d6 K2",
+            "(1d6) ^ 2",
+            "1d6 * 1.5",
+            "1d6 / (1d2 - 1)",
+            "200d100",
+        ] {
+            assert!(
+                Roller::new(input).unwrap().probability_at_least(1).is_err(),
+                "{}",
+                input
+            );
         }
     }
 
     #[test]
-    fn float_neg_signed_add_test() {
-        let r = Roller::new("20 + -1.5").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(18, res.get_total());
-        } else {
-            unreachable!()
+    fn die_transform_test() {
+        let r = Roller::new("4d6 + 1d8")
+            .unwrap()
+            .with_die_transform(|_, _| 1);
+        let res = r.roll_with_values(&[3, 6, 2, 5, 8]).unwrap();
+        let res = res.as_single().unwrap();
+        assert_eq!(5, res.get_total());
+        assert_eq!("[1, 1, 1, 1] + [1]", res.to_string_history());
+        match &res.get_history()[0] {
+            RollHistory::Roll(dice) => assert!(dice.iter().all(|d| d.crit == Critic::Min)),
+            _ => unreachable!(),
         }
-    }
 
-    #[test]
-    fn signed_add_test() {
-        let r = Roller::new("20 + +5").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(25, res.get_total());
-        } else {
-            unreachable!()
+        let r = Roller::new("2d6")
+            .unwrap()
+            .with_die_transform(|sides, raw| sides + 1 - raw);
+        let res = r.roll_with_values(&[2, 6]).unwrap();
+        assert_eq!("[5, 1]", res.as_single().unwrap().to_string_history());
+
+        let r = Roller::new("2d6")
+            .unwrap()
+            .with_die_transform(|_, raw| raw + 6);
+        match r.roll() {
+            Err(RollError::ParamError(e)) => assert!(e.starts_with("Transformed value")),
+            _ => unreachable!(),
         }
     }
 
     #[test]
-    fn signed_neg_add_test() {
-        let r = Roller::new("20 + -5").unwrap();
-        let res = r.roll().unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(15, res.get_total());
-        } else {
-            unreachable!()
+    fn validate_test() {
+        for input in &[
+            "4d6 K3",
+            "(2d6 + 6) ^# 8 : test",
+            "1d100<=65",
+            "3d6 ro1h + 1dF",
+        ] {
+            assert!(Roller::validate(input).is_ok(), "{}", input);
         }
+        assert!(matches!(
+            Roller::validate("2d6 +"),
+            Err(RollError::ParseError(_))
+        ));
+        let cases = [
+            ("1d0", "Dice can't have 0 sides"),
+            ("(1d6) ^ 0", "Can't repeat 0 times or negatively"),
+            (
+                "4d6 k[1,5]",
+                "Can't keep die at position 5, positions go from 1 to 4",
+            ),
+            ("4d6 K5", "Can't keep 5 dice out of 4"),
+            (
+                "4d6 K3 d1",
+                "Can't use `K3` and `d1` together, only one keep or drop option is allowed",
+            ),
+            (
+                "1d18446744073709551616",
+                "Invalid number `18446744073709551616`: number too large to fit in target type",
+            ),
+        ];
+        for (input, expected) in cases.iter() {
+            match Roller::validate(input) {
+                Err(RollError::ParamError(e)) => assert_eq!(expected, &e, "{}", input),
+                _ => unreachable!("{}", input),
+            }
+        }
+        let nested = format!("{}1d6{}", "(".repeat(65), ")".repeat(65));
+        assert!(matches!(
+            Roller::validate(&nested),
+            Err(RollError::TooDeep(64))
+        ));
     }
 
     #[test]
-    fn counting_roller_test() {
-        let r = Roller::new("3d6").unwrap();
-        let rolls = vec![3, 6, 3];
-        let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut rolls.into_iter(),
-            })
-            .unwrap();
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            assert_eq!(res.get_total(), 12);
-        } else {
-            assert!(false);
+    fn percent_test() {
+        let r = Roller::new("%65").unwrap();
+        let res = r.roll_with_values(&[65]).unwrap();
+        assert_eq!(1, res.as_single().unwrap().get_total());
+        assert_eq!(Some(SuccessLevel::Regular), res.success_level());
+        let res = r.roll_with_values(&[66]).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
+        assert_eq!("[66]", res.as_single().unwrap().to_string_history());
+
+        let (mut success, mut failure) = (false, false);
+        for seed in 0..20 {
+            let res = r.roll_seeded(seed).unwrap();
+            let res = res.as_single().unwrap();
+            let die = match &res.get_history()[0] {
+                RollHistory::Roll(dice) => dice[0].res,
+                _ => unreachable!(),
+            };
+            assert_eq!(if die <= 65 { 1 } else { 0 }, res.get_total());
+            success |= die <= 65;
+            failure |= die > 65;
         }
+        assert!(success && failure);
+
+        assert_eq!(ast::parse("1d100<=65").unwrap(), ast::parse("%65").unwrap());
     }
 
     #[test]
-    fn target_number_test() {
-        let r = Roller::new("10d10 t7").unwrap();
+    fn botch_test() {
+        let r = Roller::new("6d10>=7b").unwrap();
+        let res = r.roll_with_values(&[8, 1, 9, 10, 2, 3]).unwrap();
+        assert_eq!(2, res.as_single().unwrap().get_total());
+
+        let values = [7, 1, 1, 1, 9, 3];
+        let res = r.roll_with_values(&values).unwrap();
+        assert_eq!(0, res.as_single().unwrap().get_total());
         let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut (1..11),
-            })
+            .clone()
+            .with_overbotch(true)
+            .roll_with_values(&values)
             .unwrap();
-        println!("{}", res);
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            // We rolled one of every number, with a target number of 7 we should score a success
-            // on the 7, 8, 9, and 10. So four total.
-            assert_eq!(res.get_total(), 4);
-        } else {
-            assert!(false);
-        }
-    }
+        assert_eq!(-1, res.as_single().unwrap().get_total());
+        // the roller's choice is kept when computing the total again
+        let modifier = TotalModifier::CompareBotch(Comparator::Ge, 9);
+        assert_eq!(-2, res.total_with(modifier).unwrap());
 
-    #[test]
-    fn target_number_double_test() {
-        let r = Roller::new("10d10 t7 tt9").unwrap();
-        let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut (1..11),
-            })
+        // a 1 satisfying the comparison is a success and a botch, they cancel out
+        let res = Roller::new("3d10<=3b")
+            .unwrap()
+            .roll_with_values(&[1, 2, 5])
             .unwrap();
-        println!("{}", res);
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            // We rolled one of every number. That's a success each for the 7 and 8, and two
-            // success each for the 9 and 10. So a toal of six.
-            assert_eq!(res.get_total(), 6);
-        } else {
-            assert!(false);
-        }
-    }
+        assert_eq!(1, res.as_single().unwrap().get_total());
 
-    // Where a user has asked for a doubles threashold that is lower than the single threashold,
-    // the single threashold is ignored.
-    #[test]
-    fn target_number_double_lower_than_target_test() {
-        let r = Roller::new("10d10 tt7 t9").unwrap();
-        let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut (1..11),
-            })
+        // without botch, 1s are just failed dice
+        let res = Roller::new("6d10>=7")
+            .unwrap()
+            .roll_with_values(&values)
             .unwrap();
-        println!("{}", res);
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            // We rolled one of every number. That's two successes each for the 7, 8, 9, and 10.
-            // So eight total.
-            assert_eq!(res.get_total(), 8);
-        } else {
-            assert!(false);
-        }
+        assert_eq!(2, res.as_single().unwrap().get_total());
     }
 
-    // Where a user has asked for a doubles without singles.
     #[test]
-    fn target_number_double_only() {
-        let r = Roller::new("10d10 tt8").unwrap();
-        let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut (1..11),
-            })
-            .unwrap();
-        println!("{}", res);
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            // We rolled one of every number. That's two successes each for the 8, 9, and 10.
-            // So six total.
-            assert_eq!(res.get_total(), 6);
-        } else {
-            assert!(false);
+    fn number_error_conversion_test() {
+        fn parse(s: &str) -> Result<u8> {
+            let n: i64 = s.parse()?;
+            Ok(u8::try_from(n)?)
+        }
+        assert_eq!(42, parse("42").unwrap());
+        match parse("4x2") {
+            Err(RollError::ParamError(e)) => {
+                assert_eq!("Invalid number: invalid digit found in string", e)
+            }
+            _ => unreachable!(),
+        }
+        match parse("300") {
+            Err(RollError::ParamError(e)) => assert_eq!(
+                "Number out of range: out of range integral type conversion attempted",
+                e
+            ),
+            _ => unreachable!(),
         }
     }
 
     #[test]
-    fn target_enum() {
-        let r = Roller::new("6d6 t[2,4,6]").unwrap();
-        let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut (1..7),
-            })
-            .unwrap();
-        println!("{}", res);
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            // We rolled one of every number. That's half of them being even
-            assert_eq!(res.get_total(), 3);
-        } else {
-            assert!(false);
+    fn large_numbers_test() {
+        // must not panic whatever the rolled value is
+        let _ = Roller::new("1d18446744073709551615").unwrap().roll();
+
+        let r = Roller::new("1d18446744073709551615").unwrap();
+        let res = r.roll_with_values(&[i64::MAX as u64]).unwrap();
+        assert_eq!(i64::MAX, res.as_single().unwrap().get_total());
+        match r.roll_with_values(&[u64::MAX]) {
+            Err(RollError::ParamError(e)) => {
+                assert_eq!("Die value 18446744073709551615 is too big to be summed", e)
+            }
+            _ => unreachable!(),
         }
 
-        let mock = vec![1, 2, 2, 4, 6, 3];
-        let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut mock.into_iter(),
-            })
-            .unwrap();
-        println!("{}", res);
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            // We rolled one of every number. That's half of them being even
-            assert_eq!(res.get_total(), 4);
-        } else {
-            assert!(false);
+        let r = Roller::new("2d18446744073709551615").unwrap();
+        match r.roll_with_values(&[i64::MAX as u64, 1]) {
+            Err(RollError::ParamError(e)) => assert_eq!("Total is too big to be computed", e),
+            _ => unreachable!(),
         }
 
-        let mock = vec![1, 3, 3, 4, 6, 3];
-        let res = r
-            .roll_with_source(&mut IteratorDiceRollSource {
-                iterator: &mut mock.into_iter(),
-            })
+        match Roller::new("9223372036854775807 + 1").unwrap().roll() {
+            Err(RollError::ParamError(e)) => assert_eq!("Result is too big to be computed", e),
+            _ => unreachable!(),
+        }
+        let res = Roller::new("-9223372036854775807 - 1")
+            .unwrap()
+            .roll()
             .unwrap();
-        println!("{}", res);
-        let res = res.get_result();
-        if let RollResultType::Single(res) = res {
-            // We rolled one of every number. That's half of them being even
-            assert_eq!(res.get_total(), 2);
-        } else {
-            assert!(false);
+        assert_eq!(i64::MIN, res.as_single().unwrap().get_total());
+
+        for input in &[
+            "1d18446744073709551616",
+            "99999999999999999999",
+            "3d6 k99999999999999999999",
+        ] {
+            match Roller::new(input).unwrap().roll() {
+                Err(RollError::ParamError(e)) => assert!(e.starts_with("Invalid number")),
+                _ => unreachable!(),
+            }
         }
     }
 
@@ -1,4 +1,10 @@
-use std::sync::{Arc, Once, RwLock};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, Once, RwLock},
+};
 
 use pest::{
     iterators::{Pair, Pairs},
@@ -6,10 +12,75 @@ use pest::{
 };
 use pest_derive::Parser;
 
-use crate::{error::Result, DiceResult, SingleRollResult};
+use crate::{
+    error::{Result, RollError},
+    Comparator, DiceResult, FudgeConfig, SingleRollResult, Warning,
+};
 
+/// A source of dice results, used by [`crate::Roller::roll_with_source()`].
 pub trait DiceRollSource {
+    /// Roll one die with `sides` sides, the returned value must be in `1..=sides`
     fn roll_single_die(&mut self, sides: u64) -> u64;
+
+    /// Called for each die once rolled and read by the roll, see [`RollEvent`]. Does nothing by
+    /// default.
+    fn on_event(&mut self, _event: &RollEvent) {}
+
+    /// Says if the source can't give meaningful dice anymore, stopping the indefinite explosions
+    /// and rerolls. Always `false` by default.
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+
+    /// Called before rolling a group of `count` dice, an error returned stopping the roll before
+    /// any of them is rolled, like when they exceed a budget. Returns `Ok(())` by default.
+    fn reserve(&mut self, _count: u64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called once a roll is done, an error returned failing the roll, like when the source ran
+    /// out of dice. Returns `None` by default.
+    fn take_error(&mut self) -> Option<RollError> {
+        None
+    }
+}
+
+/// What happened to a die during a roll, see [`crate::Roller::roll_with_observer()`].
+///
+/// `group` is the index of the roll group of the die, in the order of
+/// [`SingleRollResult::get_group_ranges()`]. Fudge dice are rolled as d6.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RollEvent {
+    /// A die of a roll group was rolled
+    DieRolled {
+        /// Roll group of the die
+        group: usize,
+        /// Number of sides of the die
+        sides: u64,
+        /// Value rolled
+        value: u64,
+    },
+    /// A die was rolled by an explosion
+    Exploded {
+        /// Roll group of the die
+        group: usize,
+        /// Number of sides of the die
+        sides: u64,
+        /// Value rolled
+        value: u64,
+    },
+    /// A die was rolled again
+    Rerolled {
+        /// Roll group of the die
+        group: usize,
+        /// Number of sides of the die
+        sides: u64,
+        /// Value of the die before the reroll
+        previous: u64,
+        /// Value rolled
+        value: u64,
+    },
 }
 
 #[derive(Parser)]
@@ -17,20 +88,114 @@ pub trait DiceRollSource {
 pub(crate) struct RollParser;
 
 // arbitrary limit to avoid OOM
+#[allow(dead_code)]
 const MAX_DICE_SIDES: u64 = 5000;
+#[allow(dead_code)]
 const MAX_NB_DICE: u64 = 5000;
 
-// number represent nb dice to keep/drop
-#[derive(Clone, PartialEq)]
-pub(crate) enum TotalModifier {
+/// Modifier applied on the dice of a roll to compute its total.
+///
+/// Numbers in keep and drop variants represent the number of dice to keep or drop.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum TotalModifier {
+    /// Keep the `n` highest dice
     KeepHi(usize),
+    /// Keep the `n` lowest dice
     KeepLo(usize),
+    /// Drop the `n` highest dice
     DropHi(usize),
+    /// Drop the `n` lowest dice
     DropLo(usize),
+    /// Keep the dice at the given positions in roll order, starting at 1
+    KeepIndices(Vec<usize>),
+    /// Keep the dice which value satisfies the comparison with the given value
+    KeepCompare(Comparator, u64),
+    /// Drop the dice which value satisfies the comparison with the given value
+    DropCompare(Comparator, u64),
+    /// Keep only the median die. With an even number of dice, the lower of the two middle dice
+    /// is kept.
+    Median,
+    /// Count successes instead of summing: target, failure and double target values. A value of 0
+    /// means the corresponding threshold is not used.
     TargetFailureDouble(u64, u64, u64),
+    /// Count as success each die which value is in the list
     TargetEnum(Vec<u64>),
-    Fudge,
-    None(Rule),
+    /// Count as success each die which value satisfies the comparison with the target
+    Compare(Comparator, u64),
+    /// Like `Compare`, but each 1 rolled cancels a success. The total can go under 0 only if
+    /// allowed with [`crate::Roller::with_overbotch()`]. A 1 satisfying the comparison, like with
+    /// `<=3b`, is both a success and a botch: they cancel out.
+    CompareBotch(Comparator, u64),
+    /// Interpret the dice as Fudge dice, with the given thresholds
+    Fudge(FudgeConfig),
+    /// Plain sum of the dice
+    None,
+}
+
+// Default maximum number of nested parentheses
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 64;
+
+// Closure applied on each die rolled, see `Roller::with_die_transform()`
+#[derive(Clone)]
+pub(crate) struct DieTransform(pub(crate) Arc<dyn Fn(u64, u64) -> u64 + Send + Sync>);
+
+impl std::fmt::Debug for DieTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DieTransform")
+    }
+}
+
+// Settings of the `Roller` used while computing a roll
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct RollOptions {
+    pub(crate) fudge: FudgeConfig,
+    pub(crate) max_depth: usize,
+    // a closure can't be serialized, serializing fails if there's one
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            skip_deserializing,
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "serialize_die_transform"
+        )
+    )]
+    pub(crate) die_transform: Option<DieTransform>,
+    pub(crate) overbotch: bool,
+    pub(crate) spans: bool,
+    pub(crate) total_cap: Option<i64>,
+    pub(crate) total_floor: Option<i64>,
+    pub(crate) named_dice: BTreeMap<String, Vec<String>>,
+    pub(crate) max_explosion_percent: Option<u64>,
+    pub(crate) fudge_explode: bool,
+}
+
+impl Default for RollOptions {
+    fn default() -> Self {
+        RollOptions {
+            fudge: FudgeConfig::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            die_transform: None,
+            overbotch: false,
+            spans: false,
+            total_cap: None,
+            total_floor: None,
+            named_dice: BTreeMap::new(),
+            max_explosion_percent: None,
+            fudge_explode: false,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_die_transform<S: serde::Serializer>(
+    _: &Option<DieTransform>,
+    _: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    Err(serde::ser::Error::custom(
+        "A `Roller` with a die transform can't be serialized",
+    ))
 }
 
 struct OptionResult {
@@ -40,12 +205,12 @@ struct OptionResult {
 
 // Struct to have a singleton of PrecClimber without using once_cell
 #[derive(Clone)]
-struct Climber {
+pub(crate) struct Climber {
     inner: Arc<RwLock<PrattParser<Rule>>>,
 }
 
 impl Climber {
-    fn climb<'i, P, F, G, T>(&self, pairs: P, primary: F, infix: G) -> T
+    pub(crate) fn climb<'i, P, F, G, T>(&self, pairs: P, primary: F, infix: G) -> T
     where
         P: Iterator<Item = Pair<'i, Rule>>,
         F: FnMut(Pair<'i, Rule>) -> T,
@@ -60,8 +225,8 @@ impl Climber {
     }
 }
 
-fn get_climber() -> Climber {
-    static mut PREC_CLIMBER: *const Climber = 0 as *const Climber;
+pub(crate) fn get_climber() -> Climber {
+    static mut PREC_CLIMBER: *const Climber = std::ptr::null();
     static ONCE: Once = Once::new();
 
     unsafe {
@@ -73,12 +238,13 @@ fn get_climber() -> Climber {
                 inner: Arc::new(RwLock::new(
                     PrattParser::new()
                         .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::sub, Assoc::Left))
-                        .op(Op::infix(Rule::mul, Assoc::Left) | Op::infix(Rule::div, Assoc::Left)),
+                        .op(Op::infix(Rule::mul, Assoc::Left) | Op::infix(Rule::div, Assoc::Left))
+                        .op(Op::infix(Rule::pow, Assoc::Right)),
                 )),
             };
 
             // Put it in the heap so it can outlive this call
-            PREC_CLIMBER = std::mem::transmute(Box::new(singleton));
+            PREC_CLIMBER = Box::into_raw(Box::new(singleton));
         });
 
         // Now we give out a copy of the data that is safe to use concurrently.
@@ -86,53 +252,153 @@ fn get_climber() -> Climber {
     }
 }
 
+// Number of dice the group can still add by explosions, `max_explosions` being the cap of the
+// group if any
+fn explosions_left(rolls: &SingleRollResult, max_explosions: Option<u64>) -> u64 {
+    max_explosions.map_or(u64::MAX, |max| {
+        max.saturating_sub(rolls.get_explosion_count())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
 fn compute_explode<RNG: DiceRollSource>(
     rolls: &mut SingleRollResult,
     sides: u64,
     res: Vec<DiceResult>,
     option: Pair<Rule>,
-    prev_modifier: &TotalModifier,
+    prev_rule: Option<Rule>,
     rng: &mut RNG,
-) -> (TotalModifier, Vec<DiceResult>) {
-    let value = extract_option_value(option).unwrap_or(sides);
-    let nb = res.iter().filter(|x| x.res >= value).count() as u64;
-    if prev_modifier != &TotalModifier::None(Rule::explode)
-        && prev_modifier != &TotalModifier::None(Rule::i_explode)
-    {
-        rolls.add_history(res.clone(), false);
+    group: usize,
+    max_explosions: Option<u64>,
+) -> Result<(TotalModifier, Vec<DiceResult>)> {
+    let is_bonus = has_bonus(&option);
+    let value = extract_option_value(option)?.unwrap_or(sides);
+    let mut nb = res.iter().filter(|x| x.res >= value).count() as u64;
+    if !matches!(prev_rule, Some(Rule::explode) | Some(Rule::i_explode)) {
+        rolls.add_history(res.clone());
+    }
+    rolls.mark_exploded(value);
+    let left = explosions_left(rolls, max_explosions);
+    if nb > left {
+        nb = left;
+        rolls.add_warning(Warning::ExplosionCapReached);
     }
     let res = if nb > 0 {
-        let res = roll_dice(nb, sides, rng);
-        rolls.add_history(res.clone(), false);
+        let res = roll_exploded_dice(nb, sides, rng, group)?;
+        add_explosion_history(rolls, res.clone(), is_bonus);
         res
     } else {
         res
     };
-    (TotalModifier::None(Rule::explode), res)
+    Ok((TotalModifier::None, res))
 }
 
+#[allow(clippy::too_many_arguments)]
 fn compute_i_explode<RNG: DiceRollSource>(
     rolls: &mut SingleRollResult,
     sides: u64,
     res: Vec<DiceResult>,
     option: Pair<Rule>,
-    prev_modifier: &TotalModifier,
+    prev_rule: Option<Rule>,
     rng: &mut RNG,
-) -> (TotalModifier, Vec<DiceResult>) {
-    let value = extract_option_value(option).unwrap_or(sides);
-    if prev_modifier != &TotalModifier::None(Rule::explode)
-        && prev_modifier != &TotalModifier::None(Rule::i_explode)
-    {
-        rolls.add_history(res.clone(), false);
+    group: usize,
+    max_explosions: Option<u64>,
+) -> Result<(TotalModifier, Vec<DiceResult>)> {
+    let is_bonus = has_bonus(&option);
+    let value = extract_option_value(option)?.unwrap_or(sides);
+    if !matches!(prev_rule, Some(Rule::explode) | Some(Rule::i_explode)) {
+        rolls.add_history(res.clone());
     }
+    rolls.mark_exploded(value);
     let mut nb = res.into_iter().filter(|x| x.res >= value).count() as u64;
     let mut res = Vec::new();
-    while nb > 0 {
-        res = roll_dice(nb, sides, rng);
-        nb = res.iter().filter(|x| x.res >= value).count() as u64;
-        rolls.add_history(res.clone(), false);
+    while nb > 0 && !rng.is_exhausted() {
+        let rolled = nb.min(explosions_left(rolls, max_explosions));
+        if rolled == 0 {
+            break;
+        }
+        res = roll_exploded_dice(rolled, sides, rng, group)?;
+        // the explosions over the cap are still pending
+        nb = nb - rolled + res.iter().filter(|x| x.res >= value).count() as u64;
+        add_explosion_history(rolls, res.clone(), is_bonus);
+        rolls.mark_exploded(value);
+    }
+    if nb > 0 {
+        rolls.add_warning(Warning::ExplosionCapReached);
+    }
+    Ok((TotalModifier::None, res))
+}
+
+// roll the dice of a roll group
+fn roll_group_dice<RNG: DiceRollSource>(
+    nb: u64,
+    sides: u64,
+    rng: &mut RNG,
+    group: usize,
+) -> Result<Vec<DiceResult>> {
+    rng.reserve(nb)?;
+    let res = roll_dice(nb, sides, rng);
+    for d in res.iter() {
+        rng.on_event(&RollEvent::DieRolled {
+            group,
+            sides,
+            value: d.res,
+        });
+    }
+    Ok(res)
+}
+
+// roll the dice of an explosion
+fn roll_exploded_dice<RNG: DiceRollSource>(
+    nb: u64,
+    sides: u64,
+    rng: &mut RNG,
+    group: usize,
+) -> Result<Vec<DiceResult>> {
+    rng.reserve(nb)?;
+    let res = roll_dice(nb, sides, rng);
+    for d in res.iter() {
+        rng.on_event(&RollEvent::Exploded {
+            group,
+            sides,
+            value: d.res,
+        });
     }
-    (TotalModifier::None(Rule::i_explode), res)
+    Ok(res)
+}
+
+// roll again a die
+fn reroll_die<RNG: DiceRollSource>(
+    previous: DiceResult,
+    sides: u64,
+    rng: &mut RNG,
+    group: usize,
+) -> DiceResult {
+    let new = roll_dice(1, sides, rng)[0];
+    rng.on_event(&RollEvent::Rerolled {
+        group,
+        sides,
+        previous: previous.res,
+        value: new.res,
+    });
+    new
+}
+
+// exploded dice go to the bonus tally if asked to, with the other dice otherwise
+fn add_explosion_history(rolls: &mut SingleRollResult, res: Vec<DiceResult>, is_bonus: bool) {
+    rolls.add_explosions(res.len() as u64);
+    if is_bonus {
+        rolls.add_bonus_history(res);
+    } else {
+        rolls.add_history(res);
+    }
+}
+
+fn has_bonus(option: &Pair<Rule>) -> bool {
+    option
+        .clone()
+        .into_inner()
+        .any(|p| p.as_rule() == Rule::bonus)
 }
 
 fn compute_reroll<RNG: DiceRollSource>(
@@ -141,25 +407,26 @@ fn compute_reroll<RNG: DiceRollSource>(
     res: Vec<DiceResult>,
     option: Pair<Rule>,
     rng: &mut RNG,
-) -> (TotalModifier, Vec<DiceResult>) {
-    let value = extract_option_value(option).unwrap();
+    group: usize,
+) -> Result<(TotalModifier, Vec<DiceResult>)> {
+    let value = extract_option_value(option)?.unwrap();
     let mut has_rerolled = false;
     let res: Vec<DiceResult> = res
         .into_iter()
         .map(|x| {
             if x.res <= value {
                 has_rerolled = true;
-                roll_dice(1, sides, rng)[0]
+                reroll_die(x, sides, rng, group)
             } else {
                 x
             }
         })
         .collect();
 
-    if has_rerolled {
-        rolls.add_history(res.clone(), false);
+    if has_rerolled || rolls.get_history().is_empty() {
+        rolls.add_history(res.clone());
     }
-    (TotalModifier::None(Rule::reroll), res)
+    Ok((TotalModifier::None, res))
 }
 
 fn compute_i_reroll<RNG: DiceRollSource>(
@@ -168,65 +435,187 @@ fn compute_i_reroll<RNG: DiceRollSource>(
     res: Vec<DiceResult>,
     option: Pair<Rule>,
     rng: &mut RNG,
-) -> (TotalModifier, Vec<DiceResult>) {
-    let value = extract_option_value(option).unwrap();
+    group: usize,
+) -> Result<(TotalModifier, Vec<DiceResult>)> {
+    let value = extract_option_value(option)?.unwrap();
     let mut has_rerolled = false;
     let res: Vec<DiceResult> = res
         .into_iter()
         .map(|x| {
             let mut x = x;
-            while x.res <= value {
+            while x.res <= value && !rng.is_exhausted() {
                 has_rerolled = true;
-                x = roll_dice(1, sides, rng)[0]
+                x = reroll_die(x, sides, rng, group)
             }
             x
         })
         .collect();
 
-    if has_rerolled {
-        rolls.add_history(res.clone(), false);
+    if has_rerolled || rolls.get_history().is_empty() {
+        rolls.add_history(res.clone());
+    }
+    if res.iter().any(|x| x.res <= value) {
+        rolls.add_warning(Warning::RerollCapReached);
     }
-    (TotalModifier::None(Rule::i_reroll), res)
+    Ok((TotalModifier::None, res))
 }
 
+// reroll once the dice lower or equal to the value, adding the new dice to the pool
+fn compute_add_reroll<RNG: DiceRollSource>(
+    rolls: &mut SingleRollResult,
+    sides: u64,
+    mut res: Vec<DiceResult>,
+    option: Pair<Rule>,
+    rng: &mut RNG,
+    group: usize,
+) -> Result<(TotalModifier, Vec<DiceResult>)> {
+    let value = extract_option_value(option)?.unwrap();
+    if rolls.get_history().is_empty() {
+        rolls.add_history(res.clone());
+    }
+    let added: Vec<DiceResult> = res
+        .iter()
+        .filter(|x| x.res <= value)
+        .map(|&x| reroll_die(x, sides, rng, group))
+        .collect();
+    if !added.is_empty() {
+        rolls.add_history(added.clone());
+        res.extend(added);
+    }
+    Ok((TotalModifier::None, res))
+}
+
+// reroll once and keep the higher or the lower of the two dice
+fn compute_reroll_keep<RNG: DiceRollSource>(
+    rolls: &mut SingleRollResult,
+    sides: u64,
+    res: Vec<DiceResult>,
+    option: Pair<Rule>,
+    rng: &mut RNG,
+    group: usize,
+) -> Result<(TotalModifier, Vec<DiceResult>)> {
+    let keep_higher = option.as_rule() == Rule::reroll_hi;
+    let value = extract_option_value(option)?.unwrap();
+    let mut discarded = Vec::new();
+    let res: Vec<DiceResult> = res
+        .into_iter()
+        .map(|x| {
+            if x.res <= value {
+                let new = reroll_die(x, sides, rng, group);
+                let (kept, other) = if (new.res > x.res) == keep_higher {
+                    (new, x)
+                } else {
+                    (x, new)
+                };
+                discarded.push(other);
+                kept
+            } else {
+                x
+            }
+        })
+        .collect();
+
+    rolls.add_history(res.clone());
+    if !discarded.is_empty() {
+        rolls.add_discarded_history(discarded);
+    }
+    Ok((TotalModifier::None, res))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn compute_option<RNG: DiceRollSource>(
     rolls: &mut SingleRollResult,
     sides: u64,
     res: Vec<DiceResult>,
     option: Pair<Rule>,
     rng: &mut RNG,
-    prev_modifier: &TotalModifier,
+    prev_rule: Option<Rule>,
+    group: usize,
+    max_explosions: Option<u64>,
 ) -> Result<OptionResult> {
     let (modifier, mut res) = match &option.as_rule() {
-        Rule::explode => compute_explode(rolls, sides, res, option, prev_modifier, rng),
-        Rule::i_explode => compute_i_explode(rolls, sides, res, option, prev_modifier, rng),
-        Rule::reroll => compute_reroll(rolls, sides, res, option, rng),
-        Rule::i_reroll => compute_i_reroll(rolls, sides, res, option, rng),
+        Rule::explode => compute_explode(
+            rolls,
+            sides,
+            res,
+            option,
+            prev_rule,
+            rng,
+            group,
+            max_explosions,
+        )?,
+        Rule::i_explode => compute_i_explode(
+            rolls,
+            sides,
+            res,
+            option,
+            prev_rule,
+            rng,
+            group,
+            max_explosions,
+        )?,
+        Rule::reroll => compute_reroll(rolls, sides, res, option, rng, group)?,
+        Rule::i_reroll => compute_i_reroll(rolls, sides, res, option, rng, group)?,
+        Rule::add_reroll => compute_add_reroll(rolls, sides, res, option, rng, group)?,
+        Rule::reroll_hi | Rule::reroll_lo => {
+            compute_reroll_keep(rolls, sides, res, option, rng, group)?
+        }
         Rule::keep_hi => {
-            let value = extract_option_value(option).unwrap();
+            let value = extract_option_value(option)?.unwrap();
             if rolls.get_history().is_empty() {
-                rolls.add_history(res.clone(), false);
+                rolls.add_history(res.clone());
             }
             (TotalModifier::KeepHi(value as usize), res)
         }
         Rule::keep_lo => {
-            let value = extract_option_value(option).unwrap();
+            let value = extract_option_value(option)?.unwrap();
             if rolls.get_history().is_empty() {
-                rolls.add_history(res.clone(), false);
+                rolls.add_history(res.clone());
             }
             (TotalModifier::KeepLo(value as usize), res)
         }
+        Rule::keep_idx => {
+            let indices = option
+                .into_inner()
+                .map(|p| parse_number(&p))
+                .collect::<Result<Vec<usize>>>()?;
+            check_indices(&indices, res.len())?;
+            if rolls.get_history().is_empty() {
+                rolls.add_history_in_roll_order(res.clone());
+            }
+            (TotalModifier::KeepIndices(indices), res)
+        }
+        Rule::keep_cmp => {
+            let (comparator, value) = extract_comparison(option)?;
+            if rolls.get_history().is_empty() {
+                rolls.add_history(res.clone());
+            }
+            (TotalModifier::KeepCompare(comparator, value), res)
+        }
+        Rule::drop_cmp => {
+            let (comparator, value) = extract_comparison(option)?;
+            if rolls.get_history().is_empty() {
+                rolls.add_history(res.clone());
+            }
+            (TotalModifier::DropCompare(comparator, value), res)
+        }
+        Rule::median => {
+            if rolls.get_history().is_empty() {
+                rolls.add_history(res.clone());
+            }
+            (TotalModifier::Median, res)
+        }
         Rule::drop_hi => {
-            let value = extract_option_value(option).unwrap();
+            let value = extract_option_value(option)?.unwrap();
             if rolls.get_history().is_empty() {
-                rolls.add_history(res.clone(), false);
+                rolls.add_history(res.clone());
             }
             (TotalModifier::DropHi(value as usize), res)
         }
         Rule::drop_lo => {
-            let value = extract_option_value(option).unwrap();
+            let value = extract_option_value(option)?.unwrap();
             if rolls.get_history().is_empty() {
-                rolls.add_history(res.clone(), false);
+                rolls.add_history(res.clone());
             }
             (TotalModifier::DropLo(value as usize), res)
         }
@@ -234,29 +623,37 @@ fn compute_option<RNG: DiceRollSource>(
             let value_or_enum = option.into_inner().next().unwrap();
             match value_or_enum.as_rule() {
                 Rule::number => (
-                    TotalModifier::TargetFailureDouble(
-                        value_or_enum.as_str().parse::<u64>().unwrap(),
-                        0,
-                        0,
-                    ),
+                    TotalModifier::TargetFailureDouble(parse_number(&value_or_enum)?, 0, 0),
                     res,
                 ),
                 Rule::target_enum => {
                     let numbers_list = value_or_enum.into_inner();
-                    let numbers_list: Vec<_> = numbers_list
-                        .map(|p| p.as_str().parse::<u64>().unwrap())
-                        .collect();
+                    let numbers_list = numbers_list
+                        .map(|p| parse_number(&p))
+                        .collect::<Result<Vec<u64>>>()?;
                     (TotalModifier::TargetEnum(numbers_list), res)
                 }
                 _ => unreachable!(),
             }
         }
+        Rule::compare => {
+            let has_botch = option
+                .clone()
+                .into_inner()
+                .any(|p| p.as_rule() == Rule::botch);
+            let (comparator, value) = extract_comparison(option)?;
+            if has_botch {
+                (TotalModifier::CompareBotch(comparator, value), res)
+            } else {
+                (TotalModifier::Compare(comparator, value), res)
+            }
+        }
         Rule::double_target => {
-            let value = extract_option_value(option).unwrap();
+            let value = extract_option_value(option)?.unwrap();
             (TotalModifier::TargetFailureDouble(0, 0, value), res)
         }
         Rule::failure => {
-            let value = extract_option_value(option).unwrap();
+            let value = extract_option_value(option)?.unwrap();
             (TotalModifier::TargetFailureDouble(0, value, 0), res)
         }
         _ => unreachable!("{:#?}", option),
@@ -277,35 +674,134 @@ fn compute_option<RNG: DiceRollSource>(
                 n
             }
         }
-        TotalModifier::None(_)
+        TotalModifier::None
+        | TotalModifier::KeepIndices(_)
+        | TotalModifier::KeepCompare(_, _)
+        | TotalModifier::DropCompare(_, _)
+        | TotalModifier::Median
         | TotalModifier::TargetFailureDouble(_, _, _)
         | TotalModifier::TargetEnum(_)
-        | TotalModifier::Fudge => 0,
+        | TotalModifier::Compare(_, _)
+        | TotalModifier::CompareBotch(_, _)
+        | TotalModifier::Fudge(_) => 0,
     };
+    if let TotalModifier::KeepIndices(indices) = &modifier {
+        // positions are in roll order, so select before sorting
+        let res = indices.iter().map(|&i| res[i - 1]).collect();
+        return Ok(OptionResult { res, modifier });
+    }
+    if let TotalModifier::KeepCompare(comparator, value) = modifier {
+        res.retain(|d| comparator.compare(d.res, value));
+        return Ok(OptionResult { res, modifier });
+    }
+    if let TotalModifier::DropCompare(comparator, value) = modifier {
+        res.retain(|d| !comparator.compare(d.res, value));
+        return Ok(OptionResult { res, modifier });
+    }
     res.sort_unstable();
     let res = match modifier {
         TotalModifier::KeepHi(_) => res[res.len() - n..].to_vec(),
         TotalModifier::KeepLo(_) => res[..n].to_vec(),
         TotalModifier::DropHi(_) => res[..res.len() - n].to_vec(),
         TotalModifier::DropLo(_) => res[n..].to_vec(),
-        TotalModifier::None(_)
+        TotalModifier::Median => res
+            .iter()
+            .skip(res.len().saturating_sub(1) / 2)
+            .take(1)
+            .copied()
+            .collect(),
+        TotalModifier::None
+        | TotalModifier::KeepIndices(_)
+        | TotalModifier::KeepCompare(_, _)
+        | TotalModifier::DropCompare(_, _)
         | TotalModifier::TargetFailureDouble(_, _, _)
         | TotalModifier::TargetEnum(_)
-        | TotalModifier::Fudge => res,
+        | TotalModifier::Compare(_, _)
+        | TotalModifier::CompareBotch(_, _)
+        | TotalModifier::Fudge(_) => res,
     };
     Ok(OptionResult { res, modifier })
 }
 
+// Add the dice whose successes are counted to the history. After a keep or drop option, they are
+// already in it: the successes are counted on the dice it kept instead.
+fn add_counted_dice(rolls: &mut SingleRollResult, previous: &TotalModifier, res: &[DiceResult]) {
+    match previous {
+        TotalModifier::KeepHi(_)
+        | TotalModifier::KeepLo(_)
+        | TotalModifier::DropHi(_)
+        | TotalModifier::DropLo(_)
+        | TotalModifier::KeepIndices(_)
+        | TotalModifier::KeepCompare(_, _)
+        | TotalModifier::DropCompare(_, _)
+        | TotalModifier::Median => rolls.set_keep(previous.clone()),
+        _ => rolls.add_history(res.to_vec()),
+    }
+}
+
+// roll dice, their number being rolled first if it's a block like `(1d4)d6`
+fn compute_dice<RNG: DiceRollSource>(
+    pair: Pair<Rule>,
+    rng: &mut RNG,
+    options: &RollOptions,
+    depth: usize,
+    group: &mut usize,
+    current: usize,
+) -> Result<SingleRollResult> {
+    let mut dice = pair.into_inner();
+    match dice.peek().map(|p| p.as_rule()) {
+        Some(Rule::dice_count) => {
+            let block = dice.next().unwrap().into_inner().next().unwrap();
+            let expr = block.into_inner().next().unwrap().into_inner();
+            let count = compute(expr, rng, options, depth + 1, group)?;
+            let nb = u64::try_from(count.get_total())
+                .ok()
+                .filter(|&nb| nb > 0)
+                .ok_or_else(|| format!("Can't roll {} dice", count.get_total()))?;
+            Ok(compute_roll(dice, Some(nb), rng, options, current)?.with_rolled_count(count))
+        }
+        _ => compute_roll(dice, None, rng, options, current),
+    }
+}
+
+// roll the dice of a dice term, `count` being their number if it was rolled
 fn compute_roll<RNG: DiceRollSource>(
+    dice: Pairs<Rule>,
+    count: Option<u64>,
+    rng: &mut RNG,
+    options: &RollOptions,
+    group: usize,
+) -> Result<SingleRollResult> {
+    let reroll_pool = dice
+        .clone()
+        .find(|p| p.as_rule() == Rule::reroll_pool)
+        .map(extract_comparison)
+        .transpose()?;
+    let first = compute_pool(dice.clone(), count, rng, options, group)?;
+    match reroll_pool {
+        // the whole pool is rolled again once, both rolls being kept in the history
+        Some((comparator, value)) if comparator.compare(first.get_total(), value as i64) => {
+            let second = compute_pool(dice, count, rng, options, group)?;
+            Ok(SingleRollResult::from_attempts(vec![first, second], 1))
+        }
+        _ => Ok(first),
+    }
+}
+
+// Roll the dice and apply their options, except rerolling the whole pool
+fn compute_pool<RNG: DiceRollSource>(
     mut dice: Pairs<Rule>,
+    count: Option<u64>,
     rng: &mut RNG,
+    options: &RollOptions,
+    group: usize,
 ) -> Result<SingleRollResult> {
     let mut rolls = SingleRollResult::new();
     let maybe_nb = dice.next().unwrap();
     let nb = match maybe_nb.as_rule() {
         Rule::nb_dice => {
             dice.next(); // skip `d` token
-            let n = maybe_nb.as_str().parse::<u64>().unwrap();
+            let n = parse_number(&maybe_nb)?;
             // if n > MAX_NB_DICE {
             //     return Err(
             //         format!("Exceed maximum allowed number of dices ({})", MAX_NB_DICE).into(),
@@ -313,32 +809,56 @@ fn compute_roll<RNG: DiceRollSource>(
             // }
             n
         }
-        Rule::roll => 1, // no number before `d`, assume 1 dice
+        // no number before `d`, assume 1 dice
+        Rule::roll => count.unwrap_or(1),
         _ => unreachable!("{:?}", maybe_nb),
     };
 
     let pair = dice.next().unwrap();
     let (sides, is_fudge) = match pair.as_rule() {
-        Rule::number => (pair.as_str().parse::<u64>().unwrap(), false),
+        Rule::number => (parse_number(&pair)?, false),
         Rule::fudge => (6, true),
         _ => unreachable!("{:?}", pair),
     };
 
-    if sides == 0 {
-        return Err("Dice can't have 0 sides".into());
-    }
-    // else if sides > MAX_DICE_SIDES {
+    // if sides > MAX_DICE_SIDES {
     //     return Err(format!("Dice can't have more than {}", MAX_DICE_SIDES).into());
     // }
 
-    let mut res = roll_dice(nb, sides, rng);
-    let mut modifier = TotalModifier::None(Rule::expr);
+    check_options(nb, sides, is_fudge, dice.clone())?;
+
+    let max_explosions = options
+        .max_explosion_percent
+        .map(|percent| nb.saturating_mul(percent) / 100);
+    let mut res = roll_group_dice(nb, sides, rng, group)?;
+    // added to the dice once the explosions and rerolls are done, which read the values rolled
+    let per_die = match dice.peek().map(|p| p.as_rule()) {
+        Some(Rule::per_die) => Some(parse_per_die(&dice.next().unwrap())?),
+        _ => None,
+    };
+    // critic markers don't change the roll, they are applied once all the dice are rolled
+    let (crits, dice): (Vec<_>, Vec<_>) = dice
+        .filter(|p| p.as_rule() != Rule::reroll_pool)
+        .partition(|p| matches!(p.as_rule(), Rule::crit_success | Rule::crit_failure));
+    let mut dice = dice.into_iter();
+    let mut modifier = TotalModifier::None;
+    let mut prev_rule = None;
     let mut next_option = dice.next();
     if !is_fudge {
         if next_option.is_some() {
             while next_option.is_some() {
                 let option = next_option.unwrap();
-                let opt_res = compute_option(&mut rolls, sides, res, option, rng, &modifier)?;
+                let rule = option.as_rule();
+                let opt_res = compute_option(
+                    &mut rolls,
+                    sides,
+                    res,
+                    option,
+                    rng,
+                    prev_rule,
+                    group,
+                    max_explosions,
+                )?;
                 res = opt_res.res;
                 modifier = match opt_res.modifier {
                     TotalModifier::TargetFailureDouble(t, f, d) => match modifier {
@@ -352,79 +872,214 @@ fn compute_roll<RNG: DiceRollSource>(
                             }
                         }
                         _ => {
-                            rolls.add_history(res.clone(), is_fudge);
+                            add_counted_dice(&mut rolls, &modifier, &res);
                             opt_res.modifier
                         }
                     },
                     TotalModifier::TargetEnum(_) => {
-                        rolls.add_history(res.clone(), is_fudge);
+                        add_counted_dice(&mut rolls, &modifier, &res);
+                        opt_res.modifier
+                    }
+                    TotalModifier::Compare(comparator, target)
+                    | TotalModifier::CompareBotch(comparator, target) => {
+                        add_counted_dice(&mut rolls, &modifier, &res);
+                        rolls.set_check(comparator, target);
                         opt_res.modifier
                     }
                     _ => opt_res.modifier,
                 };
+                prev_rule = Some(rule);
                 next_option = dice.next();
             }
         } else {
-            rolls.add_history(res, is_fudge);
+            rolls.add_history(res);
         }
+        if let Some(modifier) = per_die {
+            rolls.map_dice(|value| apply_per_die(value, modifier));
+        }
+        if !crits.is_empty() {
+            let mut success = (Comparator::Eq, sides);
+            let mut failure = (Comparator::Eq, 1);
+            for crit in crits {
+                match crit.as_rule() {
+                    Rule::crit_success => success = extract_crit(crit)?,
+                    _ => failure = extract_crit(crit)?,
+                }
+            }
+            rolls.set_crits(success, failure);
+        }
+        rolls.set_overbotch(options.overbotch);
         rolls.compute_total(modifier)?;
     } else {
-        rolls.add_history(res, is_fudge);
-        rolls.compute_total(if is_fudge {
-            TotalModifier::Fudge
-        } else {
-            TotalModifier::None(Rule::expr)
-        })?;
+        let config = options.fudge;
+        if config.minus_max > config.blank_max {
+            return Err("Fudge dice blank threshold can't be lower than the minus one".into());
+        }
+        if options.fudge_explode {
+            let is_plus = |d: &DiceResult| config.value(d.res) > 0;
+            let mut nb = res.iter().filter(|d| is_plus(d)).count() as u64;
+            while nb > 0 && !rng.is_exhausted() {
+                let rolled = nb.min(explosions_left(&rolls, max_explosions));
+                if rolled == 0 {
+                    break;
+                }
+                let exploded = roll_exploded_dice(rolled, sides, rng, group)?;
+                nb = nb - rolled + exploded.iter().filter(|d| is_plus(d)).count() as u64;
+                rolls.add_explosions(rolled);
+                res.extend(exploded);
+            }
+            if nb > 0 {
+                rolls.add_warning(Warning::ExplosionCapReached);
+            }
+        }
+        rolls.add_fudge_history(res, config);
+        rolls.compute_total(TotalModifier::Fudge(config))?;
     }
 
     Ok(rolls)
 }
 
-// compute a whole roll expression
+// `%#` is rolled as `1d100<=#`
+fn compute_percent<RNG: DiceRollSource>(
+    percent: Pair<Rule>,
+    rng: &mut RNG,
+    group: usize,
+) -> Result<SingleRollResult> {
+    let target = parse_number(&percent.into_inner().next().unwrap())?;
+    let mut rolls = SingleRollResult::new();
+    rolls.add_history(roll_group_dice(1, 100, rng, group)?);
+    rolls.set_check(Comparator::Le, target);
+    rolls.compute_total(TotalModifier::Compare(Comparator::Le, target))?;
+    Ok(rolls)
+}
+
+// roll dice with named faces, which don't count in the total
+fn compute_named_dice<RNG: DiceRollSource>(
+    pair: Pair<Rule>,
+    rng: &mut RNG,
+    options: &RollOptions,
+    group: usize,
+) -> Result<SingleRollResult> {
+    let (count, name) = parse_named_dice(pair)?;
+    let faces = options
+        .named_dice
+        .get(&name)
+        .ok_or_else(|| format!("Unknown die `{}`, it must be registered first", name))?;
+    let mut rolls = SingleRollResult::new();
+    rolls.add_faces_history(
+        roll_group_dice(count, faces.len() as u64, rng, group)?
+            .iter()
+            .map(|d| faces[d.res as usize - 1].clone())
+            .collect(),
+    );
+    rolls.compute_total(TotalModifier::None)?;
+    Ok(rolls)
+}
+
+// Number of dice and name of a named dice term
+pub(crate) fn parse_named_dice(pair: Pair<Rule>) -> Result<(u64, String)> {
+    let mut inner = pair.into_inner();
+    let first = inner.next().unwrap();
+    let count = match first.as_rule() {
+        Rule::nb_dice => {
+            inner.next(); // skip `d` token
+            parse_number(&first)?
+        }
+        _ => 1,
+    };
+    Ok((count, inner.next().unwrap().as_str().to_owned()))
+}
+
+// compute a whole roll expression, `depth` being the number of parentheses around it and `group`
+// the number of roll groups computed before it
 pub(crate) fn compute<RNG: DiceRollSource>(
     expr: Pairs<Rule>,
     rng: &mut RNG,
-    is_block: bool,
+    options: &RollOptions,
+    depth: usize,
+    group: &mut usize,
 ) -> Result<SingleRollResult> {
+    if depth > options.max_depth {
+        return Err(RollError::TooDeep(options.max_depth));
+    }
     let res = get_climber().climb(
         expr,
-        |pair: Pair<Rule>| match pair.as_rule() {
-            Rule::integer => Ok(SingleRollResult::with_total(
-                pair.as_str().replace(' ', "").parse::<i64>().unwrap(),
-            )),
-            Rule::float => Ok(SingleRollResult::with_float(
-                pair.as_str().replace(' ', "").parse::<f64>().unwrap(),
-            )),
-            Rule::block_expr => {
-                let expr = pair.into_inner().next().unwrap().into_inner();
-                compute(expr, rng, true)
+        |pair: Pair<Rule>| {
+            let rule = pair.as_rule();
+            let is_block = matches!(rule, Rule::block_expr | Rule::best | Rule::worst);
+            // a block counts its own groups, any other operand is one group
+            let current = *group;
+            if !is_block {
+                *group += 1;
+            }
+            // implicit whitespace may be part of the pair, it's not part of the span
+            let start = pair.as_span().start();
+            let span = start..start + pair.as_str().trim_end().len();
+            // length of the opening parenthesis, with the function name
+            let open_len = pair.as_str().find('(').map_or(1, |i| i + 1);
+            let mut res = match rule {
+                Rule::integer => pair
+                    .as_str()
+                    .replace(' ', "")
+                    .parse::<i64>()
+                    .map(SingleRollResult::with_total)
+                    .map_err(|e| format!("Invalid number `{}`: {}", pair.as_str(), e).into()),
+                Rule::float => Ok(SingleRollResult::with_float(
+                    pair.as_str().replace(' ', "").parse::<f64>().unwrap(),
+                )),
+                Rule::block_expr => {
+                    let expr = pair.into_inner().next().unwrap().into_inner();
+                    compute(expr, rng, options, depth + 1, group)
+                }
+                Rule::best | Rule::worst => compute_attempts(pair, rng, options, depth, group),
+                Rule::dice => compute_dice(pair, rng, options, depth, group, current),
+                Rule::percent => compute_percent(pair, rng, current),
+                Rule::named_dice => compute_named_dice(pair, rng, options, current),
+                Rule::fractional_dice => Err(fractional_dice_error(&pair)),
+                _ => unreachable!("{:#?}", pair),
+            }?;
+            if options.spans {
+                if is_block {
+                    let last = res.get_history().len() - 1;
+                    res.set_span_at(0, span.start..span.start + open_len);
+                    res.set_span_at(last, span.end - 1..span.end);
+                } else {
+                    res.set_span(span);
+                }
             }
-            Rule::dice => compute_roll(pair.into_inner(), rng),
-            _ => unreachable!("{:#?}", pair),
+            Ok(res)
         },
-        |lhs: Result<SingleRollResult>, op: Pair<Rule>, rhs: Result<SingleRollResult>| match (
-            lhs, rhs,
-        ) {
-            (Ok(lhs), Ok(rhs)) => match op.as_rule() {
-                Rule::add => Ok(lhs + rhs),
-                Rule::sub => Ok(lhs - rhs),
-                Rule::mul => Ok(lhs * rhs),
+        |lhs: Result<SingleRollResult>, op: Pair<Rule>, rhs: Result<SingleRollResult>| {
+            let (lhs, rhs) = (lhs?, rhs?);
+            // the separator is inserted after the history of the left operand
+            let separator = if rhs.get_history().is_empty() {
+                None
+            } else {
+                Some(lhs.get_history().len())
+            };
+            let mut res = match op.as_rule() {
+                Rule::add => lhs.checked_add(rhs),
+                Rule::sub => lhs.checked_sub(rhs),
+                Rule::mul => lhs.checked_mul(rhs),
                 Rule::div => {
                     if rhs.is_zero() {
                         Err("Can't divide by zero".into())
                     } else {
-                        Ok(lhs / rhs)
+                        lhs.checked_div(rhs)
                     }
                 }
+                Rule::pow => lhs.checked_pow(rhs),
                 _ => unreachable!(),
-            },
-            (Err(e), _) => Err(e),
-            (_, Err(e)) => Err(e),
+            }?;
+            if let (true, Some(index)) = (options.spans, separator) {
+                res.set_span_at(index, op.as_span().start()..op.as_span().end());
+            }
+            Ok(res)
         },
     );
     match res {
         Ok(mut single_roll_res) => {
-            if is_block {
+            if depth > 0 {
                 single_roll_res.add_parenthesis();
             }
             Ok(single_roll_res)
@@ -433,6 +1088,41 @@ pub(crate) fn compute<RNG: DiceRollSource>(
     }
 }
 
+// Number of rolls of `best` and `worst`, at least one
+pub(crate) fn parse_attempts(pair: &Pair<Rule>) -> Result<u64> {
+    match parse_number(pair)? {
+        0 => Err("`best` and `worst` need at least one roll".into()),
+        count => Ok(count),
+    }
+}
+
+// roll the expression of `best` or `worst` several times, keeping the highest or lowest total
+fn compute_attempts<RNG: DiceRollSource>(
+    pair: Pair<Rule>,
+    rng: &mut RNG,
+    options: &RollOptions,
+    depth: usize,
+    group: &mut usize,
+) -> Result<SingleRollResult> {
+    let highest = pair.as_rule() == Rule::best;
+    let mut inner = pair.into_inner();
+    let count = parse_attempts(&inner.next().unwrap())?;
+    let expr = inner.next().unwrap();
+    let mut attempts: Vec<SingleRollResult> = Vec::new();
+    let mut kept = 0;
+    for i in 0..count {
+        let attempt = compute(expr.clone().into_inner(), rng, options, depth + 1, group)?;
+        let total = attempt.get_total();
+        if let Some(best) = attempts.get(kept).map(SingleRollResult::get_total) {
+            if (highest && total > best) || (!highest && total < best) {
+                kept = i as usize;
+            }
+        }
+        attempts.push(attempt);
+    }
+    Ok(SingleRollResult::from_attempts(attempts, kept))
+}
+
 pub(crate) fn find_first_dice(expr: &mut Pairs<Rule>) -> Option<String> {
     let mut next_pair = expr.next();
     while next_pair.is_some() {
@@ -457,9 +1147,257 @@ pub(crate) fn roll_dice<RNG: DiceRollSource>(
         .collect()
 }
 
-fn extract_option_value(option: Pair<Rule>) -> Option<u64> {
+// Reject the option stacks that can't be computed without ambiguity, before rolling anything
+pub(crate) fn check_options<'i>(
+    nb: u64,
+    sides: u64,
+    is_fudge: bool,
+    options: impl Iterator<Item = Pair<'i, Rule>>,
+) -> Result<()> {
+    if sides == 0 {
+        return Err("Dice can't have 0 sides".into());
+    }
+    let mut selection: Option<Pair<Rule>> = None;
+    let mut targets: Vec<Pair<Rule>> = Vec::new();
+    let mut has_explode = false;
+    for option in options {
+        let rule = option.as_rule();
+        match rule {
+            Rule::per_die if is_fudge => {
+                return Err(
+                    format!("`{}` can't be used on Fudge dice", option.as_str().trim()).into(),
+                );
+            }
+            // the other options are ignored on Fudge dice
+            _ if is_fudge => (),
+            Rule::explode | Rule::i_explode => {
+                has_explode = true;
+                let value = extract_option_value(option.clone())?.unwrap_or(sides);
+                if rule == Rule::i_explode && value <= 1 {
+                    return Err(format!(
+                        "`{}` would explode indefinitely on every value",
+                        option.as_str().trim()
+                    )
+                    .into());
+                }
+            }
+            // the added dice change the size of the pool, like exploded dice
+            Rule::add_reroll => has_explode = true,
+            Rule::i_reroll if extract_option_value(option.clone())?.unwrap_or(0) >= sides => {
+                return Err(format!(
+                    "`{}` would reroll indefinitely every value of a d{}",
+                    option.as_str().trim(),
+                    sides
+                )
+                .into());
+            }
+            Rule::keep_hi
+            | Rule::keep_lo
+            | Rule::keep_idx
+            | Rule::keep_cmp
+            | Rule::median
+            | Rule::drop_cmp
+            | Rule::drop_hi
+            | Rule::drop_lo => {
+                if let Some(previous) = &selection {
+                    return Err(format!(
+                        "Can't use `{}` and `{}` together, only one keep or drop option is allowed",
+                        previous.as_str().trim(),
+                        option.as_str().trim()
+                    )
+                    .into());
+                }
+                // keeping no dice always totals 0, likely a mistake. Dropping none keeps them all.
+                if matches!(rule, Rule::keep_hi | Rule::keep_lo)
+                    && extract_option_value(option.clone())? == Some(0)
+                {
+                    return Err(format!("`{}` would keep no dice", option.as_str().trim()).into());
+                }
+                // exploded dice change the size of the pool
+                if !has_explode && rule == Rule::keep_idx {
+                    let indices = option
+                        .clone()
+                        .into_inner()
+                        .map(|p| parse_number(&p))
+                        .collect::<Result<Vec<usize>>>()?;
+                    check_indices(&indices, usize::try_from(nb).unwrap_or(usize::MAX))?;
+                } else if !has_explode
+                    && !matches!(rule, Rule::keep_cmp | Rule::drop_cmp | Rule::median)
+                {
+                    let value = extract_option_value(option.clone())?.unwrap_or(0);
+                    let verb = match rule {
+                        Rule::keep_hi | Rule::keep_lo => "keep",
+                        _ => "drop",
+                    };
+                    if value > nb {
+                        return Err(format!("Can't {} {} dice out of {}", verb, value, nb).into());
+                    }
+                }
+                selection = Some(option);
+            }
+            Rule::target
+            | Rule::double_target
+            | Rule::failure
+            | Rule::compare
+            | Rule::crit_success
+            | Rule::crit_failure => {
+                if let Some(previous) = targets.iter().find(|p| p.as_rule() == rule) {
+                    return Err(format!(
+                        "Can't use `{}` and `{}` together",
+                        previous.as_str().trim(),
+                        option.as_str().trim()
+                    )
+                    .into());
+                }
+                targets.push(option);
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+// Check that the positions given to keep are valid for `len` dice
+pub(crate) fn check_indices(indices: &[usize], len: usize) -> Result<()> {
+    for (i, &index) in indices.iter().enumerate() {
+        if index == 0 || index > len {
+            return Err(format!(
+                "Can't keep die at position {}, positions go from 1 to {}",
+                index, len
+            )
+            .into());
+        }
+        if indices[..i].contains(&index) {
+            return Err(format!("Die at position {} is kept twice", index).into());
+        }
+    }
+    Ok(())
+}
+
+// Get the value added to each die by `(+# each)`
+pub(crate) fn parse_per_die(per_die: &Pair<Rule>) -> Result<i64> {
+    let integer = per_die.clone().into_inner().next().unwrap();
+    integer
+        .as_str()
+        .replace(' ', "")
+        .parse()
+        .map_err(|e| format!("Invalid number `{}`: {}", integer.as_str(), e).into())
+}
+
+// Add the per-die modifier to a die, which can't go under 1
+fn apply_per_die(value: u64, modifier: i64) -> u64 {
+    if modifier < 0 {
+        value.saturating_sub(modifier.unsigned_abs()).max(1)
+    } else {
+        value.saturating_add(modifier as u64)
+    }
+}
+
+// Get the comparator and the value of an option comparing each die to a value
+pub(crate) fn extract_comparison(option: Pair<Rule>) -> Result<(Comparator, u64)> {
+    let mut inner = option.into_inner();
+    let comparator = to_comparator(inner.next().unwrap().as_rule());
+    let value = parse_number(&inner.next().unwrap())?;
+    Ok((comparator, value))
+}
+
+// Get the comparison of a `cs` or `cf` option, where `>` and `<` include the value
+pub(crate) fn extract_crit(option: Pair<Rule>) -> Result<(Comparator, u64)> {
+    let mut inner = option.into_inner();
+    let first = inner.next().unwrap();
+    Ok(match first.as_rule() {
+        Rule::gt => (Comparator::Ge, parse_number(&inner.next().unwrap())?),
+        Rule::lt => (Comparator::Le, parse_number(&inner.next().unwrap())?),
+        _ => (Comparator::Eq, parse_number(&first)?),
+    })
+}
+
+fn extract_option_value(option: Pair<Rule>) -> Result<Option<u64>> {
     option
         .into_inner()
-        .next()
-        .map(|p| p.as_str().parse::<u64>().unwrap())
+        .find(|p| p.as_rule() == Rule::number)
+        .map(|p| parse_number(&p))
+        .transpose()
+}
+
+pub(crate) fn to_comparator(rule: Rule) -> Comparator {
+    match rule {
+        Rule::lt => Comparator::Lt,
+        Rule::le => Comparator::Le,
+        Rule::gt => Comparator::Gt,
+        Rule::ge => Comparator::Ge,
+        Rule::eq => Comparator::Eq,
+        _ => unreachable!("{:?}", rule),
+    }
+}
+
+// Explain why dice like `1.5d6` or `2d6.5` can't be rolled, suggesting whole dice when possible
+pub(crate) fn fractional_dice_error(pair: &Pair<Rule>) -> RollError {
+    let dice = pair.as_str().trim().replace(' ', "");
+    let (count, sides) = dice.split_at(dice.find(['d', 'D']).unwrap());
+    let sides = &sides[1..];
+    let suggestion = match sides.split_once('.') {
+        // the sides are fractional: the closest dice
+        Some((whole, _)) => whole.parse::<u64>().ok().map(|whole| {
+            let count = if count.is_empty() { "1" } else { count };
+            format!(
+                "{}d{} or {}d{}",
+                count,
+                whole,
+                count,
+                whole.saturating_add(1)
+            )
+        }),
+        // the count is fractional: the same dice range with whole dice, like `3d3` for `1.5d6`
+        None => {
+            let (whole, fraction) = count.split_once('.').unwrap();
+            let numerator = format!("{}{}", whole, fraction).parse::<u64>().ok();
+            let denominator = 10u64.checked_pow(fraction.len() as u32);
+            match (numerator, denominator, sides.parse::<u64>()) {
+                (Some(numerator), Some(denominator), Ok(sides)) if numerator > 0 => {
+                    let gcd = gcd(numerator, denominator);
+                    let (numerator, denominator) = (numerator / gcd, denominator / gcd);
+                    if sides % denominator == 0 && sides > denominator {
+                        Some(format!("{}d{}", numerator, sides / denominator))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            }
+        }
+    };
+    let kind = if sides.contains('.') {
+        "sides"
+    } else {
+        "counts"
+    };
+    let mut msg = format!(
+        "Invalid dice `{}`: dice {} must be whole numbers",
+        pair.as_str().trim(),
+        kind
+    );
+    if let Some(suggestion) = suggestion {
+        msg.push_str(&format!(", did you mean {}?", suggestion));
+    }
+    msg.into()
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Parse a number token, the grammar accepts numbers too big for the target type
+pub(crate) fn parse_number<T>(pair: &Pair<Rule>) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    pair.as_str()
+        .parse::<T>()
+        .map_err(|e| format!("Invalid number `{}`: {}", pair.as_str(), e).into())
 }
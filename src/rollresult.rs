@@ -1,17 +1,31 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, convert::TryFrom, fmt::Display, ops::Range};
 
+use crate::{error::Result, Comparator, TotalModifier};
+
+mod checkresult;
 mod diceresult;
+mod displayconfig;
+mod outcome;
 mod repeatedrollresult;
 mod rollhistory;
+mod rounding;
 mod singlerollresult;
+mod successlevel;
+mod warning;
 
+pub use checkresult::*;
 pub use diceresult::*;
+pub use displayconfig::*;
+pub use outcome::*;
 pub use repeatedrollresult::*;
 pub use rollhistory::*;
+pub use rounding::*;
 pub use singlerollresult::*;
+pub use successlevel::*;
+pub use warning::*;
 
 /// Distinguish between a simple roll and a repeated roll using `^`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RollResultType {
     /// A single roll
     Single(SingleRollResult),
@@ -23,26 +37,85 @@ pub enum RollResultType {
 ///
 /// A `RollResult` contains either a single roll result, or if the roll is repeated, a list of the
 /// same roll different results. And a reason if needed.
+///
+/// Two results are equal if they have the same totals, histories and reasons, so results can be
+/// used as keys of a map. The [draw count](RollResult::draw_count()) is not compared.
 #[derive(Debug, Clone)]
 pub struct RollResult {
     result: RollResultType,
     reason: Option<String>,
+    draws: u64,
+}
+
+impl PartialEq for RollResult {
+    fn eq(&self, other: &Self) -> bool {
+        self.result == other.result && self.reason == other.reason
+    }
+}
+
+impl Eq for RollResult {}
+
+impl std::hash::Hash for RollResult {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.result.hash(state);
+        self.reason.hash(state);
+    }
+}
+
+impl Default for RollResult {
+    /// An empty single roll result, with a total of 0 and no reason
+    fn default() -> Self {
+        RollResult::new_single(SingleRollResult::default())
+    }
 }
 
 impl RollResult {
+    /// Create an empty single roll result, with a total of 0 and no reason. Same as `Default`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a single roll result from its parts, see [`SingleRollResult::from_parts()`] for the
+    /// invariants of the total and the history.
+    ///
+    /// ```
+    /// use caith::{DiceResult, RollHistory, RollResult, Value};
+    ///
+    /// let history = vec![
+    ///     RollHistory::Roll(vec![DiceResult::new(5, 6), DiceResult::new(3, 6)]),
+    ///     RollHistory::Separator(" + "),
+    ///     RollHistory::Value(Value::Int(2)),
+    /// ];
+    /// let res = RollResult::from_parts(10, history, Some("test".to_owned())).unwrap();
+    /// assert_eq!("`[5, 3] + 2` = **10**, Reason: `test`", res.to_string());
+    /// ```
+    pub fn from_parts(
+        total: i64,
+        history: Vec<RollHistory>,
+        reason: Option<String>,
+    ) -> Result<Self> {
+        Ok(RollResult {
+            result: RollResultType::Single(SingleRollResult::from_parts(total, history)?),
+            reason,
+            draws: 0,
+        })
+    }
+
     /// Create a `RollResult` with only one single roll.
     pub fn new_single(r: SingleRollResult) -> Self {
         RollResult {
             result: RollResultType::Single(r),
             reason: None,
+            draws: 0,
         }
     }
 
     /// Create a `RollResult` with a repeated roll results.
     pub fn new_repeated(v: Vec<SingleRollResult>, total: Option<i64>) -> Self {
         RollResult {
-            result: RollResultType::Repeated(RepeatedRollResult { rolls: v, total }),
+            result: RollResultType::Repeated(RepeatedRollResult::new(v, total)),
             reason: None,
+            draws: 0,
         }
     }
 
@@ -69,6 +142,389 @@ impl RollResult {
         }
     }
 
+    /// Compute the total of a single roll result with the given modifier applied.
+    ///
+    /// All the values of the history are considered as one pool of dice, without the attempts not
+    /// kept by `best`, `worst` or a pool reroll. The result itself is not modified. It returns an
+    /// error if the result is a repeated roll or if the modifier can't be applied, like keeping or
+    /// dropping more dice than available.
+    pub fn total_with(&self, modifier: TotalModifier) -> Result<i64> {
+        self.as_single()
+            .ok_or("Not a single roll result")?
+            .total_with(modifier)
+    }
+
+    /// Says if no dice counting in the total were rolled to get this result, like for the
+    /// constant expression `5`. The dice with named faces don't count in the total, a result with
+    /// only them is empty, see [`RollResult::is_deterministic()`] to count them as rolled.
+    ///
+    /// This distinguishes a total of zero because nothing was rolled from a total of zero from a
+    /// roll, like zero success when counting successes.
+    pub fn is_empty(&self) -> bool {
+        match &self.result {
+            RollResultType::Single(result) => result.is_empty(),
+            RollResultType::Repeated(results) => results.iter().all(|r| r.is_empty()),
+        }
+    }
+
+    /// Says if the result was computed without rolling any die, like for `5` or `(2 + 3) * 2`, to
+    /// tell a calculated result from a rolled one.
+    ///
+    /// Unlike [`RollResult::is_empty()`], the dice with named faces count as rolled, even if they
+    /// don't count in the total.
+    pub fn is_deterministic(&self) -> bool {
+        let rolled = |r: &SingleRollResult| {
+            r.get_history().iter().any(|h| {
+                matches!(
+                    h,
+                    RollHistory::Roll(_)
+                        | RollHistory::Fudge(_, _)
+                        | RollHistory::Bonus(_)
+                        | RollHistory::Faces(_)
+                )
+            })
+        };
+        match &self.result {
+            RollResultType::Single(result) => !rolled(result),
+            RollResultType::Repeated(results) => !results.iter().any(rolled),
+        }
+    }
+
+    /// Get the sum of the exploded dice sent to the bonus tally with the `b` suffix (ex: `3d6 !b`).
+    ///
+    /// These dice are not part of the total. For a repeated roll, it's the sum of the bonus of
+    /// each roll.
+    pub fn bonus_total(&self) -> i64 {
+        match &self.result {
+            RollResultType::Single(result) => result.get_bonus_total(),
+            RollResultType::Repeated(results) => results.iter().map(|r| r.get_bonus_total()).sum(),
+        }
+    }
+
+    /// Get the number of dice which exploded, see [`SingleRollResult::get_explosion_count()`]. For
+    /// a repeated roll, it's the sum of the explosions of each roll.
+    pub fn explosion_count(&self) -> u64 {
+        match &self.result {
+            RollResultType::Single(result) => result.get_explosion_count(),
+            RollResultType::Repeated(results) => {
+                results.iter().map(|r| r.get_explosion_count()).sum()
+            }
+        }
+    }
+
+    /// Get the symbols of the Fudge dice rolled, `-`, `▢` or `+`, in the order they are shown in
+    /// the history. Use [`RollResult::outcome()`] for their net value.
+    ///
+    /// Returns `None` if the result isn't a single roll with Fudge dice.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let res = Roller::new("4dF").unwrap().roll_with_values(&[6, 1, 3, 5]).unwrap();
+    /// assert_eq!(Some(vec!['+', '+', '▢', '-']), res.fudge_symbols());
+    /// ```
+    pub fn fudge_symbols(&self) -> Option<Vec<char>> {
+        let symbols: Vec<char> = self
+            .as_single()?
+            .get_history()
+            .iter()
+            .filter_map(|h| match h {
+                RollHistory::Fudge(values, config) => Some(
+                    values
+                        .iter()
+                        .flat_map(move |&v| config.symbol(v).chars().next()),
+                ),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        if symbols.is_empty() {
+            None
+        } else {
+            Some(symbols)
+        }
+    }
+
+    /// Get the highest value rolled on a die, like to look for a natural 20.
+    ///
+    /// All the dice of the `Roll` steps of the history count, the ones dropped by a keep or drop
+    /// option too. Fudge dice, bonus and discarded dice don't. For a repeated roll, the dice of
+    /// all the rolls count. Returns `None` if no die was rolled.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let res = Roller::new("2d20 K1 + 1d4").unwrap().roll_with_values(&[20, 3, 2]).unwrap();
+    /// assert_eq!(Some(20), res.highest_die());
+    /// assert_eq!(Some(2), res.lowest_die());
+    /// ```
+    pub fn highest_die(&self) -> Option<u64> {
+        self.rolled_values().max()
+    }
+
+    /// Get the lowest value rolled on a die, like to look for a 1. The same dice as for
+    /// [`RollResult::highest_die()`] count.
+    pub fn lowest_die(&self) -> Option<u64> {
+        self.rolled_values().min()
+    }
+
+    /// Get each value rolled with the number of dice showing it, like to look for doubles or
+    /// triples. The same dice as for [`RollResult::highest_die()`] count.
+    ///
+    /// The values shown by the most dice come first, and the highest value first among the ones
+    /// shown by as many dice.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let res = Roller::new("5d6").unwrap().roll_with_values(&[3, 5, 3, 1, 5]).unwrap();
+    /// assert_eq!(vec![(5, 2), (3, 2), (1, 1)], res.matches());
+    /// ```
+    pub fn matches(&self) -> Vec<(u64, usize)> {
+        let mut tally = BTreeMap::new();
+        for value in self.rolled_values() {
+            *tally.entry(value).or_insert(0) += 1;
+        }
+        let mut matches: Vec<_> = tally.into_iter().collect();
+        matches.sort_by(|(a_value, a_count), (b_value, b_count)| {
+            b_count.cmp(a_count).then(b_value.cmp(a_value))
+        });
+        matches
+    }
+
+    // The single roll, or each roll of a repeated one
+    fn singles(&self) -> Box<dyn Iterator<Item = &SingleRollResult> + '_> {
+        match &self.result {
+            RollResultType::Single(result) => Box::new(std::iter::once(result)),
+            RollResultType::Repeated(results) => Box::new(results.iter()),
+        }
+    }
+
+    // Values of the dice of all the `Roll` steps of the history
+    fn rolled_values(&self) -> impl Iterator<Item = u64> + '_ {
+        self.singles()
+            .flat_map(|r| r.get_history())
+            .filter_map(|h| match h {
+                RollHistory::Roll(dice) => Some(dice.iter().map(|d| d.res)),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// Get the number of values drawn from the dice roll source to evaluate the expression, one
+    /// per die rolled, explosions and rerolls included, like to check how much of a random
+    /// source a roll uses.
+    ///
+    /// It's 0 for a result which wasn't rolled by a [`crate::Roller`], like one built with
+    /// [`RollResult::from_parts()`]. The counts of results combined with the arithmetic operators
+    /// are added.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let res = Roller::new("4d6 ir1").unwrap().roll_with_values(&[1, 4, 1, 2, 6, 3]).unwrap();
+    /// assert_eq!(6, res.draw_count());
+    /// ```
+    pub fn draw_count(&self) -> u64 {
+        self.draws
+    }
+
+    pub(crate) fn set_draw_count(&mut self, draws: u64) {
+        self.draws = draws;
+    }
+
+    /// Get the margin of the roll against a difficulty class: `total - dc`.
+    ///
+    /// A positive or zero margin means the roll meets the difficulty. A repeated roll is counted as
+    /// the sum of all its rolls.
+    pub fn margin(&self, dc: i64) -> i64 {
+        self.total().saturating_sub(dc)
+    }
+
+    /// Get the degrees of success of the roll against a difficulty class, one degree every
+    /// `degree_size` points of [margin](RollResult::margin()).
+    ///
+    /// A margin from `0` to `degree_size - 1` gives 0 degree: a plain success. Each full
+    /// `degree_size` above adds a degree. Failures give negative degrees: a margin from `-1` to
+    /// `-degree_size` gives `-1`, and so on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `degree_size` is 0.
+    pub fn degrees(&self, dc: i64, degree_size: u64) -> i64 {
+        assert!(degree_size > 0, "degree size can't be 0");
+        let size = i64::try_from(degree_size).unwrap_or(i64::MAX);
+        self.margin(dc).div_euclid(size)
+    }
+
+    /// Resolve a check against a difficulty class, with one degree every `degree_size` points of
+    /// margin like [`RollResult::degrees()`].
+    ///
+    /// When `roll_under` is set, the total must be lower than or equal to `dc` and the margin is
+    /// `dc - total`, otherwise it must be greater than or equal to `dc`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `degree_size` is 0.
+    pub fn check(&self, dc: i64, degree_size: u64, roll_under: bool) -> CheckResult {
+        assert!(degree_size > 0, "degree size can't be 0");
+        let size = i64::try_from(degree_size).unwrap_or(i64::MAX);
+        let total = self.total();
+        let margin = if roll_under {
+            dc.saturating_sub(total)
+        } else {
+            total.saturating_sub(dc)
+        };
+        CheckResult {
+            total,
+            success: margin >= 0,
+            degrees: margin.div_euclid(size),
+        }
+    }
+
+    /// Get the level of success of a roll-under check, like `1d100<=65`.
+    ///
+    /// Returns `None` if the result isn't a single roll of one die compared with `<=`.
+    pub fn success_level(&self) -> Option<SuccessLevel> {
+        self.as_single()?.get_success_level()
+    }
+
+    /// Get the meaning of the total: a sum, a count of successes or a sum of Fudge dice.
+    ///
+    /// The result of an arithmetic operation is a sum, even if an operand counts successes. A
+    /// repeated roll is the sum of its totals.
+    pub fn outcome(&self) -> Outcome {
+        match &self.result {
+            RollResultType::Single(result) => result.get_outcome(),
+            RollResultType::Repeated(_) => Outcome::Sum(self.total()),
+        }
+    }
+
+    /// Says if the total satisfies the comparison against `dc`, like a difficulty to beat. A
+    /// repeated roll is compared with the sum of its totals.
+    ///
+    /// ```
+    /// use caith::{Comparator, Roller};
+    ///
+    /// let res = Roller::new("1d20 + 5").unwrap().roll_with_values(&[10]).unwrap();
+    /// assert!(res.meets(15, Comparator::Ge));
+    /// assert!(!res.meets(15, Comparator::Gt));
+    /// ```
+    pub fn meets(&self, dc: i64, comparator: Comparator) -> bool {
+        comparator.compare(self.total(), dc)
+    }
+
+    /// Says if two results are the same roll, whatever the order or the grouping of their dice.
+    ///
+    /// The results are equivalent if they have the same total, the same reason, and the same
+    /// dice values once sorted: the dice of all the groups are compared together, dropped, bonus
+    /// and discarded dice included, and so are the faces of the named dice. Unlike `==`, the
+    /// history isn't compared: the order of the groups, the operators, the constants and the
+    /// flags of the dice, like exploded or dropped, are ignored.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let a = Roller::new("1d6 + 2d6").unwrap().roll_with_values(&[5, 2, 3]).unwrap();
+    /// let b = Roller::new("2d6 + 1d6").unwrap().roll_with_values(&[3, 5, 2]).unwrap();
+    /// assert!(a.equivalent(&b));
+    /// assert_ne!(a, b);
+    /// ```
+    pub fn equivalent(&self, other: &Self) -> bool {
+        self.total() == other.total()
+            && self.reason == other.reason
+            && self.sorted_dice() == other.sorted_dice()
+    }
+
+    // Values of all the dice and faces of all the named dice, sorted
+    fn sorted_dice(&self) -> (Vec<u64>, Vec<&str>) {
+        let mut values = Vec::new();
+        let mut faces = Vec::new();
+        for h in self.singles().flat_map(|r| r.get_history()) {
+            match h {
+                RollHistory::Roll(dice)
+                | RollHistory::Bonus(dice)
+                | RollHistory::Discarded(dice) => values.extend(dice.iter().map(|d| d.res)),
+                RollHistory::Fudge(dice, _) => values.extend(dice),
+                RollHistory::Faces(f) => faces.extend(f.iter().map(String::as_str)),
+                _ => (),
+            }
+        }
+        values.sort_unstable();
+        faces.sort_unstable();
+        (values, faces)
+    }
+
+    /// Add `other` to this result without consuming them, giving a new result with the same
+    /// history separator and reason merging as the `Add` implementation.
+    pub fn add_ref(&self, other: &Self) -> Self {
+        self.clone() + other.clone()
+    }
+
+    /// Multiply the total by `factor`, like for a critical hit multiplier, rounding the product
+    /// to an integer with `rounding`. The factor is appended to the history like a float
+    /// constant, as ` * 1.5`, the history being put between parentheses if it has several terms,
+    /// and the reason is kept. A repeated roll is counted as the sum of all its rolls.
+    ///
+    /// ```
+    /// use caith::{Rounding, Roller};
+    ///
+    /// let res = Roller::new("2d6").unwrap().roll_with_values(&[3, 4]).unwrap();
+    /// let res = res.scale(1.5, Rounding::Round);
+    /// assert_eq!(11, res.as_single().unwrap().get_total());
+    /// assert_eq!("[4, 3] * 1.5", res.history_string());
+    /// ```
+    pub fn scale(self, factor: f64, rounding: Rounding) -> Self {
+        let draws = self.draws;
+        let (single, reason) = self.into_single();
+        RollResult {
+            result: RollResultType::Single(single.scale(factor, rounding)),
+            reason,
+            draws,
+        }
+    }
+
+    /// Merge `other` into this result, without the separator the arithmetic operators insert
+    /// between the histories. Useful to assemble a custom display.
+    ///
+    /// The history of `other` is appended and the total is the sum of both totals: it's not
+    /// computed again from the merged history, so the modifiers applied to each part (keep, drop,
+    /// targets...) are preserved. A repeated roll is counted as the sum of all its rolls, and
+    /// reasons are merged like with the arithmetic operators.
+    pub fn merge(self, other: Self) -> Self {
+        self.combine(other, SingleRollResult::merge)
+    }
+
+    /// Get the index ranges of the history belonging to each roll group, see
+    /// [`SingleRollResult::get_group_ranges()`].
+    ///
+    /// It's empty for a repeated roll, each of its rolls having its own history.
+    pub fn group_ranges(&self) -> Vec<Range<usize>> {
+        self.as_single()
+            .map(|r| r.get_group_ranges())
+            .unwrap_or_default()
+    }
+
+    /// Get the changes made to complete the roll without failing it, like a capped total. For a
+    /// repeated roll, the changes made to any of the rolls.
+    ///
+    /// ```
+    /// use caith::{Roller, Warning};
+    ///
+    /// let res = Roller::new("2d6")
+    ///     .unwrap()
+    ///     .with_total_cap(10)
+    ///     .roll_with_values(&[6, 5])
+    ///     .unwrap();
+    /// assert_eq!(&[Warning::TotalCapped], res.warnings());
+    /// ```
+    pub fn warnings(&self) -> &[Warning] {
+        match &self.result {
+            RollResultType::Single(res) => res.get_warnings(),
+            RollResultType::Repeated(repeated) => repeated.get_warnings(),
+        }
+    }
+
     /// If the result is a repeated roll, it will return it.
     pub fn as_repeated(&self) -> Option<&RepeatedRollResult> {
         match &self.result {
@@ -78,31 +534,319 @@ impl RollResult {
     }
 }
 
-impl Display for RollResult {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+// Merge the reasons of two combined results: a reason is never lost, whatever the side it comes
+// from. If both operands carry a different reason, they are joined with `, `, left first.
+fn merge_reason(lhs: Option<String>, rhs: Option<String>) -> Option<String> {
+    match (lhs, rhs) {
+        (Some(l), Some(r)) if l == r => Some(l),
+        (Some(l), Some(r)) => Some(format!("{}, {}", l, r)),
+        (Some(reason), None) | (None, Some(reason)) => Some(reason),
+        (None, None) => None,
+    }
+}
+
+impl RollResult {
+    // Total of the result, a repeated roll is counted as the sum of all its rolls
+    fn total(&self) -> i64 {
+        match &self.result {
+            RollResultType::Single(result) => result.get_total(),
+            RollResultType::Repeated(results) => results.get_total().unwrap_or_else(|| {
+                results
+                    .iter()
+                    .fold(0i64, |acc, r| acc.saturating_add(r.get_total()))
+            }),
+        }
+    }
+
+    // Recompute the total of a single roll with a modifier
+    pub(crate) fn apply_modifier(&mut self, modifier: TotalModifier) -> Result<()> {
+        match &mut self.result {
+            RollResultType::Single(res) => res.apply_modifier(modifier),
+            RollResultType::Repeated(_) => Err("Can't apply a modifier on repeated rolls".into()),
+        }
+    }
+
+    // Keep the total of each roll between `floor` and `cap`, the sum of a repeated roll being
+    // computed again. The cap is applied last.
+    pub(crate) fn clamp_total(&mut self, floor: Option<i64>, cap: Option<i64>) -> Result<()> {
+        let clamp = |res: &mut SingleRollResult| {
+            if let Some(floor) = floor {
+                res.floor_total(floor);
+            }
+            if let Some(cap) = cap {
+                res.cap_total(cap);
+            }
+        };
+        match &mut self.result {
+            RollResultType::Single(res) => clamp(res),
+            RollResultType::Repeated(repeated) => {
+                repeated.rolls.iter_mut().for_each(clamp);
+                repeated.collect_warnings();
+                if repeated.total.is_some() {
+                    repeated.total = Some(
+                        repeated
+                            .rolls
+                            .iter()
+                            .try_fold(0i64, |acc, r| acc.checked_add(r.get_total()))
+                            .ok_or("Total is too big to be computed")?,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Says if the total was lowered to the cap given with [`crate::Roller::with_total_cap()`].
+    /// For a repeated roll, says if any of the rolls was capped.
+    pub fn is_capped(&self) -> bool {
+        match &self.result {
+            RollResultType::Single(res) => res.is_capped(),
+            RollResultType::Repeated(repeated) => repeated.iter().any(|r| r.is_capped()),
+        }
+    }
+
+    // Turn the result into a single roll to take part in an arithmetic operation. A repeated roll
+    // is counted as the sum of all its rolls.
+    fn into_single(self) -> (SingleRollResult, Option<String>) {
+        let single = match self.result {
+            RollResultType::Single(single) => single,
+            RollResultType::Repeated(repeated) => repeated
+                .rolls
+                .into_iter()
+                .reduce(|acc, roll| acc + roll)
+                .unwrap_or_else(|| SingleRollResult::with_total(0)),
+        };
+        (single, self.reason)
+    }
+
+    fn combine(
+        self,
+        rhs: Self,
+        op: fn(SingleRollResult, SingleRollResult) -> SingleRollResult,
+    ) -> Self {
+        let draws = self.draws.saturating_add(rhs.draws);
+        let (lhs, lhs_reason) = self.into_single();
+        let (rhs, rhs_reason) = rhs.into_single();
+        RollResult {
+            result: RollResultType::Single(op(lhs, rhs)),
+            reason: merge_reason(lhs_reason, rhs_reason),
+            draws,
+        }
+    }
+}
+
+/// Combining two `RollResult` gives a single roll result. A repeated roll is counted as the sum
+/// of all its rolls.
+///
+/// The reasons of both operands are kept: if only one operand has a reason, it's the reason of the
+/// result. If both have a different one, they are joined with `, `, left operand first. The same
+/// rule applies to all the arithmetic operators.
+///
+/// # Panics
+///
+/// Panics if the total overflows an `i64`, like the other arithmetic operators.
+impl std::ops::Add for RollResult {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.combine(rhs, |l, r| l + r)
+    }
+}
+
+/// See [`RollResult`]'s `Add` implementation for the reason merging policy.
+impl std::ops::Sub for RollResult {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.combine(rhs, |l, r| l - r)
+    }
+}
+
+/// See [`RollResult`]'s `Add` implementation for the reason merging policy.
+impl std::ops::Mul for RollResult {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.combine(rhs, |l, r| l * r)
+    }
+}
+
+/// See [`RollResult`]'s `Add` implementation for the reason merging policy.
+///
+/// # Panics
+///
+/// Panics if `rhs` total is zero, or if the total overflows an `i64`.
+impl std::ops::Div for RollResult {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        self.combine(rhs, |l, r| l / r)
+    }
+}
+
+/// Summing results chains them with `+`, like the `Add` implementation: the histories are joined
+/// with `+` separators and the reasons are merged the same way. Summing an empty iterator gives
+/// [`RollResult::new()`].
+///
+/// ```
+/// use caith::{RollResult, Roller};
+///
+/// let res: RollResult = ["1d6 : fire", "1d4 : cold"]
+///     .iter()
+///     .map(|input| Roller::new(input).unwrap().roll_with_values(&[4]).unwrap())
+///     .sum();
+/// assert_eq!(8, res.as_single().unwrap().get_total());
+/// assert_eq!(Some("fire, cold"), res.get_reason().map(String::as_str));
+/// ```
+impl std::iter::Sum for RollResult {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        // the first result is not added to `RollResult::new()` to avoid a leading separator
+        iter.reduce(|acc, res| acc + res).unwrap_or_default()
+    }
+}
+
+impl<'a> std::iter::Sum<&'a RollResult> for RollResult {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.cloned().sum()
+    }
+}
+
+// A constant operand for the operators with an integer
+fn constant(value: i64) -> RollResult {
+    RollResult::new_single(SingleRollResult::with_total(value))
+}
+
+/// Adding an integer to a `RollResult` gives a single roll result, with the integer appended to
+/// the history, like a situational modifier computed at runtime. The reason is kept.
+///
+/// ```
+/// use caith::Roller;
+///
+/// let res = Roller::new("1d6 : attack").unwrap().roll().unwrap();
+/// let total = res.as_single().unwrap().get_total();
+/// let res = res + 2;
+/// assert_eq!(total + 2, res.as_single().unwrap().get_total());
+/// ```
+impl std::ops::Add<i64> for RollResult {
+    type Output = Self;
+
+    fn add(self, rhs: i64) -> Self::Output {
+        self + constant(rhs)
+    }
+}
+
+/// See [`RollResult`]'s `Add<i64>` implementation.
+impl std::ops::Sub<i64> for RollResult {
+    type Output = Self;
+
+    fn sub(self, rhs: i64) -> Self::Output {
+        self - constant(rhs)
+    }
+}
+
+/// See [`RollResult`]'s `Add<i64>` implementation.
+impl std::ops::Mul<i64> for RollResult {
+    type Output = Self;
+
+    fn mul(self, rhs: i64) -> Self::Output {
+        self * constant(rhs)
+    }
+}
+
+/// See [`RollResult`]'s `Add<i64>` implementation.
+///
+/// # Panics
+///
+/// Panics if `rhs` is zero.
+impl std::ops::Div<i64> for RollResult {
+    type Output = Self;
+
+    fn div(self, rhs: i64) -> Self::Output {
+        self / constant(rhs)
+    }
+}
+
+impl RollResult {
+    /// Turn the result into a readable `String` using the given configuration. The default
+    /// configuration gives the same output as `Display`.
+    pub fn to_string_with(&self, config: &DisplayConfig) -> String {
+        let mut s = String::new();
+        // writing in a `String` can't fail
+        self.fmt_with(&mut s, config).unwrap();
+        s
+    }
+
+    /// Get only the history of the result, like `[4, 3] + 2`, without the total, the reason and
+    /// the backticks of `Display`. The histories of a repeated roll are on separate lines.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let res = Roller::new("2d6 + 2 : test").unwrap().roll_with_values(&[3, 4]).unwrap();
+    /// assert_eq!("[4, 3] + 2", res.history_string());
+    /// ```
+    pub fn history_string(&self) -> String {
+        self.history_string_with(&DisplayConfig::default())
+    }
+
+    /// Get only the history of the result using the given configuration, see
+    /// [`RollResult::history_string()`].
+    pub fn history_string_with(&self, config: &DisplayConfig) -> String {
+        match &self.result {
+            RollResultType::Single(result) => result.to_string_history_with(config),
+            RollResultType::Repeated(results) => results
+                .iter()
+                .map(|res| res.to_string_history_with(config))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Get only the history of the result without markdown. The default configuration has no
+    /// markdown, it's the same as [`RollResult::history_string()`].
+    pub fn history_string_plain(&self) -> String {
+        self.history_string_plain_with(&DisplayConfig::default())
+    }
+
+    /// Get only the history of the result using the given configuration, without markdown: the
+    /// dropped dice struck through with [`DisplayConfig::show_dropped`] are shown like the kept
+    /// ones.
+    pub fn history_string_plain_with(&self, config: &DisplayConfig) -> String {
+        if config.show_dropped == Some(true) {
+            let config = DisplayConfig {
+                show_dropped: None,
+                ..config.clone()
+            };
+            self.history_string_with(&config)
+        } else {
+            self.history_string_with(config)
+        }
+    }
+
+    fn fmt_with<W: std::fmt::Write>(&self, f: &mut W, config: &DisplayConfig) -> std::fmt::Result {
         match &self.result {
             RollResultType::Single(roll_result) => {
-                write!(f, "{}", roll_result.to_string(true))?;
+                write!(f, "{}", roll_result.to_string_with(true, config))?;
                 if let Some(reason) = &self.reason {
-                    write!(f, ", Reason: `{}`", reason)?;
+                    write!(f, ", Reason: {}", config.reason(reason))?;
                 }
             }
             RollResultType::Repeated(repeated_result) => match repeated_result.get_total() {
                 Some(total) => {
-                    (*repeated_result)
-                        .iter()
-                        .try_for_each(|res| writeln!(f, "`{}`", res.to_string_history()))?;
+                    (*repeated_result).iter().try_for_each(|res| {
+                        writeln!(f, "`{}`", res.to_string_history_with(config))
+                    })?;
                     write!(f, "Sum: **{}**", total)?;
                     if let Some(reason) = &self.reason {
-                        write!(f, ", Reason: `{}`", reason)?;
+                        write!(f, ", Reason: {}", config.reason(reason))?;
                     }
                 }
                 None => {
                     (*repeated_result)
                         .iter()
-                        .try_for_each(|res| writeln!(f, "{}", res.to_string(true)))?;
+                        .try_for_each(|res| writeln!(f, "{}", res.to_string_with(true, config)))?;
                     if let Some(reason) = &self.reason {
-                        write!(f, "Reason: `{}`", reason)?;
+                        write!(f, "Reason: {}", config.reason(reason))?;
                     }
                 }
             },
@@ -112,9 +856,1377 @@ impl Display for RollResult {
     }
 }
 
+impl RollResult {
+    /// Get a one line summary of the result, without markdown, meant for logs.
+    ///
+    /// Unlike `Display`, the format is stable across versions. It's a list of `key=value` fields
+    /// separated by a space, always in this order:
+    /// - `total`: the total of the roll. For a repeated roll, the sum of all the rolls
+    /// - `rolls`: only for a repeated roll, the total of each roll, like `[7,12]`
+    /// - `dice`: the values of the dice counted in the total, in history order, like `[3,4]`.
+    ///   The dropped dice are left out, and Fudge dice are given as `-1`, `0` or `1`
+    /// - `mod`: the sum of the constants added to or subtracted from the roll, always signed, like
+    ///   `+0`. A constant used in another operation, like `2` in `1d6 * 2`, isn't counted
+    /// - `bonus`: only if some dice went to the bonus tally, its sum
+    /// - `reason`: only if there is a reason, it's always the last field and runs to the end of
+    ///   the line, line breaks being replaced by spaces
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let res = Roller::new("2d6 + 1 : hello").unwrap().roll_with_values(&[3, 4]).unwrap();
+    /// assert_eq!("total=8 dice=[4,3] mod=+1 reason=hello", res.summary());
+    /// ```
+    pub fn summary(&self) -> String {
+        let rolls: Vec<&SingleRollResult> = match &self.result {
+            RollResultType::Single(result) => vec![result],
+            RollResultType::Repeated(results) => results.iter().collect(),
+        };
+        let mut s = format!("total={}", self.total());
+        if self.as_repeated().is_some() {
+            s.push_str(&format!(
+                " rolls={}",
+                join(rolls.iter().map(|r| r.get_total()))
+            ));
+        }
+        let dice = rolls.iter().flat_map(|r| summary_dice(r));
+        s.push_str(&format!(" dice={}", join(dice)));
+        let modifier: i64 = rolls.iter().map(|r| summary_modifier(r)).sum();
+        s.push_str(&format!(" mod={:+}", modifier));
+        if rolls.iter().any(|r| {
+            r.get_history()
+                .iter()
+                .any(|h| matches!(h, RollHistory::Bonus(_)))
+        }) {
+            s.push_str(&format!(" bonus={}", self.bonus_total()));
+        }
+        if let Some(reason) = &self.reason {
+            s.push_str(" reason=");
+            s.push_str(&reason.replace(['\r', '\n'], " "));
+        }
+        s
+    }
+}
+
+fn join(values: impl Iterator<Item = i64>) -> String {
+    let values: Vec<String> = values.map(|v| v.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+// Values of the dice counted in the total, fudge dice being read as -1, 0 or 1
+fn summary_dice(roll: &SingleRollResult) -> Vec<i64> {
+    roll.get_history()
+        .iter()
+        .flat_map(|h| match h {
+            RollHistory::Roll(dice) => dice
+                .iter()
+                .filter(|d| !d.dropped)
+                .map(|d| d.res as i64)
+                .collect(),
+            RollHistory::Fudge(dice, config) => dice.iter().map(|&d| config.value(d)).collect(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+type Steps<'a> = std::iter::Peekable<std::vec::IntoIter<&'a RollHistory>>;
+
+// Sum of the constants added to or subtracted from the dice, read from the signs and the
+// parentheses of the history. A constant used in another operation counts for nothing.
+fn summary_modifier(roll: &SingleRollResult) -> i64 {
+    attempts_modifier(&mut roll.get_history().into_iter().peekable()).0
+}
+
+// Modifier of the attempts separated by ` | `, like the ones of `best()`: only the attempt whose
+// dice are kept counts. Also returns if some dice are kept.
+fn attempts_modifier(steps: &mut Steps) -> (i64, bool) {
+    let mut first = None;
+    let mut kept = None;
+    loop {
+        let (modifier, has_dice) = sum_modifier(steps);
+        first.get_or_insert(modifier);
+        if has_dice && kept.is_none() {
+            kept = Some(modifier);
+        }
+        match steps.peek() {
+            Some(RollHistory::Separator(separator::ATTEMPT)) => steps.next(),
+            _ => break,
+        };
+    }
+    (kept.or(first).unwrap_or(0), kept.is_some())
+}
+
+// Modifier of the terms separated by `+` or `-`
+fn sum_modifier(steps: &mut Steps) -> (i64, bool) {
+    let mut sign = 1;
+    let mut modifier = 0i64;
+    let mut has_dice = false;
+    loop {
+        let (term, term_dice) = term_modifier(steps);
+        modifier = modifier.saturating_add(sign * term);
+        has_dice |= term_dice;
+        sign = match steps.peek() {
+            Some(RollHistory::Separator(separator::ADD)) => 1,
+            Some(RollHistory::Separator(separator::SUB)) => -1,
+            _ => break,
+        };
+        steps.next();
+    }
+    (modifier, has_dice)
+}
+
+// Modifier of the operands joined by another operation, like `*` or `^`: nothing is added when
+// there is more than one
+fn term_modifier(steps: &mut Steps) -> (i64, bool) {
+    let (mut modifier, mut has_dice) = operand_modifier(steps);
+    while let Some(RollHistory::Separator(sep)) = steps.peek() {
+        if matches!(*sep, separator::ADD | separator::SUB | separator::ATTEMPT) {
+            break;
+        }
+        steps.next();
+        has_dice |= operand_modifier(steps).1;
+        modifier = 0;
+    }
+    (modifier, has_dice)
+}
+
+fn operand_modifier(steps: &mut Steps) -> (i64, bool) {
+    match steps.next() {
+        Some(RollHistory::Value(v)) => (v.get_value(), false),
+        Some(RollHistory::OpenParenthesis) => {
+            let res = attempts_modifier(steps);
+            steps.next();
+            res
+        }
+        Some(RollHistory::Roll(dice)) => {
+            // the bonus or discarded dice following a roll are part of it
+            while let Some(RollHistory::Bonus(_) | RollHistory::Discarded(_)) = steps.peek() {
+                steps.next();
+            }
+            (0, dice.iter().any(|d| !d.dropped))
+        }
+        Some(_) => (0, true),
+        None => (0, false),
+    }
+}
+
+impl Display for RollResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_with(f, &DisplayConfig::default())
+    }
+}
+
 impl Display for SingleRollResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.to_string(true))?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_support::IteratorDiceRollSource, RollError, Roller};
+
+    fn result(total: i64, reason: Option<&str>) -> RollResult {
+        let mut res = RollResult::new_single(SingleRollResult::with_total(total));
+        if let Some(reason) = reason {
+            res.add_reason(reason.to_owned());
+        }
+        res
+    }
+
+    #[test]
+    fn reason_survives_operand_order_test() {
+        type Op = fn(RollResult, RollResult) -> RollResult;
+        let ops: [(Op, i64, i64); 4] = [
+            (|l, r| l + r, 8, 8),
+            (|l, r| l - r, 4, -4),
+            (|l, r| l * r, 12, 12),
+            (|l, r| l / r, 3, 0),
+        ];
+        for (op, expected, expected_swapped) in ops.iter() {
+            let res = op(result(6, Some("attack")), result(2, None));
+            assert_eq!(*expected, res.as_single().unwrap().get_total());
+            assert_eq!(Some(&"attack".to_owned()), res.get_reason());
+
+            let res = op(result(2, None), result(6, Some("attack")));
+            assert_eq!(*expected_swapped, res.as_single().unwrap().get_total());
+            assert_eq!(Some(&"attack".to_owned()), res.get_reason());
+
+            let res = op(result(6, None), result(2, None));
+            assert_eq!(None, res.get_reason());
+        }
+    }
+
+    #[test]
+    fn reason_concatenation_test() {
+        let res = result(6, Some("attack")) + result(2, Some("bless"));
+        assert_eq!(Some(&"attack, bless".to_owned()), res.get_reason());
+        let res = result(2, Some("bless")) - result(6, Some("attack"));
+        assert_eq!(Some(&"bless, attack".to_owned()), res.get_reason());
+        let res = result(2, Some("bless")) * result(6, Some("bless"));
+        assert_eq!(Some(&"bless".to_owned()), res.get_reason());
+    }
+
+    #[test]
+    fn repeated_accessors_test() {
+        let res = Roller::new("(1d20)^6")
+            .unwrap()
+            .roll_with_values(&[12, 3, 20, 7, 7, 15])
+            .unwrap();
+        let repeated = res.as_repeated().unwrap();
+        assert_eq!(vec![12, 3, 20, 7, 7, 15], repeated.totals());
+        assert_eq!(Some(20), repeated.max());
+        assert_eq!(Some(3), repeated.min());
+        assert_eq!(64, repeated.sum_of_totals().unwrap());
+        assert_eq!(None, repeated.get_total());
+        assert_eq!(
+            "#1: `[12]` = **12**\n\
+             #2: `[3]` = **3**\n\
+             #3: `[20]` = **20**\n\
+             #4: `[7]` = **7**\n\
+             #5: `[7]` = **7**\n\
+             #6: `[15]` = **15**",
+            repeated.to_string()
+        );
+
+        let res = Roller::new("(1d6 + 2)^#3")
+            .unwrap()
+            .roll_with_values(&[4, 1, 6])
+            .unwrap();
+        assert_eq!(
+            "#1: `[1] + 2` = **3**\n#2: `[4] + 2` = **6**\n#3: `[6] + 2` = **8**",
+            res.as_repeated().unwrap().to_string()
+        );
+
+        let res = Roller::new("(1d6 + 9223372036854775806)^2")
+            .unwrap()
+            .roll_with_values(&[1, 1])
+            .unwrap();
+        assert!(res.as_repeated().unwrap().sum_of_totals().is_err());
+    }
+
+    #[test]
+    fn repeated_arithmetic_test() {
+        let repeated = RollResult::new_repeated(
+            vec![
+                SingleRollResult::with_total(3),
+                SingleRollResult::with_total(4),
+            ],
+            None,
+        );
+        let res = repeated + result(2, None);
+        assert_eq!(9, res.as_single().unwrap().get_total());
+        assert_eq!("3 + 4 + 2", res.as_single().unwrap().to_string_history());
+    }
+
+    #[test]
+    fn total_with_test() {
+        let roll_mock = vec![2, 6, 3, 5];
+        let res = Roller::new("4d6")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut roll_mock.into_iter(),
+            })
+            .unwrap();
+        assert_eq!(16, res.total_with(TotalModifier::None).unwrap());
+        assert_eq!(11, res.total_with(TotalModifier::KeepHi(2)).unwrap());
+        assert_eq!(5, res.total_with(TotalModifier::KeepLo(2)).unwrap());
+        assert_eq!(5, res.total_with(TotalModifier::DropHi(2)).unwrap());
+        assert_eq!(14, res.total_with(TotalModifier::DropLo(1)).unwrap());
+        assert_eq!(
+            1,
+            res.total_with(TotalModifier::TargetFailureDouble(5, 2, 0))
+                .unwrap()
+        );
+        // the result itself is untouched
+        assert_eq!(16, res.as_single().unwrap().get_total());
+    }
+
+    #[test]
+    fn total_with_out_of_range_test() {
+        let res = RollResult::new_single(SingleRollResult::with_total(4));
+        assert!(matches!(
+            res.total_with(TotalModifier::KeepHi(2)),
+            Err(RollError::ParamError(_))
+        ));
+        assert!(res.total_with(TotalModifier::DropLo(5)).is_err());
+
+        let repeated = RollResult::new_repeated(vec![SingleRollResult::with_total(4)], None);
+        assert!(repeated.total_with(TotalModifier::None).is_err());
+    }
+
+    #[test]
+    fn max_dice_shown_test() {
+        let values = vec![6, 5, 5, 4, 3, 2, 1, 1];
+        let res = Roller::new("8d6")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut values.into_iter(),
+            })
+            .unwrap();
+        let config = DisplayConfig {
+            max_dice_shown: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            "`[6, 5, 5, ... (8 dice), 1, 1]` = **27**",
+            res.to_string_with(&config)
+        );
+        // the full data is still there
+        match &res.as_single().unwrap().get_history()[0] {
+            RollHistory::Roll(dice) => assert_eq!(8, dice.len()),
+            _ => unreachable!(),
+        }
+
+        // under the threshold, nothing is elided
+        let config = DisplayConfig {
+            max_dice_shown: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(res.to_string(), res.to_string_with(&config));
+        assert_eq!(
+            "`[6, 5, 5, 4, 3, 2, 1, 1]` = **27**",
+            res.to_string_with(&config)
+        );
+    }
+
+    #[test]
+    fn empty_roll_display_test() {
+        let single = SingleRollResult::from_parts(
+            2,
+            vec![
+                RollHistory::Roll(vec![]),
+                RollHistory::Separator(" + "),
+                RollHistory::Value(Value::Int(2)),
+            ],
+        )
+        .unwrap();
+        let res = RollResult::new_single(single);
+        assert_eq!("`[] + 2` = **2**", res.to_string());
+        let config = DisplayConfig {
+            max_dice_shown: Some(0),
+            show_dropped: Some(false),
+            sort_ascending: true,
+            ..Default::default()
+        };
+        assert_eq!("`[] + 2` = **2**", res.to_string_with(&config));
+        assert_eq!(
+            "[]",
+            RollHistory::Fudge(vec![], Default::default()).to_string()
+        );
+    }
+
+    #[test]
+    fn fudge_symbols_test() {
+        let r = Roller::new("4dF + 2dF + 1").unwrap();
+        let res = r.roll_with_values(&[2, 5, 3, 6, 1, 4]).unwrap();
+        assert_eq!(
+            Some(vec!['+', '+', '▢', '-', '▢', '-']),
+            res.fudge_symbols()
+        );
+        assert_eq!(Outcome::Sum(1), res.outcome());
+
+        // the thresholds of the roller are used
+        let res = Roller::new("3dF")
+            .unwrap()
+            .with_fudge_config(FudgeConfig {
+                minus_max: 1,
+                blank_max: 5,
+            })
+            .roll_with_values(&[5, 1, 6])
+            .unwrap();
+        assert_eq!(Some(vec!['+', '▢', '-']), res.fudge_symbols());
+        assert_eq!(Outcome::Fudge(0), res.outcome());
+
+        let res = Roller::new("2d6").unwrap().roll().unwrap();
+        assert_eq!(None, res.fudge_symbols());
+        let res = Roller::new("(1dF)^2").unwrap().roll().unwrap();
+        assert_eq!(None, res.fudge_symbols());
+    }
+
+    #[test]
+    fn highest_lowest_die_test() {
+        let res = Roller::new("3d6 K2 + 1d8 - 2d4 + 3")
+            .unwrap()
+            .roll_with_values(&[5, 1, 6, 8, 2, 3])
+            .unwrap();
+        assert_eq!(Some(8), res.highest_die());
+        // the dropped 1 counts
+        assert_eq!(Some(1), res.lowest_die());
+
+        let res = Roller::new("(1d20 + 2)^3")
+            .unwrap()
+            .roll_with_values(&[12, 20, 7])
+            .unwrap();
+        assert_eq!(Some(20), res.highest_die());
+        assert_eq!(Some(7), res.lowest_die());
+
+        let res = Roller::new("4dF + 2").unwrap().roll().unwrap();
+        assert_eq!(None, res.highest_die());
+        assert_eq!(None, res.lowest_die());
+    }
+
+    #[test]
+    fn matches_test() {
+        let res = Roller::new("6d6")
+            .unwrap()
+            .roll_with_values(&[4, 4, 2, 6, 6, 6])
+            .unwrap();
+        assert_eq!(vec![(6, 3), (4, 2), (2, 1)], res.matches());
+
+        // over all the groups, dropped dice included
+        let res = Roller::new("2d10 K1 + 1d10")
+            .unwrap()
+            .roll_with_values(&[7, 3, 3])
+            .unwrap();
+        assert_eq!(vec![(3, 2), (7, 1)], res.matches());
+        assert!(Roller::new("4dF")
+            .unwrap()
+            .roll()
+            .unwrap()
+            .matches()
+            .is_empty());
+    }
+
+    #[test]
+    fn equivalent_test() {
+        let a = Roller::new("1d6 + 2d6 + 1 : damage")
+            .unwrap()
+            .roll_with_values(&[5, 2, 3])
+            .unwrap();
+        let b = Roller::new("2d6 + 1 + 1d6 : damage")
+            .unwrap()
+            .roll_with_values(&[3, 2, 5])
+            .unwrap();
+        assert_ne!(a, b);
+        assert!(a.equivalent(&b));
+        assert!(b.equivalent(&a));
+
+        // the same dice in one group
+        let c = Roller::new("3d6 + 1 : damage")
+            .unwrap()
+            .roll_with_values(&[2, 5, 3])
+            .unwrap();
+        assert!(a.equivalent(&c));
+
+        // built from a history in another order
+        let single = SingleRollResult::from_parts(
+            11,
+            vec![
+                RollHistory::roll(&[2, 3], 6),
+                RollHistory::Separator(" + "),
+                RollHistory::roll(&[5], 6),
+                RollHistory::Separator(" + "),
+                RollHistory::Value(Value::Int(1)),
+            ],
+        )
+        .unwrap();
+        let mut d = RollResult::new_single(single);
+        assert!(!a.equivalent(&d));
+        d.add_reason("damage".to_owned());
+        assert!(a.equivalent(&d));
+
+        // other dice, total or reason
+        let other_dice = Roller::new("3d6 + 1 : damage")
+            .unwrap()
+            .roll_with_values(&[1, 6, 3])
+            .unwrap();
+        assert!(!a.equivalent(&other_dice));
+        let other_total = Roller::new("3d6 + 2 : damage")
+            .unwrap()
+            .roll_with_values(&[2, 5, 3])
+            .unwrap();
+        assert!(!a.equivalent(&other_total));
+        let other_reason = Roller::new("3d6 + 1 : heal")
+            .unwrap()
+            .roll_with_values(&[2, 5, 3])
+            .unwrap();
+        assert!(!a.equivalent(&other_reason));
+    }
+
+    #[test]
+    fn scale_test() {
+        let res = Roller::new("2d6 : crit")
+            .unwrap()
+            .roll_with_values(&[3, 4])
+            .unwrap();
+        let scaled = |rounding| res.clone().scale(1.5, rounding);
+
+        let floor = scaled(Rounding::Floor);
+        assert_eq!(10, floor.as_single().unwrap().get_total());
+        assert_eq!(Outcome::Sum(10), floor.outcome());
+        assert_eq!("[4, 3] * 1.5", floor.history_string());
+        assert_eq!(Some("crit"), floor.get_reason().map(String::as_str));
+
+        assert_eq!(11, scaled(Rounding::Round).as_single().unwrap().get_total());
+        assert_eq!(11, scaled(Rounding::Ceil).as_single().unwrap().get_total());
+        assert_eq!(10, scaled(Rounding::Trunc).as_single().unwrap().get_total());
+
+        // rounding goes the same way for a negative total
+        let res = result(-7, None).scale(1.5, Rounding::Floor);
+        assert_eq!(-11, res.as_single().unwrap().get_total());
+        let res = result(-7, None).scale(1.5, Rounding::Round);
+        assert_eq!(-11, res.as_single().unwrap().get_total());
+        let res = result(-7, None).scale(1.5, Rounding::Trunc);
+        assert_eq!(-10, res.as_single().unwrap().get_total());
+
+        // the whole expression is scaled
+        let res = Roller::new("2d6 + 1")
+            .unwrap()
+            .roll_with_values(&[3, 4])
+            .unwrap()
+            .scale(1.5, Rounding::Floor);
+        assert_eq!(12, res.as_single().unwrap().get_total());
+        assert_eq!("([4, 3] + 1) * 1.5", res.history_string());
+    }
+
+    #[test]
+    fn meets_test() {
+        let res = Roller::new("2d6 + 2")
+            .unwrap()
+            .roll_with_values(&[3, 5])
+            .unwrap();
+        let cases = [
+            (Comparator::Ge, [(9, true), (10, true), (11, false)]),
+            (Comparator::Gt, [(9, true), (10, false), (11, false)]),
+            (Comparator::Le, [(9, false), (10, true), (11, true)]),
+            (Comparator::Lt, [(9, false), (10, false), (11, true)]),
+            (Comparator::Eq, [(9, false), (10, true), (11, false)]),
+        ];
+        for (comparator, checks) in cases.iter() {
+            for (dc, expected) in checks.iter() {
+                assert_eq!(
+                    *expected,
+                    res.meets(*dc, *comparator),
+                    "10 {} {}",
+                    comparator,
+                    dc
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sum_test() {
+        let roll = |input: &str, values: &[u64]| {
+            Roller::new(input)
+                .unwrap()
+                .roll_with_values(values)
+                .unwrap()
+        };
+        let results = vec![
+            roll("2d6 : fire", &[3, 5]),
+            roll("1d4 + 1", &[2]),
+            roll("1d8 : cold", &[7]),
+        ];
+        let chained = results[0].clone() + results[1].clone() + results[2].clone();
+        let by_ref: RollResult = results.iter().sum();
+        let sum: RollResult = results.into_iter().sum();
+        assert_eq!(chained, sum);
+        assert_eq!(chained, by_ref);
+        assert_eq!(18, sum.as_single().unwrap().get_total());
+        assert_eq!("[5, 3] + [2] + 1 + [7]", sum.history_string());
+        assert_eq!(Some("fire, cold"), sum.get_reason().map(String::as_str));
+
+        let empty: RollResult = std::iter::empty::<RollResult>().sum();
+        assert_eq!(RollResult::new(), empty);
+    }
+
+    #[test]
+    fn history_string_test() {
+        let res = Roller::new("4d6kh3 + 2 : stat")
+            .unwrap()
+            .roll_with_values(&[2, 6, 3, 4])
+            .unwrap();
+        assert_eq!("[6, 4, 3, 2] + 2", res.history_string());
+        assert_eq!(
+            format!("`{}` = **15**, Reason: `stat`", res.history_string()),
+            res.to_string()
+        );
+
+        let marked = DisplayConfig {
+            show_dropped: Some(true),
+            ..Default::default()
+        };
+        assert_eq!("[6, 4, 3, ~~2~~] + 2", res.history_string_with(&marked));
+        assert_eq!("[6, 4, 3, 2] + 2", res.history_string_plain_with(&marked));
+        assert_eq!(res.history_string(), res.history_string_plain());
+        assert_eq!(
+            format!(
+                "`{}` = **15**, Reason: `stat`",
+                res.history_string_with(&marked)
+            ),
+            res.to_string_with(&marked)
+        );
+
+        let res = Roller::new("(1d6 + 1) ^+ 2")
+            .unwrap()
+            .roll_with_values(&[3, 5])
+            .unwrap();
+        assert_eq!("[3] + 1\n[5] + 1", res.history_string());
+        assert_eq!("`[3] + 1`\n`[5] + 1`\nSum: **10**", res.to_string());
+    }
+
+    #[test]
+    fn show_dropped_test() {
+        let roll = |input: &str, values: Vec<u64>| {
+            Roller::new(input)
+                .unwrap()
+                .roll_with_source(&mut IteratorDiceRollSource {
+                    iterator: &mut values.into_iter(),
+                })
+                .unwrap()
+        };
+        let marked = DisplayConfig {
+            show_dropped: Some(true),
+            ..Default::default()
+        };
+        let hidden = DisplayConfig {
+            show_dropped: Some(false),
+            ..Default::default()
+        };
+
+        let res = roll("4d6kh3", vec![2, 6, 3, 4]);
+        assert_eq!("`[6, 4, 3, 2]` = **13**", res.to_string());
+        assert_eq!("`[6, 4, 3, ~~2~~]` = **13**", res.to_string_with(&marked));
+        assert_eq!("`[6, 4, 3]` = **13**", res.to_string_with(&hidden));
+        match &res.as_single().unwrap().get_history()[0] {
+            RollHistory::Roll(dice) => {
+                assert_eq!(
+                    vec![false, false, false, true],
+                    dice.iter().map(|d| d.dropped).collect::<Vec<_>>()
+                )
+            }
+            _ => unreachable!(),
+        }
+
+        // equal values: the lowest shown ones are dropped
+        let res = roll("4d6kh2 + 2d6 dl1", vec![4, 2, 6, 4, 3, 5]);
+        assert_eq!(
+            "`[6, 4, ~~4~~, ~~2~~] + [5, ~~3~~]` = **15**",
+            res.to_string_with(&marked)
+        );
+        assert_eq!(
+            "`[~~6~~, ~~4~~, 4, 2]` = **6**",
+            roll("4d6 kl2", vec![4, 2, 6, 4]).to_string_with(&marked)
+        );
+        assert_eq!(
+            "`[~~4~~, 2, ~~6~~, 4]` = **6**",
+            roll("4d6 k[2, 4]", vec![4, 2, 6, 4]).to_string_with(&marked)
+        );
+        assert_eq!(
+            "`[6, 4]` = **10**",
+            roll("3d6 dh1", vec![4, 6, 6]).to_string_with(&hidden)
+        );
+        // without keep or drop, nothing is dropped
+        assert_eq!(
+            "`[6, 4, 1]` = **11**",
+            roll("3d6", vec![4, 6, 1]).to_string_with(&hidden)
+        );
+    }
+
+    #[test]
+    fn operator_symbols_test() {
+        let res = Roller::new("2d6 * 2 / (1 + 1d4 - 1)")
+            .unwrap()
+            .roll_with_values(&[5, 2, 3])
+            .unwrap();
+        let config = DisplayConfig {
+            operator_symbols: [
+                None,
+                Some("−".to_owned()),
+                Some("×".to_owned()),
+                Some("÷".to_owned()),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            "`[5, 2] × 2 ÷ (1 + [3] − 1)` = **4**",
+            res.to_string_with(&config)
+        );
+        assert_eq!("`[5, 2] * 2 / (1 + [3] - 1)` = **4**", res.to_string());
+        // the stored separator is untouched
+        assert!(res
+            .as_single()
+            .unwrap()
+            .get_history()
+            .iter()
+            .any(|h| matches!(h, RollHistory::Separator(" * "))));
+    }
+
+    #[test]
+    fn explosion_marker_test() {
+        let config = DisplayConfig {
+            explosion_marker: Some("!".to_owned()),
+            ..Default::default()
+        };
+        let res = Roller::new("2d6 e6")
+            .unwrap()
+            .roll_with_values(&[4, 6, 6])
+            .unwrap();
+        assert_eq!("`[6!, 4][6]` = **16**", res.to_string_with(&config));
+        assert_eq!("`[6, 4][6]` = **16**", res.to_string());
+
+        let res = Roller::new("1d6 ie")
+            .unwrap()
+            .roll_with_values(&[6, 6, 3])
+            .unwrap();
+        assert_eq!("`[6!][6!][3]` = **15**", res.to_string_with(&config));
+        match &res.as_single().unwrap().get_history()[2] {
+            RollHistory::Roll(dice) => assert!(!dice[0].exploded),
+            _ => unreachable!(),
+        }
+
+        let config = DisplayConfig {
+            explosion_marker: Some("💥".to_owned()),
+            ..Default::default()
+        };
+        let res = Roller::new("3d10 !9b")
+            .unwrap()
+            .roll_with_values(&[9, 2, 10, 10, 1, 4])
+            .unwrap();
+        assert_eq!(
+            "`[10💥, 9💥, 2] (bonus [10💥, 1]) (bonus [4])` = **21**",
+            res.to_string_with(&config)
+        );
+    }
+
+    #[test]
+    fn target_symbols_test() {
+        let config = DisplayConfig {
+            target_symbols: Some(("✓".to_owned(), "✗".to_owned())),
+            ..Default::default()
+        };
+        let res = Roller::new("4d10 t8f2")
+            .unwrap()
+            .roll_with_values(&[8, 9, 2, 5])
+            .unwrap();
+        assert_eq!("`[9✓, 8✓, 5, 2✗]` = **1**", res.to_string_with(&config));
+        assert_eq!("`[9, 8, 5, 2]` = **1**", res.to_string());
+
+        let res = Roller::new("3d10 t7 tt10 + 2d6>=5")
+            .unwrap()
+            .roll_with_values(&[10, 7, 3, 6, 1])
+            .unwrap();
+        assert_eq!(
+            "`[10✓✓, 7✓, 3] + [6✓, 1]` = **4**",
+            res.to_string_with(&config)
+        );
+
+        let res = Roller::new("4d10>=7b")
+            .unwrap()
+            .roll_with_values(&[8, 1, 1, 4])
+            .unwrap();
+        assert_eq!("`[8✓, 4, 1✗, 1✗]` = **0**", res.to_string_with(&config));
+    }
+
+    #[test]
+    fn show_thresholds_test() {
+        let config = DisplayConfig {
+            show_thresholds: true,
+            ..Default::default()
+        };
+        let res = Roller::new("4d10 t7f2 : attack")
+            .unwrap()
+            .roll_with_values(&[8, 9, 2, 5])
+            .unwrap();
+        assert_eq!(
+            "`[9, 8, 5, 2]` = **1** (target 7, failure 2), Reason: `attack`",
+            res.to_string_with(&config)
+        );
+        assert_eq!("`[9, 8, 5, 2]` = **1**, Reason: `attack`", res.to_string());
+
+        let cases = [
+            ("3d10 t8 tt10", "target 8, double 10"),
+            ("3d10 t[2,4]", "target [2, 4]"),
+            ("3d10>=7", "target >=7"),
+            ("3d10>=7b", "target >=7, botch 1"),
+        ];
+        for (input, thresholds) in cases.iter() {
+            let res = Roller::new(input)
+                .unwrap()
+                .roll_with_values(&[10, 4, 1])
+                .unwrap();
+            assert!(
+                res.to_string_with(&config)
+                    .ends_with(&format!(" ({})", thresholds)),
+                "{}",
+                input
+            );
+        }
+
+        // only for a roll counting successes
+        let res = Roller::new("2d6 K1")
+            .unwrap()
+            .roll_with_values(&[3, 5])
+            .unwrap();
+        assert_eq!("`[5, 3]` = **5**", res.to_string_with(&config));
+
+        // a merged result is a sum, even when a part counted successes
+        let res = Roller::new("3d10 t7")
+            .unwrap()
+            .roll_with_values(&[8, 2, 9])
+            .unwrap()
+            .merge(Roller::new("1d4").unwrap().roll_with_values(&[3]).unwrap());
+        assert_eq!("`[9, 8, 2][3]` = **5**", res.to_string_with(&config));
+    }
+
+    #[test]
+    fn sort_ascending_test() {
+        let config = DisplayConfig {
+            sort_ascending: true,
+            ..Default::default()
+        };
+        let res = Roller::new("3d6 + 2d6 e6b")
+            .unwrap()
+            .roll_with_values(&[6, 2, 4, 6, 1, 3])
+            .unwrap();
+        assert_eq!(
+            "`[2, 4, 6] + [1, 6] (bonus [3])` = **19**",
+            res.to_string_with(&config)
+        );
+        assert_eq!("`[6, 4, 2] + [6, 1] (bonus [3])` = **19**", res.to_string());
+
+        let res = Roller::new("4dF")
+            .unwrap()
+            .roll_with_values(&[6, 1, 3, 5])
+            .unwrap();
+        assert_eq!("`[-, ▢, +, +]` = **1**", res.to_string_with(&config));
+
+        let config = DisplayConfig {
+            sort_ascending: true,
+            show_dropped: Some(true),
+            ..Default::default()
+        };
+        let res = Roller::new("4d6 K3")
+            .unwrap()
+            .roll_with_values(&[6, 2, 4, 2])
+            .unwrap();
+        assert_eq!("`[~~2~~, 2, 4, 6]` = **12**", res.to_string_with(&config));
+    }
+
+    #[test]
+    fn reason_escaping_test() {
+        let r = Roller::new("1d6 : my `sword` **of doom**").unwrap();
+        let res = r.roll_with_values(&[4]).unwrap();
+        assert_eq!(
+            "`[4]` = **4**, Reason: `` my `sword` **of doom** ``",
+            res.to_string()
+        );
+        let config = DisplayConfig {
+            raw_reason: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            "`[4]` = **4**, Reason: `my `sword` **of doom**`",
+            res.to_string_with(&config)
+        );
+
+        // the fence is longer than any run of backticks
+        let res = Roller::new("(1d6)^+2 : ``a``")
+            .unwrap()
+            .roll_with_values(&[4, 2])
+            .unwrap();
+        assert_eq!(
+            "`[4]`\n`[2]`\nSum: **6**, Reason: ``` ``a`` ```",
+            res.to_string()
+        );
+        let res = Roller::new("1d6 : no markdown")
+            .unwrap()
+            .roll_with_values(&[4])
+            .unwrap();
+        assert_eq!("`[4]` = **4**, Reason: `no markdown`", res.to_string());
+    }
+
+    #[test]
+    fn is_empty_test() {
+        let res = Roller::new("5").unwrap().roll().unwrap();
+        assert!(res.is_empty());
+        let res = Roller::new("0").unwrap().roll().unwrap();
+        assert!(res.is_empty());
+        assert_eq!(0, res.as_single().unwrap().get_total());
+
+        let res = Roller::new("3d6 t6")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![1, 2, 3].into_iter(),
+            })
+            .unwrap();
+        assert!(!res.is_empty());
+        assert_eq!(0, res.as_single().unwrap().get_total());
+
+        let res = Roller::new("1d6 + 2")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![4].into_iter(),
+            })
+            .unwrap();
+        assert!(!res.is_empty());
+    }
+
+    #[test]
+    fn is_deterministic_test() {
+        let res = Roller::new("5").unwrap().roll().unwrap();
+        assert!(res.is_deterministic());
+        let res = Roller::new("(2 + 3) * 1.5").unwrap().roll().unwrap();
+        assert!(res.is_deterministic());
+
+        let res = Roller::new("1d6").unwrap().roll_with_values(&[4]).unwrap();
+        assert!(!res.is_deterministic());
+        let res = Roller::new("1d6 + 3")
+            .unwrap()
+            .roll_with_values(&[4])
+            .unwrap();
+        assert!(!res.is_deterministic());
+        let res = Roller::new("4dF").unwrap().roll().unwrap();
+        assert!(!res.is_deterministic());
+
+        let res = Roller::new("(5)^+2").unwrap().roll().unwrap();
+        assert!(res.is_deterministic());
+
+        // named faces are rolled, but they don't count in the total
+        let r = Roller::new("2d{story}")
+            .unwrap()
+            .with_die("story", &["Hope", "Fear"])
+            .unwrap();
+        let res = r.roll_with_values(&[1, 2]).unwrap();
+        assert!(res.is_empty());
+        assert!(!res.is_deterministic());
+    }
+
+    #[test]
+    fn margin_test() {
+        let roll = |value| {
+            Roller::new("1d20")
+                .unwrap()
+                .roll_with_values(&[value])
+                .unwrap()
+        };
+        let above = roll(17);
+        assert_eq!(7, above.margin(10));
+        assert_eq!(1, above.degrees(10, 5));
+        let at = roll(10);
+        assert_eq!(0, at.margin(10));
+        assert_eq!(0, at.degrees(10, 5));
+        let below = roll(4);
+        assert_eq!(-6, below.margin(10));
+        assert_eq!(-2, below.degrees(10, 5));
+        assert_eq!(-1, roll(9).degrees(10, 5));
+
+        let repeated = Roller::new("(1d20) ^ 2")
+            .unwrap()
+            .roll_with_values(&[8, 9])
+            .unwrap();
+        assert_eq!(2, repeated.margin(15));
+    }
+
+    #[test]
+    fn merge_test() {
+        let lhs = Roller::new("3d6 K2 : attack")
+            .unwrap()
+            .roll_with_values(&[2, 5, 4])
+            .unwrap();
+        let rhs = Roller::new("1d4").unwrap().roll_with_values(&[3]).unwrap();
+        let res = lhs.merge(rhs);
+        let single = res.as_single().unwrap();
+        assert_eq!(12, single.get_total());
+        assert_eq!("[5, 4, 2][3]", single.to_string_history());
+        assert!(!single
+            .get_history()
+            .iter()
+            .any(|h| matches!(h, RollHistory::Separator(_))));
+        assert_eq!(Some(&"attack".to_owned()), res.get_reason());
+    }
+
+    #[test]
+    fn group_ranges_test() {
+        let res = Roller::new("2d6 + 1d8")
+            .unwrap()
+            .roll_with_values(&[3, 5, 7])
+            .unwrap();
+        let ranges = res.group_ranges();
+        assert_eq!(vec![0..1, 2..3], ranges);
+        let history = res.as_single().unwrap().get_history();
+        assert_eq!("[5, 3]", history[ranges[0].clone()][0].to_string());
+        assert_eq!("[7]", history[ranges[1].clone()][0].to_string());
+
+        let res = Roller::new("(1d6 + 2) * 3")
+            .unwrap()
+            .roll_with_values(&[4])
+            .unwrap();
+        assert_eq!(vec![0..2, 3..5, 6..7], res.group_ranges());
+
+        let res = Roller::new("(1d6) ^ 2")
+            .unwrap()
+            .roll_with_values(&[4, 2])
+            .unwrap();
+        assert!(res.group_ranges().is_empty());
+    }
+
+    #[test]
+    fn summary_test() {
+        let res = Roller::new("2d6 : hello")
+            .unwrap()
+            .roll_with_values(&[3, 4])
+            .unwrap();
+        assert_eq!("total=7 dice=[4,3] mod=+0 reason=hello", res.summary());
+
+        let res = Roller::new("1d20 - 2 + 1d4 * 2")
+            .unwrap()
+            .roll_with_values(&[12, 3])
+            .unwrap();
+        assert_eq!("total=16 dice=[12,3] mod=-2", res.summary());
+
+        let res = Roller::new("(1d6 + 1) ^+ 2 : two\nlines")
+            .unwrap()
+            .roll_with_values(&[2, 5])
+            .unwrap();
+        assert_eq!(
+            "total=9 rolls=[3,6] dice=[2,5] mod=+2 reason=two lines",
+            res.summary()
+        );
+
+        let res = Roller::new("3d6 !b")
+            .unwrap()
+            .roll_with_values(&[6, 2, 1, 4])
+            .unwrap();
+        assert_eq!("total=9 dice=[6,2,1] mod=+0 bonus=4", res.summary());
+
+        // the dropped dice are left out
+        let res = Roller::new("4d6K3 + 2")
+            .unwrap()
+            .roll_with_values(&[6, 5, 1, 4])
+            .unwrap();
+        assert_eq!("total=17 dice=[6,5,4] mod=+2", res.summary());
+
+        // the signs and the parentheses are read
+        let res = Roller::new("1d4 + 2 - (1 + 3)")
+            .unwrap()
+            .roll_with_values(&[1])
+            .unwrap();
+        assert_eq!("total=-1 dice=[1] mod=-2", res.summary());
+        let res = Roller::new("1d6 + 2 * 3 - 1")
+            .unwrap()
+            .roll_with_values(&[6])
+            .unwrap();
+        assert_eq!("total=11 dice=[6] mod=-1", res.summary());
+
+        // only the kept attempt counts
+        let res = Roller::new("best(3, 1d20+5)")
+            .unwrap()
+            .roll_with_values(&[6, 5, 1])
+            .unwrap();
+        assert_eq!("total=11 dice=[6] mod=+5", res.summary());
+    }
+
+    #[test]
+    fn add_ref_test() {
+        let attack = Roller::new("1d20 + 5 : attack")
+            .unwrap()
+            .roll_with_values(&[12])
+            .unwrap();
+        let damage = Roller::new("2d6 : damage")
+            .unwrap()
+            .roll_seeded(42)
+            .unwrap();
+        let damage_total = damage.as_single().unwrap().get_total();
+        let res = attack.add_ref(&damage);
+        assert_eq!(17 + damage_total, res.as_single().unwrap().get_total());
+        assert_eq!(Some(&"attack, damage".to_owned()), res.get_reason());
+        assert_eq!(
+            format!(
+                "[12] + 5 + {}",
+                damage.as_single().unwrap().to_string_history()
+            ),
+            res.as_single().unwrap().to_string_history()
+        );
+
+        assert_eq!(17, attack.as_single().unwrap().get_total());
+        assert_eq!("[12] + 5", attack.as_single().unwrap().to_string_history());
+        assert_eq!(Some(&"attack".to_owned()), attack.get_reason());
+        assert_eq!(damage_total, damage.as_single().unwrap().get_total());
+        assert_eq!(Some(&"damage".to_owned()), damage.get_reason());
+    }
+
+    #[test]
+    fn reason_survives_modifiers_test() {
+        let res = Roller::new("4d6 : stats")
+            .unwrap()
+            .roll_with_values(&[3, 6, 2, 5])
+            .unwrap();
+        let modifiers = vec![
+            TotalModifier::KeepHi(3),
+            TotalModifier::KeepLo(2),
+            TotalModifier::DropHi(1),
+            TotalModifier::DropLo(1),
+            TotalModifier::KeepIndices(vec![1, 3]),
+            TotalModifier::TargetFailureDouble(5, 1, 6),
+            TotalModifier::TargetEnum(vec![2, 4, 6]),
+            TotalModifier::Compare(Comparator::Ge, 4),
+            TotalModifier::Fudge(FudgeConfig::default()),
+            TotalModifier::None,
+        ];
+        for modifier in modifiers {
+            res.total_with(modifier.clone()).unwrap();
+            assert_eq!(
+                Some(&"stats".to_owned()),
+                res.get_reason(),
+                "{:?}",
+                modifier
+            );
+        }
+        assert_eq!(16, res.as_single().unwrap().get_total());
+
+        for input in &[
+            "4d6 K3 : stats",
+            "6d10 t7 f1 : stats",
+            "1d100<=65 : stats",
+            "4dF : stats",
+        ] {
+            let res = Roller::new(input).unwrap().roll().unwrap();
+            assert_eq!(Some(&"stats".to_owned()), res.get_reason(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn from_parts_test() {
+        let history = vec![
+            RollHistory::OpenParenthesis,
+            RollHistory::Roll(vec![DiceResult::new(6, 6), DiceResult::new(1, 6)]),
+            RollHistory::Bonus(vec![DiceResult::new(4, 6)]),
+            RollHistory::Separator(" + "),
+            RollHistory::Value(Value::Int(2)),
+            RollHistory::CloseParenthesis,
+            RollHistory::Separator(" * "),
+            RollHistory::Value(Value::Int(2)),
+        ];
+        let res = RollResult::from_parts(18, history, Some("test".to_owned())).unwrap();
+        assert_eq!(
+            "`([6, 1] (bonus [4]) + 2) * 2` = **18**, Reason: `test`",
+            res.to_string()
+        );
+        assert_eq!(4, res.bonus_total());
+        assert_eq!(
+            11,
+            res.total_with(TotalModifier::None).unwrap(),
+            "the history is kept as is"
+        );
+
+        assert_eq!("`0`", RollResult::new().to_string());
+        assert!(RollResult::default().is_empty());
+
+        let invalid = vec![
+            vec![RollHistory::Separator(" + ")],
+            vec![
+                RollHistory::Value(Value::Int(2)),
+                RollHistory::Separator(" + "),
+            ],
+            vec![
+                RollHistory::Value(Value::Int(2)),
+                RollHistory::Value(Value::Int(2)),
+            ],
+            vec![
+                RollHistory::OpenParenthesis,
+                RollHistory::Value(Value::Int(2)),
+            ],
+            vec![RollHistory::CloseParenthesis],
+            vec![RollHistory::Bonus(vec![DiceResult::new(4, 6)])],
+            vec![RollHistory::Roll(vec![DiceResult::new(0, 6)])],
+        ];
+        for history in invalid {
+            assert!(
+                RollResult::from_parts(0, history.clone(), None).is_err(),
+                "{:?}",
+                history
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "division by zero")]
+    fn divide_by_zero_test() {
+        let _ = Roller::new("1d6").unwrap().roll().unwrap() / 0;
+    }
+
+    #[test]
+    fn integer_ops_test() {
+        let roll = || {
+            Roller::new("2d6 : attack")
+                .unwrap()
+                .roll_with_source(&mut IteratorDiceRollSource {
+                    iterator: &mut vec![5, 4].into_iter(),
+                })
+                .unwrap()
+        };
+
+        let res = roll() + 2;
+        assert_eq!("`[5, 4] + 2` = **11**, Reason: `attack`", res.to_string());
+        let res = roll() - 2;
+        assert_eq!("`[5, 4] - 2` = **7**, Reason: `attack`", res.to_string());
+        let res = roll() * 2;
+        assert_eq!("`[5, 4] * 2` = **18**, Reason: `attack`", res.to_string());
+        let res = roll() / 2;
+        assert_eq!("`[5, 4] / 2` = **4**, Reason: `attack`", res.to_string());
+
+        // the total is kept when chaining, even after options changing the total
+        let res = Roller::new("3d6 t5")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![6, 5, 1].into_iter(),
+            })
+            .unwrap();
+        let res = res + 1 - 3;
+        assert_eq!("`[6, 5, 1] + 1 - 3` = **0**", res.to_string());
+        assert_eq!(0, res.as_single().unwrap().get_total());
+
+        // a repeated roll is counted as the sum of its rolls
+        let res = Roller::new("(1d6) ^ 2")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![3, 4].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(17, (res + 10).as_single().unwrap().get_total());
+    }
+
+    #[test]
+    fn history_constructors_test() {
+        let values = [3, 6, 1, 3];
+        let res = Roller::new("4d6")
+            .unwrap()
+            .roll_with_values(&values)
+            .unwrap();
+        let rolled = &res.as_single().unwrap().get_history()[0];
+        let built = RollHistory::roll(&values, 6);
+        assert_eq!(rolled.to_string(), built.to_string());
+        match (rolled, &built) {
+            (RollHistory::Roll(rolled), RollHistory::Roll(built)) => {
+                let crits =
+                    |dice: &Vec<DiceResult>| dice.iter().map(|d| d.crit).collect::<Vec<_>>();
+                assert_eq!(crits(rolled), crits(built));
+            }
+            _ => unreachable!(),
+        }
+
+        let config = FudgeConfig::default();
+        let values = [2, 5, 3, 6];
+        let res = Roller::new("4dF")
+            .unwrap()
+            .roll_with_values(&values)
+            .unwrap();
+        match (
+            &res.as_single().unwrap().get_history()[0],
+            RollHistory::fudge(&values, config),
+        ) {
+            (RollHistory::Fudge(rolled, _), RollHistory::Fudge(built, built_config)) => {
+                assert_eq!(rolled, &built);
+                assert_eq!(config, built_config);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn explosion_count_test() {
+        let res = Roller::new("1d6 ie")
+            .unwrap()
+            .roll_with_values(&[6, 6, 3])
+            .unwrap();
+        assert_eq!(2, res.explosion_count());
+        assert_eq!("`[6][6][3]` = **15**", res.to_string());
+
+        let res = Roller::new("3d6 e5b + 1d6 !")
+            .unwrap()
+            .roll_with_values(&[5, 6, 1, 6, 2, 6, 6, 1])
+            .unwrap();
+        assert_eq!(4, res.explosion_count());
+
+        let res = Roller::new("(2d6 !) ^ 2")
+            .unwrap()
+            .roll_with_values(&[6, 1, 2, 2, 3])
+            .unwrap();
+        assert_eq!(1, res.explosion_count());
+
+        let res = Roller::new("3d6")
+            .unwrap()
+            .roll_with_values(&[6, 6, 6])
+            .unwrap();
+        assert_eq!(0, res.explosion_count());
+    }
+
+    #[test]
+    fn hash_test() {
+        let roll = |input: &str, values: &[u64]| {
+            Roller::new(input)
+                .unwrap()
+                .roll_with_values(values)
+                .unwrap()
+        };
+        let mut set = std::collections::HashSet::new();
+        assert!(set.insert(roll("2d6 + 1.5 : test", &[3, 4])));
+        assert!(!set.insert(roll("2d6 + 1.5 : test", &[4, 3])));
+        // another reason, total, history or constant
+        assert!(set.insert(roll("2d6 + 1.5 : other", &[3, 4])));
+        assert!(set.insert(roll("2d6 + 1.5 : test", &[3, 5])));
+        assert!(set.insert(roll("2d6 + 1.5 : test", &[2, 5])));
+        assert!(set.insert(roll("2d6 + 1.6 : test", &[3, 4])));
+        assert!(set.insert(roll("2d6 t4 : test", &[3, 4])));
+        assert_eq!(6, set.len());
+
+        assert!(set.insert(roll("(1d6) ^ 2", &[1, 2])));
+        assert!(!set.insert(roll("(1d6) ^ 2", &[1, 2])));
+        assert!(set.insert(roll("(1d6) ^ 2", &[2, 1])));
+        assert!(set.insert(roll("(1d6) ^+ 2", &[1, 2])));
+        assert_eq!(9, set.len());
+    }
+
+    #[test]
+    fn spans_test() {
+        let spans = |input: &str, values: &[u64]| {
+            let res = Roller::new(input)
+                .unwrap()
+                .with_spans(true)
+                .roll_with_values(values)
+                .unwrap();
+            res.as_single()
+                .unwrap()
+                .get_history_views()
+                .iter()
+                .map(|v| v.span().map(|s| input[s].to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ["2d6", "+", "3"]
+                .iter()
+                .map(|s| Some(s.to_string()))
+                .collect::<Vec<_>>(),
+            spans("2d6 + 3", &[4, 5])
+        );
+        assert_eq!(
+            ["(", "1d4 e4", "1d4 e4", "+", "2", ")", "*", "1.5"]
+                .iter()
+                .map(|s| Some(s.to_string()))
+                .collect::<Vec<_>>(),
+            spans("(1d4 e4  + 2) * 1.5 : reason", &[4, 1])
+        );
+        // the separator between the rolls of `best` isn't written
+        let best = ["best(", "1d6", "+", "1", "", "1d6", "+", "1", ")"];
+        assert_eq!(
+            best.iter()
+                .map(|s| Some(s.to_string()).filter(|s| !s.is_empty()))
+                .collect::<Vec<_>>(),
+            spans("best(2, 1d6 + 1)", &[2, 5])
+        );
+
+        let res = Roller::new("2d6 + 3")
+            .unwrap()
+            .roll_with_values(&[4, 5])
+            .unwrap();
+        let views = res.as_single().unwrap().get_history_views();
+        assert_eq!(3, views.len());
+        assert!(views.iter().all(|v| v.span().is_none()));
+        assert!(matches!(views[1].history(), RollHistory::Separator(" + ")));
+    }
+}
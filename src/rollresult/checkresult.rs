@@ -0,0 +1,11 @@
+/// Resolution of a check against a difficulty, see [`crate::Roller::resolve_check()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CheckResult {
+    /// Total of the roll
+    pub total: i64,
+    /// Whether the total meets the difficulty
+    pub success: bool,
+    /// Degrees of the result, counted like [`super::RollResult::degrees()`]: 0 for a plain
+    /// success, positive for better successes and negative for failures
+    pub degrees: i64,
+}
@@ -18,6 +18,13 @@ pub struct DiceResult {
     pub res: u64,
     /// If the result was remarkable (critic)
     pub crit: Critic,
+    /// If the die was dropped by a keep or drop option, it doesn't count in the total
+    pub dropped: bool,
+    /// If the die triggered an explosion
+    pub exploded: bool,
+    /// Successes counted by the die when the roll counts successes: 2 for a double success, 1
+    /// for a success, -1 for a failure or a botch, 0 otherwise
+    pub successes: i8,
 }
 
 impl DiceResult {
@@ -35,6 +42,9 @@ impl DiceResult {
             } else {
                 Critic::No
             },
+            dropped: false,
+            exploded: false,
+            successes: 0,
         }
     }
 }
@@ -47,6 +57,13 @@ impl PartialEq for DiceResult {
 
 impl Eq for DiceResult {}
 
+// consistent with `PartialEq`, only the value counts
+impl std::hash::Hash for DiceResult {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.res.hash(state);
+    }
+}
+
 impl PartialOrd for DiceResult {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -0,0 +1,104 @@
+/// Options to customize how a roll result is turned into text.
+///
+/// The default configuration is the one used by the `Display` implementations.
+///
+/// ```
+/// use caith::{DisplayConfig, Roller};
+///
+/// let config = DisplayConfig {
+///     max_dice_shown: Some(10),
+///     ..Default::default()
+/// };
+/// let res = Roller::new("500d6").unwrap().roll().unwrap();
+/// println!("{}", res.to_string_with(&config));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DisplayConfig {
+    /// Maximum number of dice shown for each roll group. Beyond it, only the first and last dice
+    /// of the group are shown, around the number of dice in the group: `[6, 5, ... (500 dice), 1]`.
+    ///
+    /// `None` shows all the dice. The full data stays available with
+    /// [`super::SingleRollResult::get_history()`].
+    pub max_dice_shown: Option<usize>,
+    /// How the dice dropped by a keep or drop option are shown: `Some(true)` marks them as struck
+    /// through, `[6, 4, 3, ~~2~~]`, `Some(false)` omits them, `[6, 4, 3]`.
+    ///
+    /// `None` shows them like the kept dice. Whether a die was dropped is available in
+    /// [`super::DiceResult::dropped`].
+    pub show_dropped: Option<bool>,
+    /// Symbols shown for the `+`, `-`, `*` and `/` operators, in this order, like `×` and `÷`
+    /// for `*` and `/`. `None` shows the operator as written. The history keeps the operator as
+    /// written in [`super::RollHistory::Separator`].
+    pub operator_symbols: [Option<String>; 4],
+    /// Show the dice of each roll group from the lowest to the highest, `[2, 4, 6]`, instead of
+    /// the highest first. Only the display is changed, the history keeps its order. The dice kept
+    /// by position (`k[1,3]`), shown in roll order, are sorted too.
+    pub sort_ascending: bool,
+    /// Write the reason as is between backticks. By default, a reason containing backticks is put
+    /// between longer backtick fences, so it can't break out of the markdown code span.
+    pub raw_reason: bool,
+    /// Marker shown after the dice which exploded, like `!` for `[6!, 4]`. `None` shows no
+    /// marker. Whether a die exploded is available in [`super::DiceResult::exploded`].
+    pub explosion_marker: Option<String>,
+    /// Symbols shown after the dice counting a success and the ones counting a failure when the
+    /// roll counts successes, like `✓` and `✗` for `[8✓, 9✓, 5, 2✗]`. A double success shows the
+    /// success symbol twice. `None` shows no symbol. The successes of each die are available in
+    /// [`super::DiceResult::successes`].
+    pub target_symbols: Option<(String, String)>,
+    /// Show the thresholds used to count the successes after the total, like
+    /// `= **1** (target 7, failure 2)`. They are shown for a roll of dice counting successes, not
+    /// for the result of an arithmetic operation.
+    pub show_thresholds: bool,
+}
+
+impl DisplayConfig {
+    // Render a separator of the history, replacing its operator by the configured symbol
+    pub(crate) fn separator(&self, sep: &str) -> String {
+        let index = match sep.trim() {
+            "+" => 0,
+            "-" => 1,
+            "*" => 2,
+            "/" => 3,
+            _ => return sep.to_string(),
+        };
+        match &self.operator_symbols[index] {
+            Some(symbol) => sep.replace(sep.trim(), symbol),
+            None => sep.to_string(),
+        }
+    }
+
+    // Render the reason as a markdown code span, with a fence longer than any run of backticks
+    // in it
+    pub(crate) fn reason(&self, reason: &str) -> String {
+        let longest_run = reason
+            .split(|c| c != '`')
+            .map(|run| run.len())
+            .max()
+            .unwrap_or(0);
+        if self.raw_reason || longest_run == 0 {
+            format!("`{}`", reason)
+        } else {
+            // the spaces are stripped by markdown, they separate a backtick at the edge from the
+            // fence
+            let fence = "`".repeat(longest_run + 1);
+            format!("{} {} {}", fence, reason, fence)
+        }
+    }
+
+    // Render a list of dice, eliding the middle of it if needed
+    pub(crate) fn dice_list(&self, dice: Vec<String>) -> String {
+        let len = dice.len();
+        let shown = match self.max_dice_shown {
+            Some(max) if len > max => {
+                let tail = max / 2;
+                let head = max - tail;
+                let mut shown = dice[..head].to_vec();
+                shown.push(format!("... ({} dice)", len));
+                shown.extend_from_slice(&dice[len - tail..]);
+                shown
+            }
+            _ => dice,
+        };
+        format!("[{}]", shown.join(", "))
+    }
+}
@@ -0,0 +1,17 @@
+/// Meaning of the total of a roll, see [`super::RollResult::outcome()`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// The total is the sum of the kept dice and constants
+    Sum(i64),
+    /// The dice were compared to targets, the total is the number of `successes - failures`. It's
+    /// not lower than 0 when ones are botches, unless overbotch is asked.
+    SuccessCount {
+        /// Number of successes, a die counting double adding two
+        successes: u64,
+        /// Number of failures
+        failures: u64,
+    },
+    /// The total is the sum of Fudge dice
+    Fudge(i64),
+}
@@ -1,15 +1,19 @@
-use std::ops::Deref;
+use std::{fmt::Display, ops::Deref};
 
-use crate::rollresult::SingleRollResult;
+use crate::{
+    error::Result,
+    rollresult::{singlerollresult::merge_warnings, DisplayConfig, SingleRollResult, Warning},
+};
 
 /// Represent a repeated roll.
 ///
 /// Can store the sum of all the roll if asked to. Usually created through
 /// [`super::RollResult::new_repeated()`] function.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RepeatedRollResult {
     pub(crate) rolls: Vec<SingleRollResult>,
     pub(crate) total: Option<i64>,
+    pub(crate) warnings: Vec<Warning>,
 }
 
 impl Deref for RepeatedRollResult {
@@ -21,8 +25,85 @@ impl Deref for RepeatedRollResult {
 }
 
 impl RepeatedRollResult {
+    pub(crate) fn new(rolls: Vec<SingleRollResult>, total: Option<i64>) -> Self {
+        let mut res = RepeatedRollResult {
+            rolls,
+            total,
+            warnings: Vec::new(),
+        };
+        res.collect_warnings();
+        res
+    }
+
+    // Gather the warnings of all the rolls
+    pub(crate) fn collect_warnings(&mut self) {
+        self.warnings = self.rolls.iter().fold(Vec::new(), |acc, r| {
+            merge_warnings(acc, r.get_warnings().to_vec())
+        });
+    }
+
+    /// Get the changes made to complete any of the rolls, see
+    /// [`super::RollResult::warnings()`]
+    pub fn get_warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     /// If the repeated roll was asked with a total, this will return the computed total.
     pub fn get_total(&self) -> Option<i64> {
         self.total
     }
+
+    /// Get the total of each roll, in the order of the rolls.
+    pub fn totals(&self) -> Vec<i64> {
+        self.rolls.iter().map(|r| r.get_total()).collect()
+    }
+
+    /// Get the highest total of the rolls, `None` if there is no roll.
+    pub fn max(&self) -> Option<i64> {
+        self.rolls.iter().map(|r| r.get_total()).max()
+    }
+
+    /// Get the lowest total of the rolls, `None` if there is no roll.
+    pub fn min(&self) -> Option<i64> {
+        self.rolls.iter().map(|r| r.get_total()).min()
+    }
+
+    /// Get the sum of the totals of the rolls, whether the sum was asked with `^+` or not.
+    pub fn sum_of_totals(&self) -> Result<i64> {
+        self.rolls
+            .iter()
+            .try_fold(0i64, |acc, r| acc.checked_add(r.get_total()))
+            .ok_or_else(|| "Total is too big to be computed".into())
+    }
+
+    /// Turn the rolls into a readable `String` using the given configuration, one numbered roll
+    /// per line, followed by the sum if it was asked with `^+`.
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let res = Roller::new("(1d6)^+2").unwrap().roll_with_values(&[4, 2]).unwrap();
+    /// assert_eq!(
+    ///     "#1: `[4]` = **4**\n#2: `[2]` = **2**\nSum: **6**",
+    ///     res.as_repeated().unwrap().to_string()
+    /// );
+    /// ```
+    pub fn to_string_with(&self, config: &DisplayConfig) -> String {
+        let mut lines: Vec<String> = self
+            .rolls
+            .iter()
+            .enumerate()
+            .map(|(i, r)| format!("#{}: {}", i + 1, r.to_string_with(true, config)))
+            .collect();
+        if let Some(total) = self.total {
+            lines.push(format!("Sum: **{}**", total));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Display for RepeatedRollResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_string_with(&DisplayConfig::default()))
+    }
 }
@@ -1,6 +1,19 @@
 use std::fmt::Display;
 
-use crate::rollresult::DiceResult;
+use crate::rollresult::{DiceResult, DisplayConfig};
+
+// Separators put in the history between the operands
+pub(crate) mod separator {
+    pub(crate) const ADD: &str = " + ";
+    pub(crate) const SUB: &str = " - ";
+    pub(crate) const MUL: &str = " * ";
+    pub(crate) const DIV: &str = " / ";
+    pub(crate) const POW: &str = " ^ ";
+    // between the attempts of `best()`, `worst()` and the pool rerolls
+    pub(crate) const ATTEMPT: &str = " | ";
+    // between the roll giving the number of dice and the dice, like `([3])d[6, 5, 2]`
+    pub(crate) const COUNT: &str = "d";
+}
 
 /// Carry a constant, either an `i64` or a `f64`.
 #[derive(Debug, Clone)]
@@ -21,6 +34,28 @@ impl Value {
     }
 }
 
+// floats are compared bit by bit, so `Eq` holds and a result can be hashed
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl std::hash::Hash for Value {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        match self {
+            Value::Int(i) => (0u8, *i as u64).hash(state),
+            Value::Float(f) => (1u8, f.to_bits()).hash(state),
+        }
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match *self {
@@ -31,18 +66,69 @@ impl Display for Value {
     }
 }
 
+/// Thresholds used to read the value of a Fudge die, rolled as a d6.
+///
+/// The default reads 1 and 2 as `-`, 3 and 4 as blank, 5 and 6 as `+`. See
+/// [`crate::Roller::with_fudge_config()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FudgeConfig {
+    /// Highest value read as `-`
+    pub minus_max: u64,
+    /// Highest value read as blank, values above are read as `+`
+    pub blank_max: u64,
+}
+
+impl Default for FudgeConfig {
+    fn default() -> Self {
+        FudgeConfig {
+            minus_max: 2,
+            blank_max: 4,
+        }
+    }
+}
+
+impl FudgeConfig {
+    /// Get the value of a Fudge die: -1, 0 or 1
+    pub fn value(&self, res: u64) -> i64 {
+        if res <= self.minus_max {
+            -1
+        } else if res <= self.blank_max {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Get the symbol of a Fudge die: `-`, `▢` or `+`
+    pub fn symbol(&self, res: u64) -> &'static str {
+        match self.value(res) {
+            -1 => "-",
+            0 => "▢",
+            _ => "+",
+        }
+    }
+}
+
 /// Carry one step of the history that led to the result.
 ///
 /// In a [`super::RollResult`]'s history, we either have a vector of the roll, or a separator
 /// between different dices. Ex: for `1d6 + 1d6`, we will have a [`RollHistory::Roll`] followed by
 /// [`RollHistory::Separator`] and another [`RollHistory::Roll`].
 #[non_exhaustive]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RollHistory {
     /// A roll with normal dices
     Roll(Vec<DiceResult>),
-    /// A roll with Fudge dices
-    Fudge(Vec<u64>),
+    /// A roll with Fudge dices, and the thresholds used to read them
+    Fudge(Vec<u64>, FudgeConfig),
+    /// Exploded dices counted in the bonus tally instead of the total
+    Bonus(Vec<DiceResult>),
+    /// Faces rolled on dice with named faces, see [`crate::Roller::with_die()`]. They don't
+    /// count in the total.
+    Faces(Vec<String>),
+    /// Dices rolled but not kept, like the discarded die of a reroll keeping the higher one
+    Discarded(Vec<DiceResult>),
     /// Was not a roll, but just a value
     Value(Value),
     /// An operation between roll and/or value
@@ -53,55 +139,90 @@ pub enum RollHistory {
     CloseParenthesis,
 }
 
-impl Display for RollHistory {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            RollHistory::Roll(v) => {
-                let mut s = String::new();
-                s.push('[');
-                let len = v.len();
-                v.iter().enumerate().for_each(|(i, r)| {
-                    s.push_str(&r.res.to_string());
-                    if i < len - 1 {
-                        s.push_str(", ");
-                    }
-                });
-                s.push(']');
-                s
-            }
-            RollHistory::Fudge(v) => {
-                let mut s = String::new();
-                s.push('[');
-                let len = v.len();
-                v.iter().enumerate().for_each(|(i, r)| {
-                    let r = if *r <= 2 {
-                        "-"
-                    } else if *r <= 4 {
-                        "▢"
-                    } else {
-                        "+"
-                    };
-                    s.push_str(r);
-                    if i < len - 1 {
-                        s.push_str(", ");
+fn dices_to_string(v: &[DiceResult], config: &DisplayConfig) -> String {
+    let mut v = v.to_vec();
+    if config.sort_ascending {
+        // reversed before a stable sort, so the dropped dice stay in front of equal values
+        v.reverse();
+        v.sort_by_key(|d| d.res);
+    }
+    config.dice_list(
+        v.iter()
+            .filter_map(|r| {
+                let mut value = match (r.exploded, &config.explosion_marker) {
+                    (true, Some(marker)) => format!("{}{}", r.res, marker),
+                    _ => r.res.to_string(),
+                };
+                if let Some((success, failure)) = &config.target_symbols {
+                    match r.successes {
+                        2 => value.push_str(&success.repeat(2)),
+                        1 => value.push_str(success),
+                        -1 => value.push_str(failure),
+                        _ => (),
                     }
-                });
-                s.push(']');
-                s
-            }
-            RollHistory::Value(v) => {
-                let mut s = String::new();
-                s.push_str(&v.to_string());
-                s
-            }
-            RollHistory::Separator(sep) => {
-                let mut s = String::new();
-                s.push_str(sep);
-                s
+                }
+                match (r.dropped, config.show_dropped) {
+                    (true, Some(true)) => Some(format!("~~{}~~", value)),
+                    (true, Some(false)) => None,
+                    _ => Some(value),
+                }
+            })
+            .collect(),
+    )
+}
+
+// Sort the dice like they are shown in the history, highest first
+pub(crate) fn sort_dice(dice: &mut [DiceResult]) {
+    dice.sort_unstable_by(|a, b| b.cmp(a));
+}
+
+impl RollHistory {
+    /// Create a [`RollHistory::Roll`] from the values rolled on dice with `sides` sides. The dice
+    /// are sorted like in the history of a roll, highest first.
+    ///
+    /// ```
+    /// use caith::RollHistory;
+    ///
+    /// assert_eq!("[6, 3, 1]", RollHistory::roll(&[3, 1, 6], 6).to_string());
+    /// ```
+    pub fn roll(values: &[u64], sides: u64) -> Self {
+        let mut dice: Vec<_> = values.iter().map(|&v| DiceResult::new(v, sides)).collect();
+        sort_dice(&mut dice);
+        RollHistory::Roll(dice)
+    }
+
+    /// Create a [`RollHistory::Fudge`] from the values rolled on the d6 read as Fudge dice with
+    /// `config`. The dice are sorted like in the history of a roll, highest first.
+    pub fn fudge(values: &[u64], config: FudgeConfig) -> Self {
+        let mut values = values.to_vec();
+        values.sort_unstable_by(|a, b| b.cmp(a));
+        RollHistory::Fudge(values, config)
+    }
+
+    /// Turn this step of the history into a `String`, using the given configuration
+    pub fn to_string_with(&self, config: &DisplayConfig) -> String {
+        match self {
+            RollHistory::Roll(v) => dices_to_string(v, config),
+            RollHistory::Bonus(v) => format!(" (bonus {})", dices_to_string(v, config)),
+            RollHistory::Discarded(v) => format!(" (discarded {})", dices_to_string(v, config)),
+            RollHistory::Fudge(v, fudge) => {
+                let mut v = v.clone();
+                if config.sort_ascending {
+                    v.sort_unstable();
+                }
+                config.dice_list(v.iter().map(|r| fudge.symbol(*r).to_string()).collect())
             }
+            RollHistory::Faces(faces) => config.dice_list(faces.clone()),
+            RollHistory::Value(v) => v.to_string(),
+            RollHistory::Separator(sep) => config.separator(sep),
             RollHistory::OpenParenthesis => "(".to_string(),
             RollHistory::CloseParenthesis => ")".to_string(),
-        };
-        write!(f, "{}", s)
+        }
+    }
+}
+
+impl Display for RollHistory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_string_with(&DisplayConfig::default()))
     }
 }
@@ -0,0 +1,26 @@
+/// How a float is turned into an integer total, see [`super::RollResult::scale()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rounding {
+    /// Toward zero, like the float constants of an expression (`1d6 * 1.5`)
+    #[default]
+    Trunc,
+    /// Toward negative infinity
+    Floor,
+    /// Toward positive infinity
+    Ceil,
+    /// To the nearest integer, half-way values away from zero
+    Round,
+}
+
+impl Rounding {
+    /// Round `value`, saturating to the bounds of `i64`
+    pub fn apply(self, value: f64) -> i64 {
+        let rounded = match self {
+            Rounding::Trunc => value.trunc(),
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+            Rounding::Round => value.round(),
+        };
+        rounded as i64
+    }
+}
@@ -1,6 +1,11 @@
+use std::{convert::TryFrom, ops::Range};
+
 use crate::{
-    error::Result, parser::TotalModifier, rollresult::DiceResult, rollresult::RollHistory,
-    rollresult::Value,
+    arithmetic::checked_pow, error::Result, parser::check_indices, parser::TotalModifier,
+    rollresult::rollhistory::sort_dice, rollresult::separator, rollresult::Critic,
+    rollresult::DiceResult, rollresult::DisplayConfig, rollresult::FudgeConfig,
+    rollresult::Outcome, rollresult::RollHistory, rollresult::Rounding, rollresult::SuccessLevel,
+    rollresult::Value, rollresult::Warning, Comparator,
 };
 
 /// Carry the result of one roll and an history of the steps taken.
@@ -12,10 +17,68 @@ pub struct SingleRollResult {
     /// failure`
     total: i64,
     /// History of the steps taken that lead to this result.
-    history: Vec<RollHistory>,
+    history: Vec<Step>,
     /// Internal usage field to avoid computing a total if it's already done.
     dirty: bool,
     constant: Option<f64>,
+    /// Comparison checked by the roll, if any
+    check: Option<(Comparator, u64)>,
+    /// Modifier used to count the successes, if any, to show its thresholds
+    thresholds: Option<TotalModifier>,
+    /// Keep or drop option choosing the dice whose successes are counted, if any
+    keep: Option<TotalModifier>,
+    /// If the botches can bring the total under 0
+    overbotch: bool,
+    /// Number of dice which exploded
+    explosions: u64,
+    /// If the total was lowered to the cap of the roll
+    capped: bool,
+    /// Meaning of the total, set when it's computed
+    outcome: Outcome,
+    /// Changes made to complete the roll
+    warnings: Vec<Warning>,
+}
+
+// A step of the history, with the span of the input it comes from if recorded
+#[derive(Debug, Clone)]
+struct Step {
+    history: RollHistory,
+    span: Option<Range<usize>>,
+    // if the step belongs to an attempt not kept, see `SingleRollResult::from_attempts()`, it
+    // doesn't count in the total
+    discarded: bool,
+}
+
+impl Step {
+    fn new(history: RollHistory) -> Self {
+        Step {
+            history,
+            span: None,
+            discarded: false,
+        }
+    }
+}
+
+/// A step of the history with the span of the input it comes from, see
+/// [`SingleRollResult::get_history_views()`].
+#[derive(Debug, Clone)]
+pub struct HistoryView<'a> {
+    history: &'a RollHistory,
+    span: Option<Range<usize>>,
+}
+
+impl<'a> HistoryView<'a> {
+    /// Get the step of the history
+    pub fn history(&self) -> &'a RollHistory {
+        self.history
+    }
+
+    /// Get the byte range of the input this step comes from, `None` if it was not recorded.
+    ///
+    /// The spans are recorded only when asked with [`crate::Roller::with_spans()`].
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
 }
 
 impl SingleRollResult {
@@ -26,83 +89,354 @@ impl SingleRollResult {
             history: Vec::new(),
             dirty: true,
             constant: None,
+            check: None,
+            thresholds: None,
+            keep: None,
+            overbotch: false,
+            explosions: 0,
+            capped: false,
+            outcome: Outcome::Sum(0),
+            warnings: Vec::new(),
         }
     }
 
-    /// Create a `SingleRollResult` with already a total. Used to carry constant value.
-    pub(crate) fn with_total(total: i64) -> Self {
+    /// Create a `SingleRollResult` from a total and the history leading to it, to build a result
+    /// outside of a roll, like for testing.
+    ///
+    /// The total is used as is: it's not computed from the history, as options like keep or
+    /// targets make it differ from the sum of the dice. Only [`super::RollResult::total_with()`]
+    /// computes a total from the history.
+    ///
+    /// The history must be well formed, an error is returned otherwise: the parentheses must be
+    /// balanced, each separator must be between two operands, the bonus and discarded dice must
+    /// follow a roll, and dice can't have a value of 0.
+    pub fn from_parts(total: i64, history: Vec<RollHistory>) -> Result<Self> {
+        check_history(&history)?;
+        Ok(Self::with_sum(
+            total,
+            history.into_iter().map(Step::new).collect(),
+        ))
+    }
+
+    // Create a `SingleRollResult` with a total already computed as a sum
+    fn with_sum(total: i64, history: Vec<Step>) -> Self {
         Self {
             total,
-            history: vec![RollHistory::Value(Value::Int(total))],
+            history,
             dirty: false,
-            constant: None,
+            outcome: Outcome::Sum(total),
+            ..Self::new()
         }
     }
 
+    /// Create a `SingleRollResult` with already a total. Used to carry constant value.
+    pub(crate) fn with_total(total: i64) -> Self {
+        Self::with_sum(
+            total,
+            vec![Step::new(RollHistory::Value(Value::Int(total)))],
+        )
+    }
+
     /// Create a `SingleRollResult` with already a total. Used to carry float constant value.
     pub(crate) fn with_float(f: f64) -> Self {
         Self {
-            total: f as i64,
-            history: vec![RollHistory::Value(Value::Float(f))],
-            dirty: false,
             constant: Some(f),
+            ..Self::with_sum(
+                f as i64,
+                vec![Step::new(RollHistory::Value(Value::Float(f)))],
+            )
         }
     }
 
     #[cfg(feature = "ova")]
     /// Create a `SingleRollResult` with a history and a total.
     pub(crate) fn with_total_and_hist(total: u64, history: Vec<DiceResult>) -> Self {
-        Self {
-            total: total as i64,
-            history: vec![RollHistory::Roll(history)],
-            dirty: false,
-            constant: None,
-        }
+        Self::with_sum(total as i64, vec![Step::new(RollHistory::Roll(history))])
     }
 
     /// Get the history of the result
-    pub fn get_history(&self) -> &Vec<RollHistory> {
-        &self.history
+    pub fn get_history(&self) -> Vec<&RollHistory> {
+        self.steps().collect()
+    }
+
+    // The steps of the history, without their span
+    fn steps(&self) -> impl DoubleEndedIterator<Item = &RollHistory> {
+        self.history.iter().map(|s| &s.history)
+    }
+
+    fn steps_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut RollHistory> {
+        self.history.iter_mut().map(|s| &mut s.history)
+    }
+
+    /// Get the dice rolled counting in the total, without the ones dropped by a keep or drop
+    /// option, nor the bonus and discarded dice.
+    pub fn kept_dice(&self) -> Vec<DiceResult> {
+        self.steps()
+            .filter_map(|h| match h {
+                RollHistory::Roll(dice) => Some(dice.iter().filter(|d| !d.dropped)),
+                _ => None,
+            })
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Get the index ranges of the history belonging to each roll group, a group being delimited
+    /// by the separators (` + `, ` - `...) between the operands.
+    pub fn get_group_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for (i, h) in self.steps().enumerate() {
+            if let RollHistory::Separator(_) = h {
+                ranges.push(start..i);
+                start = i + 1;
+            }
+        }
+        if start < self.history.len() {
+            ranges.push(start..self.history.len());
+        }
+        ranges
     }
 
     /// Add a step in the history
-    pub(crate) fn add_history(&mut self, mut history: Vec<DiceResult>, is_fudge: bool) {
+    pub(crate) fn add_history(&mut self, mut history: Vec<DiceResult>) {
         self.dirty = true;
-        history.sort_unstable_by(|a, b| b.cmp(a));
-        self.history.push(if is_fudge {
-            RollHistory::Fudge(history.iter().map(|r| r.res).collect())
-        } else {
-            RollHistory::Roll(history)
+        sort_dice(&mut history);
+        self.history.push(Step::new(RollHistory::Roll(history)));
+    }
+
+    /// Add a step of Fudge dice in the history
+    pub(crate) fn add_fudge_history(&mut self, mut history: Vec<DiceResult>, config: FudgeConfig) {
+        self.dirty = true;
+        sort_dice(&mut history);
+        self.history.push(Step::new(RollHistory::Fudge(
+            history.iter().map(|r| r.res).collect(),
+            config,
+        )));
+    }
+
+    /// Add the faces rolled on dice with named faces
+    pub(crate) fn add_faces_history(&mut self, faces: Vec<String>) {
+        self.dirty = true;
+        self.history.push(Step::new(RollHistory::Faces(faces)));
+    }
+
+    /// Add a step in the history, keeping the dice in the order they were rolled
+    pub(crate) fn add_history_in_roll_order(&mut self, history: Vec<DiceResult>) {
+        self.dirty = true;
+        self.history.push(Step::new(RollHistory::Roll(history)));
+    }
+
+    /// Add exploded dices counting in the bonus tally
+    pub(crate) fn add_bonus_history(&mut self, mut history: Vec<DiceResult>) {
+        sort_dice(&mut history);
+        self.history.push(Step::new(RollHistory::Bonus(history)));
+    }
+
+    /// Count dice which exploded, each one rolling a new die
+    pub(crate) fn add_explosions(&mut self, count: u64) {
+        self.explosions += count;
+    }
+
+    // Flag the dice at or above `value` in the last step of dice as having exploded
+    pub(crate) fn mark_exploded(&mut self, value: u64) {
+        let last = self.steps_mut().rev().find_map(|h| match h {
+            RollHistory::Roll(dice) | RollHistory::Bonus(dice) => Some(dice),
+            _ => None,
         });
+        if let Some(dice) = last {
+            dice.iter_mut().for_each(|d| d.exploded = d.res >= value);
+        }
+    }
+
+    // Change the value of every die of the history, like to add a per-die modifier
+    pub(crate) fn map_dice(&mut self, f: impl Fn(u64) -> u64) {
+        self.dirty = true;
+        for h in self.steps_mut() {
+            if let RollHistory::Roll(dice)
+            | RollHistory::Bonus(dice)
+            | RollHistory::Discarded(dice) = h
+            {
+                dice.iter_mut().for_each(|d| d.res = f(d.res));
+            }
+        }
+    }
+
+    /// Add dices which were rolled but not kept
+    pub(crate) fn add_discarded_history(&mut self, mut history: Vec<DiceResult>) {
+        sort_dice(&mut history);
+        self.history
+            .push(Step::new(RollHistory::Discarded(history)));
     }
 
     pub(crate) fn add_parenthesis(&mut self) {
-        self.history.insert(0, RollHistory::OpenParenthesis);
-        self.history.push(RollHistory::CloseParenthesis);
+        self.history
+            .insert(0, Step::new(RollHistory::OpenParenthesis));
+        self.history.push(Step::new(RollHistory::CloseParenthesis));
+    }
+
+    // Remove the parentheses around the whole history added by `add_parenthesis()`, if any
+    fn strip_parenthesis(&mut self) {
+        if matches!(self.steps().next(), Some(RollHistory::OpenParenthesis)) {
+            self.history.remove(0);
+            self.history.pop();
+        }
+    }
+
+    /// Join the results of several rolls of the same expression between parentheses, separated by
+    /// ` | `, with the total of the one at `kept`. The dice of the other ones are dropped.
+    pub(crate) fn from_attempts(attempts: Vec<Self>, kept: usize) -> Self {
+        let mut res = Self::new();
+        res.dirty = false;
+        for (i, mut attempt) in attempts.into_iter().enumerate() {
+            attempt.strip_parenthesis();
+            if i == kept {
+                res.total = attempt.total;
+                res.outcome = attempt.outcome;
+            } else {
+                for step in attempt.history.iter_mut() {
+                    step.discarded = true;
+                    if let RollHistory::Roll(r) = &mut step.history {
+                        r.iter_mut().for_each(|d| d.dropped = true);
+                    }
+                }
+            }
+            res.explosions += attempt.explosions;
+            res.warnings = merge_warnings(
+                std::mem::take(&mut res.warnings),
+                std::mem::take(&mut attempt.warnings),
+            );
+            if i == 0 {
+                res.history.append(&mut attempt.history);
+            } else {
+                merge_history(&mut res, &mut attempt, separator::ATTEMPT);
+            }
+        }
+        res.add_parenthesis();
+        res
+    }
+
+    /// Put the history of the roll giving the number of dice before the dice, separated by `d`,
+    /// like `([3])d[6, 5, 2]`. The total stays the one of the dice.
+    pub(crate) fn with_rolled_count(mut self, mut count: Self) -> Self {
+        merge_history(&mut count, &mut self, separator::COUNT);
+        self.history = count.history;
+        self.explosions += count.explosions;
+        self.warnings = merge_warnings(count.warnings, std::mem::take(&mut self.warnings));
+        self
+    }
+
+    /// Record `span` as the span of all the steps of the history
+    pub(crate) fn set_span(&mut self, span: Range<usize>) {
+        for step in self.history.iter_mut() {
+            step.span = Some(span.clone());
+        }
+    }
+
+    /// Record `span` as the span of the step at `index` in the history
+    pub(crate) fn set_span_at(&mut self, index: usize, span: Range<usize>) {
+        self.history[index].span = Some(span);
+    }
+
+    /// Get the steps of the history with their span in the input, recorded only when asked with
+    /// [`crate::Roller::with_spans()`].
+    ///
+    /// ```
+    /// use caith::Roller;
+    ///
+    /// let res = Roller::new("2d6 + 3").unwrap().with_spans(true).roll().unwrap();
+    /// let views = res.as_single().unwrap().get_history_views();
+    /// assert_eq!(Some(0..3), views[0].span());
+    /// ```
+    pub fn get_history_views(&self) -> Vec<HistoryView<'_>> {
+        self.history
+            .iter()
+            .map(|step| HistoryView {
+                history: &step.history,
+                span: step.span.clone(),
+            })
+            .collect()
+    }
+
+    // Mark the dice satisfying the comparisons as critic, in place of the max and min values
+    pub(crate) fn set_crits(&mut self, success: (Comparator, u64), failure: (Comparator, u64)) {
+        for history in self.steps_mut() {
+            if let RollHistory::Roll(dice)
+            | RollHistory::Bonus(dice)
+            | RollHistory::Discarded(dice) = history
+            {
+                for die in dice.iter_mut() {
+                    die.crit = if success.0.compare(die.res, success.1) {
+                        Critic::Max
+                    } else if failure.0.compare(die.res, failure.1) {
+                        Critic::Min
+                    } else {
+                        Critic::No
+                    };
+                }
+            }
+        }
+    }
+
+    pub(crate) fn set_check(&mut self, comparator: Comparator, target: u64) {
+        self.check = Some((comparator, target));
+    }
+
+    /// Count the successes only on the dice kept by the given keep or drop option
+    pub(crate) fn set_keep(&mut self, keep: TotalModifier) {
+        self.dirty = true;
+        self.keep = Some(keep);
+    }
+
+    /// Let the botches bring the total under 0
+    pub(crate) fn set_overbotch(&mut self, allow: bool) {
+        self.dirty = true;
+        self.overbotch = allow;
     }
 
     /// Compute the total value according to some modifier
     pub(crate) fn compute_total(&mut self, modifier: TotalModifier) -> Result<i64> {
         if self.dirty {
             self.dirty = false;
-            let mut flat = self.history.iter().fold(Vec::new(), |mut acc, h| {
-                match h {
+            // each value with the position of its die in the history, to mark the dropped ones
+            let mut positions = Vec::new();
+            for (i, step) in self.history.iter().enumerate() {
+                if step.discarded {
+                    continue;
+                }
+                match &step.history {
                     RollHistory::Roll(r) => {
-                        let mut c = r.iter().map(|u| u.res as i64).collect();
-                        acc.append(&mut c);
+                        for (j, u) in r.iter().enumerate() {
+                            positions.push((die_value(u.res)?, Some((i, j))));
+                        }
                     }
-                    RollHistory::Fudge(r) => {
-                        let mut c = r.iter().map(|u| *u as i64).collect();
-                        acc.append(&mut c);
+                    RollHistory::Fudge(r, _) => {
+                        for &u in r {
+                            positions.push((die_value(u)?, None));
+                        }
                     }
-                    RollHistory::Value(v) => acc.push(v.get_value()),
+                    RollHistory::Value(v) => positions.push((v.get_value(), None)),
                     _ => (),
                 };
-                acc
-            });
-            flat.sort_unstable();
-            let flat = flat;
-            match modifier {
+            }
+            // the dice are dropped by the keep option when the modifier counts their successes
+            let dropping = match (&modifier, &self.keep) {
+                (
+                    TotalModifier::TargetFailureDouble(_, _, _)
+                    | TotalModifier::TargetEnum(_)
+                    | TotalModifier::Compare(_, _)
+                    | TotalModifier::CompareBotch(_, _),
+                    Some(keep),
+                ) => keep.clone(),
+                _ => modifier.clone(),
+            };
+            // positions are in roll order, the flat list must not be sorted. Otherwise equal values
+            // are sorted in reverse history order, so the dropped dice are contiguous when shown.
+            if !matches!(dropping, TotalModifier::KeepIndices(_)) {
+                positions.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+            }
+            let flat: Vec<i64> = positions.iter().map(|(v, _)| *v).collect();
+            match dropping {
                 TotalModifier::KeepHi(n)
                 | TotalModifier::KeepLo(n)
                 | TotalModifier::DropHi(n)
@@ -111,64 +445,316 @@ impl SingleRollResult {
                         return Err("Not enough dice to keep or drop".into());
                     }
                 }
-                TotalModifier::None(_)
+                TotalModifier::KeepIndices(ref indices) => check_indices(indices, flat.len())?,
+                TotalModifier::Median => {
+                    if flat.is_empty() {
+                        return Err("No dice to take the median of".into());
+                    }
+                }
+                TotalModifier::KeepCompare(_, _)
+                | TotalModifier::DropCompare(_, _)
+                | TotalModifier::None
                 | TotalModifier::TargetFailureDouble(_, _, _)
                 | TotalModifier::TargetEnum(_)
-                | TotalModifier::Fudge => (),
+                | TotalModifier::Compare(_, _)
+                | TotalModifier::CompareBotch(_, _)
+                | TotalModifier::Fudge(_) => (),
             }
 
-            let slice = match modifier {
-                TotalModifier::KeepHi(n) => &flat[flat.len() - n..],
-                TotalModifier::KeepLo(n) => &flat[..n],
-                TotalModifier::DropHi(n) => &flat[..flat.len() - n],
-                TotalModifier::DropLo(n) => &flat[n..],
-                TotalModifier::None(_)
+            let len = flat.len();
+            let is_kept = |k: usize| match dropping {
+                TotalModifier::KeepHi(n) => k >= len - n,
+                TotalModifier::KeepLo(n) => k < n,
+                TotalModifier::DropHi(n) => k < len - n,
+                TotalModifier::DropLo(n) => k >= n,
+                TotalModifier::KeepIndices(ref indices) => indices.contains(&(k + 1)),
+                TotalModifier::KeepCompare(comparator, value) => {
+                    comparator.compare(flat[k] as u64, value)
+                }
+                TotalModifier::DropCompare(comparator, value) => {
+                    !comparator.compare(flat[k] as u64, value)
+                }
+                // sorted from the lowest, so it's the lower middle die for an even count
+                TotalModifier::Median => k == (len - 1) / 2,
+                TotalModifier::None
                 | TotalModifier::TargetFailureDouble(_, _, _)
                 | TotalModifier::TargetEnum(_)
-                | TotalModifier::Fudge => flat.as_slice(),
+                | TotalModifier::Compare(_, _)
+                | TotalModifier::CompareBotch(_, _)
+                | TotalModifier::Fudge(_) => true,
             };
-
-            self.total = match modifier {
-                TotalModifier::TargetFailureDouble(t, f, d) => slice.iter().fold(0, |acc, &x| {
-                    let x = x as u64;
-                    if d > 0 && x >= d {
-                        acc + 2
-                    } else if t > 0 && x >= t {
-                        acc + 1
-                    } else if f > 0 && x <= f {
-                        acc - 1
+            let dropped = positions
+                .iter()
+                .enumerate()
+                .filter(|(k, _)| !is_kept(*k))
+                .filter_map(|(_, (_, pos))| *pos);
+            self.mark_dropped(dropped.collect());
+            let slice: Vec<i64> = (0..len).filter(|&k| is_kept(k)).map(|k| flat[k]).collect();
+            // successes counted by each die, the dropped ones counting none
+            let successes: Vec<i8> = (0..len)
+                .map(|k| {
+                    if is_kept(k) {
+                        die_successes(&modifier, flat[k])
                     } else {
-                        acc
+                        0
                     }
-                }),
-                TotalModifier::TargetEnum(v) => slice.iter().fold(0, |acc, &x| {
-                    if v.contains(&(x as u64)) {
-                        acc + 1
-                    } else {
-                        acc
-                    }
-                }),
-                TotalModifier::Fudge => slice.iter().fold(0, |acc, &x| {
-                    if x <= 2 {
-                        acc - 1
-                    } else if x <= 4 {
-                        acc
+                })
+                .collect();
+            self.mark_successes(
+                positions
+                    .iter()
+                    .zip(successes.iter())
+                    .filter_map(|((_, pos), &s)| pos.map(|pos| (pos, s)))
+                    .collect(),
+            );
+            let net_successes: i64 = successes.iter().map(|&s| s as i64).sum();
+            let success_count = Outcome::SuccessCount {
+                successes: successes
+                    .iter()
+                    .filter(|&&s| s > 0)
+                    .map(|&s| s as u64)
+                    .sum(),
+                failures: successes.iter().filter(|&&s| s < 0).count() as u64,
+            };
+
+            self.total = match modifier {
+                TotalModifier::TargetFailureDouble(_, _, _)
+                | TotalModifier::TargetEnum(_)
+                | TotalModifier::Compare(_, _) => net_successes,
+                TotalModifier::CompareBotch(_, _) => {
+                    if self.overbotch {
+                        net_successes
                     } else {
-                        acc + 1
+                        net_successes.max(0)
                     }
-                }),
-                _ => slice.iter().sum::<i64>(),
+                }
+                TotalModifier::Fudge(config) => {
+                    slice.iter().fold(0, |acc, &x| acc + config.value(x as u64))
+                }
+                _ => checked_sum(slice.iter().copied())?,
+            };
+            self.outcome = match modifier {
+                TotalModifier::TargetFailureDouble(_, _, _)
+                | TotalModifier::TargetEnum(_)
+                | TotalModifier::Compare(_, _)
+                | TotalModifier::CompareBotch(_, _) => success_count,
+                TotalModifier::Fudge(_) => Outcome::Fudge(self.total),
+                _ => Outcome::Sum(self.total),
+            };
+            self.thresholds = match self.outcome {
+                Outcome::SuccessCount { .. } => Some(modifier),
+                _ => None,
             };
         }
 
         Ok(self.total)
     }
 
+    // Flag the dice at the given positions (history index, die index) as dropped, and only them
+    fn mark_dropped(&mut self, dropped: Vec<(usize, usize)>) {
+        for step in self.history.iter_mut().filter(|s| !s.discarded) {
+            if let RollHistory::Roll(r) = &mut step.history {
+                r.iter_mut().for_each(|d| d.dropped = false);
+            }
+        }
+        for (i, j) in dropped {
+            if let RollHistory::Roll(r) = &mut self.history[i].history {
+                r[j].dropped = true;
+            }
+        }
+    }
+
+    // Set the successes counted by the dice at the given positions (history index, die index)
+    fn mark_successes(&mut self, successes: Vec<((usize, usize), i8)>) {
+        for ((i, j), s) in successes {
+            if let RollHistory::Roll(r) = &mut self.history[i].history {
+                r[j].successes = s;
+            }
+        }
+    }
+
+    /// Append the history of `other` without separator and add its total
+    pub(crate) fn merge(mut self, mut other: Self) -> Self {
+        self.history.append(&mut other.history);
+        let total = self.total.checked_add(other.total).unwrap_or_else(|| {
+            self.add_warning(Warning::OverflowSaturated);
+            self.total.saturating_add(other.total)
+        });
+        Self {
+            explosions: self.explosions + other.explosions,
+            capped: self.capped,
+            warnings: merge_warnings(self.warnings, other.warnings),
+            ..Self::with_sum(total, self.history)
+        }
+    }
+
+    /// Add two results, failing instead of overflowing
+    pub(crate) fn checked_add(self, rhs: Self) -> Result<Self> {
+        combine(self, rhs, separator::ADD, i64::checked_add, |l, r| l + r)
+    }
+
+    /// Subtract two results, failing instead of overflowing
+    pub(crate) fn checked_sub(self, rhs: Self) -> Result<Self> {
+        combine(self, rhs, separator::SUB, i64::checked_sub, |l, r| l - r)
+    }
+
+    /// Multiply two results, failing instead of overflowing
+    pub(crate) fn checked_mul(self, rhs: Self) -> Result<Self> {
+        combine(self, rhs, separator::MUL, i64::checked_mul, |l, r| l * r)
+    }
+
+    /// Divide two results, failing instead of overflowing or dividing by zero
+    pub(crate) fn checked_div(self, rhs: Self) -> Result<Self> {
+        if self.divides_by_zero(&rhs) {
+            return Err("Can't divide by zero".into());
+        }
+        combine(self, rhs, separator::DIV, i64::checked_div, |l, r| l / r)
+    }
+
+    // Dividing the integer totals, with a divisor of 0
+    fn divides_by_zero(&self, rhs: &Self) -> bool {
+        self.constant.is_none() && rhs.constant.is_none() && rhs.total == 0
+    }
+
+    /// Raise to a power, failing instead of overflowing or on a negative exponent
+    pub(crate) fn checked_pow(self, rhs: Self) -> Result<Self> {
+        if rhs.total < 0 || matches!(rhs.constant, Some(c) if c < 0.0) {
+            return Err("Exponent can't be negative".into());
+        }
+        combine(self, rhs, separator::POW, checked_pow, f64::powf)
+    }
+
+    /// Multiply the total by a float factor, noted in the history like a float constant. A
+    /// history of several terms is put between parentheses.
+    pub(crate) fn scale(mut self, factor: f64, rounding: Rounding) -> Self {
+        let total = rounding.apply(self.total as f64 * factor);
+        if self.steps().any(|h| matches!(h, RollHistory::Separator(_))) {
+            self.add_parenthesis();
+        }
+        let mut res = self * Self::with_float(factor);
+        res.total = total;
+        res.outcome = Outcome::Sum(total);
+        res
+    }
+
+    /// Compute again the total with the given modifier
+    pub(crate) fn apply_modifier(&mut self, modifier: TotalModifier) -> Result<()> {
+        self.dirty = true;
+        self.capped = false;
+        self.warnings
+            .retain(|&w| w != Warning::TotalCapped && w != Warning::TotalFloored);
+        self.compute_total(modifier)?;
+        Ok(())
+    }
+
+    /// Compute the total with the given modifier, without modifying this result
+    pub(crate) fn total_with(&self, modifier: TotalModifier) -> Result<i64> {
+        let mut res = self.clone();
+        res.dirty = true;
+        res.compute_total(modifier)
+    }
+
     /// Get the result value
     pub fn get_total(&self) -> i64 {
         self.total
     }
 
+    // Lower the total to `cap` if it's above
+    pub(crate) fn cap_total(&mut self, cap: i64) {
+        if self.total > cap {
+            self.total = cap;
+            self.capped = true;
+            self.add_warning(Warning::TotalCapped);
+            self.outcome = match self.outcome {
+                Outcome::Sum(_) => Outcome::Sum(cap),
+                Outcome::Fudge(_) => Outcome::Fudge(cap),
+                success_count => success_count,
+            };
+        }
+    }
+
+    // Raise the total to `floor` if it's below
+    pub(crate) fn floor_total(&mut self, floor: i64) {
+        if self.total < floor {
+            self.total = floor;
+            self.add_warning(Warning::TotalFloored);
+            self.outcome = match self.outcome {
+                Outcome::Sum(_) => Outcome::Sum(floor),
+                Outcome::Fudge(_) => Outcome::Fudge(floor),
+                success_count => success_count,
+            };
+        }
+    }
+
+    /// Says if the total was lowered to the cap given with [`crate::Roller::with_total_cap()`].
+    /// The history is kept as rolled.
+    pub fn is_capped(&self) -> bool {
+        self.capped
+    }
+
+    /// Get the changes made to complete the roll, see [`super::RollResult::warnings()`]
+    pub fn get_warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    // Record a change made to complete the roll, once
+    pub(crate) fn add_warning(&mut self, warning: Warning) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Get the meaning of the total, see [`super::RollResult::outcome()`]
+    pub fn get_outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    /// Get the sum of the dice sent to the bonus tally (exploded dice with the `b` suffix)
+    pub fn get_bonus_total(&self) -> i64 {
+        self.steps()
+            .filter_map(|h| match h {
+                RollHistory::Bonus(v) => Some(v.iter().map(|d| d.res as i64).sum::<i64>()),
+                _ => None,
+            })
+            .sum()
+    }
+
+    /// Get the number of dice which exploded. A die exploding again counts once more: `1d6 ie`
+    /// rolling `[6, 6, 3]` gives 2.
+    pub fn get_explosion_count(&self) -> u64 {
+        self.explosions
+    }
+
+    /// Get the level of success of a roll-under check (ex: `1d100<=65`).
+    ///
+    /// Returns `None` if the roll isn't a `<=` comparison on a single die.
+    pub fn get_success_level(&self) -> Option<SuccessLevel> {
+        match self.check {
+            Some((Comparator::Le, target)) => {
+                let dice = self.steps().rev().find_map(|h| match h {
+                    RollHistory::Roll(dice) => Some(dice),
+                    _ => None,
+                })?;
+                match dice.as_slice() {
+                    [die] => Some(SuccessLevel::roll_under(die.res, target)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Says if no dice counting in the total were rolled to get this result, it only contains
+    /// constant values and dice with named faces
+    pub fn is_empty(&self) -> bool {
+        !self.steps().any(|h| {
+            matches!(
+                h,
+                RollHistory::Roll(_) | RollHistory::Fudge(_, _) | RollHistory::Bonus(_)
+            )
+        })
+    }
+
     /// Says if the used value for math operation is 0
     ///
     /// If there's a constant stored, we'll use it and if not, `total` is used instead
@@ -182,14 +768,25 @@ impl SingleRollResult {
 
     /// Turn the vector of `RollHistory` to a `String`
     pub fn to_string_history(&self) -> String {
-        self.history.iter().fold(String::new(), |mut s, v| {
-            s.push_str(v.to_string().as_str());
+        self.to_string_history_with(&DisplayConfig::default())
+    }
+
+    /// Turn the vector of `RollHistory` to a `String`, using the given configuration
+    pub fn to_string_history_with(&self, config: &DisplayConfig) -> String {
+        self.steps().fold(String::new(), |mut s, v| {
+            s.push_str(v.to_string_with(config).as_str());
             s
         })
     }
 
     /// Turn the `RollResult` to a readable String, with or without markdown formatting.
     pub fn to_string(&self, md: bool) -> String {
+        self.to_string_with(md, &DisplayConfig::default())
+    }
+
+    /// Turn the `RollResult` to a readable String, with or without markdown formatting, using the
+    /// given configuration.
+    pub fn to_string_with(&self, md: bool, config: &DisplayConfig) -> String {
         if self.history.is_empty() {
             if md {
                 format!("`{}`", self.total)
@@ -197,101 +794,260 @@ impl SingleRollResult {
                 format!("{}", self.total)
             }
         } else {
-            let s = self.to_string_history();
+            let s = self.to_string_history_with(config);
+            let thresholds = match &self.thresholds {
+                Some(modifier) if config.show_thresholds => {
+                    format!(" ({})", thresholds_to_string(modifier))
+                }
+                _ => String::new(),
+            };
             format!(
-                "{1}{0}{1} = {2}{3}{2}",
+                "{1}{0}{1} = {2}{3}{2}{4}",
                 s,
                 if md { "`" } else { "" },
                 if md { "**" } else { "" },
-                self.get_total()
+                self.get_total(),
+                thresholds
             )
         }
     }
 }
 
+// Describe the thresholds used to count the successes, like `target 7, failure 2`
+fn thresholds_to_string(modifier: &TotalModifier) -> String {
+    match modifier {
+        TotalModifier::TargetFailureDouble(t, f, d) => {
+            [(t, "target"), (f, "failure"), (d, "double")]
+                .iter()
+                .filter(|(value, _)| **value > 0)
+                .map(|(value, name)| format!("{} {}", name, value))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        TotalModifier::TargetEnum(values) => format!(
+            "target [{}]",
+            values
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        TotalModifier::Compare(comparator, target) => format!("target {}{}", comparator, target),
+        TotalModifier::CompareBotch(comparator, target) => {
+            format!("target {}{}, botch 1", comparator, target)
+        }
+        _ => String::new(),
+    }
+}
+
+// Successes counted by a die when the modifier counts successes, 0 otherwise
+fn die_successes(modifier: &TotalModifier, value: i64) -> i8 {
+    let x = value as u64;
+    match *modifier {
+        TotalModifier::TargetFailureDouble(t, f, d) => {
+            if d > 0 && x >= d {
+                2
+            } else if t > 0 && x >= t {
+                1
+            } else if f > 0 && x <= f {
+                -1
+            } else {
+                0
+            }
+        }
+        TotalModifier::TargetEnum(ref v) => v.contains(&x) as i8,
+        TotalModifier::Compare(comparator, target) => comparator.compare(x, target) as i8,
+        TotalModifier::CompareBotch(comparator, target) => {
+            comparator.compare(x, target) as i8 - (x == 1) as i8
+        }
+        _ => 0,
+    }
+}
+
+// Convert a die value to be summed, dice with more than `i64::MAX` sides can roll too big values
+fn die_value(res: u64) -> Result<i64> {
+    i64::try_from(res).map_err(|_| format!("Die value {} is too big to be summed", res).into())
+}
+
+fn checked_sum(values: impl Iterator<Item = i64>) -> Result<i64> {
+    values
+        .into_iter()
+        .try_fold(0i64, |acc, x| acc.checked_add(x))
+        .ok_or_else(|| "Total is too big to be computed".into())
+}
+
+// Check the structure of a history given by the user
+fn check_history(history: &[RollHistory]) -> Result<()> {
+    let mut depth = 0usize;
+    // an operand is expected: at start, after a separator or an open parenthesis
+    let mut expect_operand = true;
+    let mut after_roll = false;
+    for h in history {
+        let null_die = match h {
+            RollHistory::Roll(dice) | RollHistory::Bonus(dice) | RollHistory::Discarded(dice) => {
+                dice.iter().any(|d| d.res == 0)
+            }
+            RollHistory::Fudge(dice, _) => dice.contains(&0),
+            _ => false,
+        };
+        if null_die {
+            return Err("A die of the history has a value of 0".into());
+        }
+        match h {
+            RollHistory::Roll(_)
+            | RollHistory::Fudge(_, _)
+            | RollHistory::Faces(_)
+            | RollHistory::Value(_) => {
+                if !expect_operand {
+                    return Err("Missing separator between two operands of the history".into());
+                }
+                expect_operand = false;
+            }
+            RollHistory::Bonus(_) | RollHistory::Discarded(_) => {
+                if !after_roll {
+                    return Err("Bonus and discarded dice must follow a roll in the history".into());
+                }
+            }
+            RollHistory::Separator(_) => {
+                if expect_operand {
+                    return Err("Misplaced separator in the history".into());
+                }
+                expect_operand = true;
+            }
+            RollHistory::OpenParenthesis => {
+                if !expect_operand {
+                    return Err("Missing separator between two operands of the history".into());
+                }
+                depth += 1;
+            }
+            RollHistory::CloseParenthesis => {
+                if depth == 0 || expect_operand {
+                    return Err("Unbalanced parentheses in the history".into());
+                }
+                depth -= 1;
+            }
+        }
+        after_roll = matches!(
+            h,
+            RollHistory::Roll(_) | RollHistory::Bonus(_) | RollHistory::Discarded(_)
+        );
+    }
+    if depth > 0 {
+        return Err("Unbalanced parentheses in the history".into());
+    }
+    if expect_operand && !history.is_empty() {
+        return Err("Misplaced separator in the history".into());
+    }
+    Ok(())
+}
+
+/// Two results are equal if they have the same total and history. The dice are compared by value
+/// only, see [`DiceResult`].
+impl PartialEq for SingleRollResult {
+    fn eq(&self, other: &Self) -> bool {
+        self.total == other.total && self.steps().eq(other.steps())
+    }
+}
+
+impl Eq for SingleRollResult {}
+
+impl std::hash::Hash for SingleRollResult {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.total.hash(state);
+        self.steps().for_each(|h| h.hash(state));
+    }
+}
+
+impl Default for SingleRollResult {
+    /// An empty result, with a total of 0
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Warnings of both sides, each once
+pub(crate) fn merge_warnings(mut left: Vec<Warning>, right: Vec<Warning>) -> Vec<Warning> {
+    for warning in right {
+        if !left.contains(&warning) {
+            left.push(warning);
+        }
+    }
+    left
+}
+
 fn merge_history(left: &mut SingleRollResult, right: &mut SingleRollResult, op: &'static str) {
     if !right.history.is_empty() {
-        left.history.push(RollHistory::Separator(op));
+        left.history.push(Step::new(RollHistory::Separator(op)));
         left.history.append(&mut right.history);
     }
 }
 
+// Shared implementation of the arithmetic operators: merge the histories and compute the total,
+// using the float constant of an operand if it has one.
+fn combine(
+    mut lhs: SingleRollResult,
+    mut rhs: SingleRollResult,
+    op: &'static str,
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<SingleRollResult> {
+    merge_history(&mut lhs, &mut rhs, op);
+    let total = match (lhs.constant, rhs.constant) {
+        (None, None) => int_op(lhs.total, rhs.total).ok_or("Result is too big to be computed")?,
+        (None, Some(constant)) => float_op(lhs.total as f64, constant).trunc() as i64,
+        (Some(constant), None) => float_op(constant, rhs.total as f64).trunc() as i64,
+        (Some(lconstant), Some(rconstant)) => float_op(lconstant, rconstant).trunc() as i64,
+    };
+    Ok(SingleRollResult {
+        explosions: lhs.explosions + rhs.explosions,
+        warnings: merge_warnings(lhs.warnings, rhs.warnings),
+        ..SingleRollResult::with_sum(total, lhs.history)
+    })
+}
+
+/// # Panics
+///
+/// Panics if the sum of the totals overflows an `i64`.
 impl std::ops::Add for SingleRollResult {
     type Output = Self;
 
-    fn add(mut self, mut rhs: Self) -> Self::Output {
-        merge_history(&mut self, &mut rhs, " + ");
-        let total = match (self.constant, rhs.constant) {
-            (None, None) => self.total + rhs.total,
-            (None, Some(constant)) => (self.total as f64 + constant).trunc() as i64,
-            (Some(constant), None) => (constant + rhs.total as f64).trunc() as i64,
-            (Some(lconstant), Some(rconstant)) => (lconstant + rconstant).trunc() as i64,
-        };
-        SingleRollResult {
-            total,
-            history: self.history,
-            dirty: false,
-            constant: None,
-        }
+    fn add(self, rhs: Self) -> Self::Output {
+        self.checked_add(rhs).expect("overflow in roll arithmetic")
     }
 }
 
+/// # Panics
+///
+/// Panics if the difference of the totals overflows an `i64`.
 impl std::ops::Sub for SingleRollResult {
     type Output = Self;
 
-    fn sub(mut self, mut rhs: Self) -> Self::Output {
-        merge_history(&mut self, &mut rhs, " - ");
-        let total = match (self.constant, rhs.constant) {
-            (None, None) => self.total - rhs.total,
-            (None, Some(constant)) => (self.total as f64 - constant).trunc() as i64,
-            (Some(constant), None) => (constant - rhs.total as f64).trunc() as i64,
-            (Some(lconstant), Some(rconstant)) => (lconstant - rconstant).trunc() as i64,
-        };
-        SingleRollResult {
-            total,
-            history: self.history,
-            dirty: false,
-            constant: None,
-        }
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.checked_sub(rhs).expect("overflow in roll arithmetic")
     }
 }
 
+/// # Panics
+///
+/// Panics if the product of the totals overflows an `i64`.
 impl std::ops::Mul for SingleRollResult {
     type Output = Self;
 
-    fn mul(mut self, mut rhs: Self) -> Self::Output {
-        merge_history(&mut self, &mut rhs, " * ");
-        let total = match (self.constant, rhs.constant) {
-            (None, None) => self.total * rhs.total,
-            (None, Some(constant)) => (self.total as f64 * constant).trunc() as i64,
-            (Some(constant), None) => (constant * rhs.total as f64).trunc() as i64,
-            (Some(lconstant), Some(rconstant)) => (lconstant * rconstant).trunc() as i64,
-        };
-        SingleRollResult {
-            total,
-            history: self.history,
-            dirty: false,
-            constant: None,
-        }
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.checked_mul(rhs).expect("overflow in roll arithmetic")
     }
 }
 
+/// # Panics
+///
+/// Panics if the divisor is an integer total of 0, or if the quotient overflows an `i64`.
 impl std::ops::Div for SingleRollResult {
     type Output = Self;
 
-    fn div(mut self, mut rhs: Self) -> Self::Output {
-        merge_history(&mut self, &mut rhs, " / ");
-        let total = match (self.constant, rhs.constant) {
-            (None, None) => self.total / rhs.total,
-            (None, Some(constant)) => (self.total as f64 / constant).trunc() as i64,
-            (Some(constant), None) => (constant / rhs.total as f64).trunc() as i64,
-            (Some(lconstant), Some(rconstant)) => (lconstant / rconstant).trunc() as i64,
-        };
-        SingleRollResult {
-            total,
-            history: self.history,
-            dirty: false,
-            constant: None,
+    fn div(self, rhs: Self) -> Self::Output {
+        if self.divides_by_zero(&rhs) {
+            panic!("division by zero in roll arithmetic");
         }
+        self.checked_div(rhs).expect("overflow in roll arithmetic")
     }
 }
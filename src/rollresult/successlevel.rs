@@ -0,0 +1,35 @@
+/// Level of success of a roll-under check, as in Basic Roleplaying or Call of Cthulhu.
+///
+/// Levels are ordered, from `Failure` to `Extreme`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SuccessLevel {
+    /// The roll is over the target
+    Failure,
+    /// The roll is at or under the target
+    Regular,
+    /// The roll is at or under half the target
+    Hard,
+    /// The roll is at or under a fifth of the target
+    Extreme,
+}
+
+impl SuccessLevel {
+    /// Get the level of a roll of `value` against `target`, succeeding at or under it. Half and
+    /// fifth of the target are rounded down.
+    pub fn roll_under(value: u64, target: u64) -> Self {
+        if value <= target / 5 {
+            SuccessLevel::Extreme
+        } else if value <= target / 2 {
+            SuccessLevel::Hard
+        } else if value <= target {
+            SuccessLevel::Regular
+        } else {
+            SuccessLevel::Failure
+        }
+    }
+
+    /// Says if the level is a success, whatever its degree
+    pub fn is_success(self) -> bool {
+        self != SuccessLevel::Failure
+    }
+}
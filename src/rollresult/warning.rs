@@ -0,0 +1,19 @@
+/// A change made to a result to complete the roll, which didn't fail it, see
+/// [`super::RollResult::warnings()`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Warning {
+    /// The total was lowered to the cap given with [`crate::Roller::with_total_cap()`]
+    TotalCapped,
+    /// The total was raised to the floor given with [`crate::Roller::with_total_floor()`]
+    TotalFloored,
+    /// A sum was too big and was kept at the closest value it can have
+    OverflowSaturated,
+    /// Indefinite rerolls were stopped because the dice source was exhausted, see
+    /// [`crate::DiceRollSource::is_exhausted()`]
+    RerollCapReached,
+    /// Explosions were stopped because the dice source was exhausted, see
+    /// [`crate::DiceRollSource::is_exhausted()`], or because the group added as many dice as
+    /// allowed by [`crate::Roller::with_max_explosion_percent()`]
+    ExplosionCapReached,
+}
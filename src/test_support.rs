@@ -0,0 +1,100 @@
+//! Helpers to test code using `caith` with deterministic dice.
+//!
+//! Only available with the `test-support` feature.
+//!
+//! ```
+//! use caith::{test_support::{assert_total, IteratorDiceRollSource}, Roller};
+//!
+//! let roller = Roller::new("2d6 + 3").unwrap();
+//! assert_total(&roller, &[4, 5], 12);
+//!
+//! let mut values = vec![6, 1].into_iter();
+//! let res = roller
+//!     .roll_with_source(&mut IteratorDiceRollSource::new(&mut values))
+//!     .unwrap();
+//! assert_eq!(10, res.as_single().unwrap().get_total());
+//! ```
+
+use crate::{DiceRollSource, Roller};
+
+/// A [`DiceRollSource`] giving the values of an iterator, in order.
+///
+/// # Panics
+///
+/// Panics if the iterator runs out of values or if a value is bigger than the number of sides of
+/// the rolled dice.
+pub struct IteratorDiceRollSource<'a, T>
+where
+    T: Iterator<Item = u64>,
+{
+    /// The iterator providing the dice values
+    pub iterator: &'a mut T,
+}
+
+impl<'a, T> IteratorDiceRollSource<'a, T>
+where
+    T: Iterator<Item = u64>,
+{
+    /// Create a source from an iterator of dice values
+    pub fn new(iterator: &'a mut T) -> Self {
+        IteratorDiceRollSource { iterator }
+    }
+}
+
+impl<T> DiceRollSource for IteratorDiceRollSource<'_, T>
+where
+    T: Iterator<Item = u64>,
+{
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        match self.iterator.next() {
+            Some(value) => {
+                if value > sides {
+                    panic!("Tried to return {} for a {} sided dice", value, sides)
+                }
+                value
+            }
+            None => panic!("Iterator out of values"),
+        }
+    }
+}
+
+/// Roll `roller` with the dice giving `values` in order, and assert the total is `expected`.
+///
+/// # Panics
+///
+/// Panics if the roll fails, if it's a repeated roll or if the total is not the expected one.
+pub fn assert_total(roller: &Roller, values: &[u64], expected: i64) {
+    let mut iterator = values.iter().copied();
+    let res = roller
+        .roll_with_source(&mut IteratorDiceRollSource::new(&mut iterator))
+        .unwrap_or_else(|e| panic!("Rolling `{}` failed: {}", roller.as_str(), e));
+    let total = res
+        .as_single()
+        .unwrap_or_else(|| panic!("`{}` is not a single roll", roller.as_str()))
+        .get_total();
+    assert_eq!(
+        expected,
+        total,
+        "`{}` rolled with {:?}",
+        roller.as_str(),
+        values
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assert_total_test() {
+        let r = Roller::new("4d6 K3").unwrap();
+        assert_total(&r, &[1, 4, 6, 2], 12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn assert_total_mismatch_test() {
+        let r = Roller::new("1d6 + 1").unwrap();
+        assert_total(&r, &[3], 3);
+    }
+}